@@ -1,7 +1,6 @@
-use crate::collectors::balena_stats_collector::BalenaStatsCollector;
-use crate::collectors::raw_stats_to_json_str::stdout_lines_to_json_array;
-use crate::domain::ContainerStats;
-use crate::parsers::balena_stats_json_parsers::parse;
+use crate::collectors::balena_stats_collector::{parse_raw_stats, BalenaStatsCollector};
+use crate::collectors::balena_stats_collector_config::StatsFormat;
+use crate::domain::{CollectionSource, ContainerStats};
 use crate::COLLECTOR_CONFIG;
 use anyhow::anyhow;
 use std::process::Command;
@@ -9,52 +8,33 @@ use std::process::Command;
 pub struct BalenaStatsCliCollector;
 
 impl BalenaStatsCollector for BalenaStatsCliCollector {
-    fn collect(&self) -> anyhow::Result<Vec<ContainerStats>> {
-        let output_as_json_array = collect_raw_from_cli()?;
-        Ok(parse(&output_as_json_array))
+    fn collect(&mut self) -> anyhow::Result<Option<Vec<ContainerStats>>> {
+        let raw = collect_raw_from_cli(&COLLECTOR_CONFIG.format)?;
+        parse_raw_stats(&COLLECTOR_CONFIG.format, &raw).map(Some)
+    }
+
+    fn source(&self) -> CollectionSource {
+        CollectionSource::CLI
+    }
+}
+
+fn stats_args(format: &StatsFormat) -> Vec<&'static str> {
+    match format {
+        StatsFormat::DOCKER => vec!["stats", "--no-stream", "--format", "\"{{json .}}\""],
+        StatsFormat::PODMAN => vec!["stats", "--no-stream", "--format", "json"],
+        StatsFormat::CRICTL => vec!["stats", "-o", "json"],
     }
 }
 
-fn collect_raw_from_cli() -> anyhow::Result<String> {
+fn collect_raw_from_cli(format: &StatsFormat) -> anyhow::Result<String> {
     let output = Command::new(COLLECTOR_CONFIG.clone().cli_path)
-        .arg("stats")
-        .arg("--no-stream")
-        .arg("--format")
-        .arg("\"{{json .}}\"")
+        .args(stats_args(format))
         .output()?;
 
     if output.status.success() {
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let unquoted_lines = remove_line_quotes(stdout.trim())?;
-        stdout_lines_to_json_array(&unquoted_lines)
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
     } else {
         let stderr = String::from_utf8_lossy(&output.stderr);
         Err(anyhow!("{}", stderr))
     }
 }
-
-pub fn remove_line_quotes(stdout: &str) -> anyhow::Result<String> {
-    let lines = stdout.split('\n').collect::<Vec<&str>>();
-    let trimmed: Vec<&str> = lines
-        .iter()
-        .filter(|line| line.trim().len() > 0)
-        .map(|line| &line[1..line.len() - 1]) // remove outer quotes
-        .collect();
-    let joined = trimmed.join("\n");
-    Ok(joined)
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn should_remove_line_quotes() {
-        let stdout = include_str!("../../test-data/balena_cli_stats_stdout_quote_lines.txt");
-        let expected = include_str!("../../test-data/balena_stats_stdout.txt");
-
-        let actual = remove_line_quotes(stdout);
-
-        assert_eq!(actual.unwrap(), expected)
-    }
-}
\ No newline at end of file
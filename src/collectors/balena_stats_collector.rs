@@ -1,5 +1,80 @@
-use crate::domain::ContainerStats;
+use crate::collectors::balena_stats_collector_config::StatsFormat;
+use crate::collectors::raw_stats_to_json_str::stdout_lines_to_json_array;
+use crate::domain::{CollectionSource, ContainerStats, SourceStatus};
+use crate::parsers::{balena_stats_json_parsers, crictl_stats_json_parsers, podman_stats_json_parsers};
+use chrono::{DateTime, Utc};
 
 pub trait BalenaStatsCollector {
-    fn collect(&self) -> anyhow::Result<Vec<ContainerStats>>;
-}
\ No newline at end of file
+    /// Current stats; `None` if no new stats are available yet.
+    fn collect(&mut self) -> anyhow::Result<Option<Vec<ContainerStats>>>;
+
+    fn source(&self) -> CollectionSource;
+
+    /// Time the last collected stats refer to; `None` if the source does not tell, i.e. they are current.
+    fn stats_timestamp(&self) -> Option<DateTime<Utc>> {
+        None
+    }
+
+    /// Status of the source as of the last collection; `None` if not tracked.
+    fn source_status(&self) -> Option<SourceStatus> {
+        None
+    }
+}
+
+/// Parses raw stats output of the given format; Docker's lines may be quoted or not.
+pub fn parse_raw_stats(format: &StatsFormat, raw: &str) -> anyhow::Result<Vec<ContainerStats>> {
+    let raw = raw.trim_start_matches('\u{feff}');
+    match format {
+        StatsFormat::DOCKER => balena_stats_json_parsers::parse(&stdout_lines_to_json_array(raw.trim())?),
+        StatsFormat::PODMAN => podman_stats_json_parsers::parse(raw),
+        StatsFormat::CRICTL => crictl_stats_json_parsers::parse(raw),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use rstest::rstest;
+    use serde_json::Value;
+
+    #[rstest]
+    #[case::docker(StatsFormat::DOCKER, include_str!("../../test-data/balena_stats_stdout.txt"), 3)]
+    #[case::podman(StatsFormat::PODMAN, include_str!("../../test-data/podman_stats.json"), 2)]
+    #[case::crictl(StatsFormat::CRICTL, include_str!("../../test-data/crictl_stats.json"), 2)]
+    fn should_parse_raw_stats_of_format(#[case] format: StatsFormat, #[case] raw: &str, #[case] expected: usize) {
+        let actual = parse_raw_stats(&format, raw).unwrap();
+
+        assert_eq!(actual.len(), expected);
+    }
+
+    proptest! {
+        #[test]
+        fn should_never_panic_on_docker_output(stdout in any::<String>()) {
+            prop_assert!(parse_raw_stats(&StatsFormat::DOCKER, &stdout).is_ok());
+        }
+
+        #[test]
+        fn should_never_panic_on_docker_objects(
+            lines in prop::collection::vec(
+                prop::collection::btree_map(
+                    prop::sample::select(vec!["BlockIO", "CPUPerc", "Container", "ID", "MemPerc", "MemUsage", "Name",
+                        "NetIO", "PIDs"]),
+                    prop_oneof![any::<String>().prop_map(Value::from), any::<i64>().prop_map(Value::from)],
+                    0..10,
+                ),
+                0..8,
+            ),
+        ) {
+            let stdout: String = lines.iter().map(|line| serde_json::to_string(line).unwrap() + "\n").collect();
+
+            prop_assert!(parse_raw_stats(&StatsFormat::DOCKER, &stdout).unwrap().len() <= lines.len());
+        }
+
+        #[test]
+        fn should_never_panic_on_podman_or_crictl_output(stdout in any::<String>()) {
+            let _ = parse_raw_stats(&StatsFormat::PODMAN, &stdout);
+            let _ = parse_raw_stats(&StatsFormat::CRICTL, &stdout);
+        }
+    }
+}
@@ -1,21 +1,175 @@
 use crate::util::config::{build_path, get_config, CONFIG_DIR};
-use serde;
 use serde::Deserialize;
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(PartialEq, Deserialize, Clone)]
 pub enum CollectorType {
     CLI,
     FILE,
+    /// Docker Engine API on `socket_path`.
+    SOCKET,
+}
+
+/// Output format of the stats command, or of the file in FILE mode.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(PartialEq, Deserialize, Clone, Debug, Default)]
+pub enum StatsFormat {
+    /// `docker`/`balena-engine stats --no-stream --format "{{json .}}"`: one JSON object per line.
+    #[default]
+    DOCKER,
+    /// `podman stats --no-stream --format json`: one JSON array.
+    PODMAN,
+    /// `crictl stats -o json`: one JSON object with a `stats` array.
+    CRICTL,
+}
+
+/// How FILE mode reads `file_path`.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(PartialEq, Deserialize, Clone, Debug, Default)]
+pub enum FileReadMode {
+    /// The whole file is the current collection; for files overwritten on every update.
+    #[default]
+    FULL,
+    /// Only appended lines are read; for files continuously appended to.
+    TAIL,
+}
+
+/// What separates the collections of an appended file.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(PartialEq, Deserialize, Clone, Debug)]
+pub enum TailGroupBy {
+    /// A separator line after every collection.
+    SEPARATOR,
+    /// A timestamp line (RFC 3339 or unix seconds) before every collection.
+    TIMESTAMP,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct TailConfig {
+    #[serde(default = "default_group_by")]
+    pub group_by: TailGroupBy,
+    #[serde(default = "default_separator")]
+    pub separator: String,
+}
+
+impl Default for TailConfig {
+    fn default() -> Self {
+        TailConfig {
+            group_by: default_group_by(),
+            separator: default_separator(),
+        }
+    }
+}
+
+fn default_group_by() -> TailGroupBy {
+    TailGroupBy::SEPARATOR
+}
+
+fn default_separator() -> String {
+    "---".to_string()
+}
+
+/// Where the time of the last update of FILE mode data is taken from.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(PartialEq, Deserialize, Clone, Debug, Default)]
+pub enum StalenessSource {
+    /// The modification time of the file.
+    #[default]
+    MTIME,
+    /// The latest timestamp line (RFC 3339 or unix seconds) in the file, e.g. written by `date -Iseconds`.
+    TIMESTAMP,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct StalenessConfig {
+    pub max_age_in_seconds: u64,
+    #[serde(default)]
+    pub source: StalenessSource,
+}
+
+/// How containers are inspected for their state, health and restarts, which stats don't show.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(PartialEq, Deserialize, Clone, Copy, Debug)]
+pub enum InspectMode {
+    /// `{cli_path} inspect` of all containers at once.
+    CLI,
+    /// Docker Engine API on `socket_path`.
+    SOCKET,
+}
+
+/// Disk usage of containers from the Docker Engine API on `socket_path`.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct DiskUsageConfig {
+    #[serde(default = "default_disk_usage_interval_in_seconds")]
+    pub interval_in_seconds: u64,
+}
+
+/// Top processes of every container from its cgroup and the host's `/proc`.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct TopProcessesConfig {
+    #[serde(default = "default_top_processes_count")]
+    pub count: usize,
+    #[serde(default = "default_top_processes_interval_in_seconds")]
+    pub interval_in_seconds: u64,
 }
 
 #[derive(Deserialize, Clone)]
 pub struct BalenaStatsCollectorConfig {
     pub mode: CollectorType,
+    #[serde(default)]
+    pub format: StatsFormat,
     pub cli_path: String,
+    #[serde(default = "default_socket_path")]
+    pub socket_path: String,
+    /// Per-interface network counters in SOCKET mode; off by default.
+    #[serde(default)]
+    pub network_interfaces: bool,
     pub file_path: String,
+    #[serde(default)]
+    pub file_read_mode: FileReadMode,
+    #[serde(default)]
+    pub tail: TailConfig,
+    pub staleness: Option<StalenessConfig>,
+    /// `None` disables inspection.
+    pub inspect_mode: Option<InspectMode>,
+    /// `None` disables disk usage collection.
+    pub disk_usage: Option<DiskUsageConfig>,
+    /// `None` disables top processes.
+    pub top_processes: Option<TopProcessesConfig>,
+    /// Host's `/proc` for the CPU core count.
+    #[serde(default = "default_proc_path")]
+    pub proc_path: String,
+    /// Root of the host's cgroup hierarchy for container limits, e.g. `/host/sys/fs/cgroup`.
+    #[serde(default = "default_cgroup_path")]
+    pub cgroup_path: String,
     pub collection_interval_in_seconds: u64,
 }
 
+fn default_socket_path() -> String {
+    "/var/run/docker.sock".to_string()
+}
+
+// Calculating sizes walks all files of the writable layers and volumes.
+fn default_disk_usage_interval_in_seconds() -> u64 {
+    3600
+}
+
+fn default_top_processes_count() -> usize {
+    5
+}
+
+fn default_top_processes_interval_in_seconds() -> u64 {
+    60
+}
+
+fn default_proc_path() -> String {
+    "/proc".to_string()
+}
+
+fn default_cgroup_path() -> String {
+    "/sys/fs/cgroup".to_string()
+}
+
 pub fn get_collector_config() -> BalenaStatsCollectorConfig {
     get_config(build_path(vec![&CONFIG_DIR, "balena_stats_collector.config.json"]))
 }
\ No newline at end of file
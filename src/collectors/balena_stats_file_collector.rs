@@ -1,20 +1,181 @@
-use crate::collectors::balena_stats_collector::BalenaStatsCollector;
-use crate::collectors::raw_stats_to_json_str::stdout_lines_to_json_array;
-use crate::domain::ContainerStats;
-use crate::parsers::balena_stats_json_parsers::parse;
-use crate::COLLECTOR_CONFIG;
+use crate::collectors::balena_stats_collector::{parse_raw_stats, BalenaStatsCollector};
+use crate::collectors::balena_stats_collector_config::{
+    BalenaStatsCollectorConfig, FileReadMode, StalenessSource, StatsFormat,
+};
+use crate::collectors::file_tail::FileTail;
+use crate::collectors::staleness::{split_off_timestamps, StalenessCheck};
+use crate::domain::{CollectionSource, ContainerStats, SourceStatus};
+use chrono::{DateTime, Utc};
 use std::fs;
+use std::path::{Path, PathBuf};
 
-pub struct BalenaStatsFileCollector;
+pub struct BalenaStatsFileCollector {
+    file_path: PathBuf,
+    format: StatsFormat,
+    tail: Option<FileTail>,
+    staleness: Option<(StalenessSource, StalenessCheck)>,
+    source_status: Option<SourceStatus>,
+    stats_timestamp: Option<DateTime<Utc>>,
+}
+
+impl BalenaStatsFileCollector {
+    pub fn new(config: &BalenaStatsCollectorConfig) -> Self {
+        let file_path = PathBuf::from(&config.file_path);
+        let tail = match config.file_read_mode {
+            FileReadMode::FULL => None,
+            FileReadMode::TAIL => Some(FileTail::new(file_path.clone(), config.tail.clone())),
+        };
+        let staleness = config
+            .staleness
+            .as_ref()
+            .map(|staleness| (staleness.source.clone(), StalenessCheck::new(staleness)));
+        BalenaStatsFileCollector {
+            file_path,
+            format: config.format.clone(),
+            tail,
+            staleness,
+            source_status: None,
+            stats_timestamp: None,
+        }
+    }
+}
 
 impl BalenaStatsCollector for BalenaStatsFileCollector {
-    fn collect(&self) -> anyhow::Result<Vec<ContainerStats>> {
-        let output_as_json_array = collect_from_file()?;
-        Ok(parse(&output_as_json_array))
+    /// No stats are returned while the file is stale.
+    fn collect(&mut self) -> anyhow::Result<Option<Vec<ContainerStats>>> {
+        let contents = match &mut self.tail {
+            None => Some(fs::read_to_string(&self.file_path)?),
+            Some(tail) => tail.read_latest_group()?.map(|lines| lines.join("\n")),
+        };
+        let (timestamp, stats) = match contents {
+            Some(contents) => {
+                let (timestamp, raw) = split_off_timestamps(&contents);
+                (timestamp, Some(parse_raw_stats(&self.format, &raw)?))
+            }
+            None => (None, None),
+        };
+        if stats.is_some() {
+            self.stats_timestamp = timestamp.or_else(|| modified(&self.file_path));
+        }
+
+        let Some((source, check)) = &mut self.staleness else {
+            return Ok(stats);
+        };
+        let last_update = match source {
+            StalenessSource::MTIME => modified(&self.file_path),
+            StalenessSource::TIMESTAMP => timestamp,
+        };
+        if let Some(last_update) = last_update {
+            check.update(last_update);
+        }
+        let status = check.status(Utc::now());
+        let stale = status.stale;
+        self.source_status = Some(status);
+        Ok(stats.filter(|_| !stale))
+    }
+
+    fn source(&self) -> CollectionSource {
+        CollectionSource::FILE
+    }
+
+    fn source_status(&self) -> Option<SourceStatus> {
+        self.source_status.clone()
+    }
+
+    /// The latest timestamp line of the collected stats, otherwise the modification time of the file.
+    fn stats_timestamp(&self) -> Option<DateTime<Utc>> {
+        self.stats_timestamp
     }
 }
 
-fn collect_from_file() -> anyhow::Result<String> {
-    let contents = fs::read_to_string(COLLECTOR_CONFIG.clone().file_path)?;
-    stdout_lines_to_json_array(contents.trim())
+// The file may be missing while rotated in TAIL mode; the last known update counts then.
+fn modified(path: &Path) -> Option<DateTime<Utc>> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .map(DateTime::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collectors::balena_stats_collector_config::{CollectorType, StalenessConfig, TailConfig};
+    use crate::util::test_support::temp_dir;
+    use chrono::TimeDelta;
+
+    fn setup_collector(test_name: &str, contents: &str) -> BalenaStatsFileCollector {
+        let dir = temp_dir(&format!("file_collector_{}", test_name));
+        let file_path = dir.join("stats.txt");
+        fs::write(&file_path, contents).unwrap();
+        BalenaStatsFileCollector::new(&BalenaStatsCollectorConfig {
+            mode: CollectorType::FILE,
+            format: StatsFormat::DOCKER,
+            cli_path: "docker".to_string(),
+            socket_path: "/var/run/docker.sock".to_string(),
+            network_interfaces: false,
+            file_path: file_path.to_string_lossy().to_string(),
+            file_read_mode: FileReadMode::FULL,
+            tail: TailConfig::default(),
+            staleness: Some(StalenessConfig {
+                max_age_in_seconds: 60,
+                source: StalenessSource::TIMESTAMP,
+            }),
+            inspect_mode: None,
+            disk_usage: None,
+            top_processes: None,
+            proc_path: "/proc".to_string(),
+            cgroup_path: "/sys/fs/cgroup".to_string(),
+            collection_interval_in_seconds: 15,
+        })
+    }
+
+    fn with_timestamp(age: TimeDelta) -> String {
+        format!(
+            "{}\n{}",
+            (Utc::now() - age).to_rfc3339(),
+            include_str!("../../test-data/balena_stats_stdout.txt")
+        )
+    }
+
+    #[test]
+    fn should_collect_current_stats() {
+        let mut collector = setup_collector("current", &with_timestamp(TimeDelta::seconds(5)));
+
+        let actual = collector.collect().unwrap();
+
+        assert_eq!(actual.map(|stats| stats.len()), Some(3));
+        assert_eq!(collector.source_status().map(|status| status.stale), Some(false));
+    }
+
+    #[test]
+    fn should_take_stats_timestamp_from_timestamp_line() {
+        let timestamp = Utc::now() - TimeDelta::seconds(5);
+        let contents = format!(
+            "{}\n{}",
+            timestamp.to_rfc3339(),
+            include_str!("../../test-data/balena_stats_stdout.txt")
+        );
+        let mut collector = setup_collector("stats_timestamp", &contents);
+
+        collector.collect().unwrap();
+
+        assert_eq!(collector.stats_timestamp(), Some(timestamp));
+    }
+
+    #[test]
+    fn should_not_collect_stale_stats() {
+        let mut collector = setup_collector("stale", &with_timestamp(TimeDelta::minutes(5)));
+
+        let actual = collector.collect().unwrap();
+
+        assert_eq!(actual, None);
+        assert_eq!(collector.source_status().map(|status| status.stale), Some(true));
+    }
+
+    #[test]
+    fn should_treat_missing_timestamp_as_stale() {
+        let mut collector = setup_collector("missing_timestamp", include_str!("../../test-data/balena_stats_stdout.txt"));
+
+        assert_eq!(collector.collect().unwrap(), None);
+    }
 }
@@ -0,0 +1,100 @@
+use crate::collectors::balena_stats_collector::BalenaStatsCollector;
+use crate::collectors::balena_stats_collector_config::BalenaStatsCollectorConfig;
+use crate::collectors::docker_socket::DockerSocketClient;
+use crate::domain::{CollectionSource, ContainerStats};
+use crate::parsers::docker_api_stats_parsers::{self, ContainerSummary};
+use crate::util::parallel::map_in_parallel;
+use std::path::PathBuf;
+use std::time::Duration;
+
+// Stats of a container take about a second, as the engine samples the CPU usage.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Collects from the engine API instead of the CLI; byte values are exact and labels are known.
+pub struct BalenaStatsSocketCollector {
+    client: DockerSocketClient,
+    network_interfaces: bool,
+}
+
+impl BalenaStatsSocketCollector {
+    pub fn new(config: &BalenaStatsCollectorConfig) -> Self {
+        BalenaStatsSocketCollector {
+            client: DockerSocketClient::new(PathBuf::from(&config.socket_path), REQUEST_TIMEOUT),
+            network_interfaces: config.network_interfaces,
+        }
+    }
+
+    fn collect_container(&self, container: &ContainerSummary) -> anyhow::Result<ContainerStats> {
+        let stats = self.client.get(&format!("/containers/{}/stats?stream=false", container.id))?;
+        let mut stats = docker_api_stats_parsers::parse(container, &stats)?;
+        if !self.network_interfaces {
+            stats.network_interfaces.clear();
+        }
+        Ok(stats)
+    }
+}
+
+impl BalenaStatsCollector for BalenaStatsSocketCollector {
+    fn collect(&mut self) -> anyhow::Result<Option<Vec<ContainerStats>>> {
+        let containers = docker_api_stats_parsers::parse_containers(&self.client.get("/containers/json")?)?;
+
+        // Requested in parallel like `docker stats` does; containers stopped in between are skipped.
+        let stats = map_in_parallel(
+            &containers,
+            |container| self.collect_container(container),
+            |container| format!("Could not collect stats of container {}", container.id),
+        );
+        Ok(Some(stats))
+    }
+
+    fn source(&self) -> CollectionSource {
+        CollectionSource::SOCKET
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collectors::balena_stats_collector_config::{CollectorType, FileReadMode, StatsFormat, TailConfig};
+    use crate::util::test_support::serve_unix_socket;
+
+    #[test]
+    fn should_collect_exact_stats_of_all_containers() {
+        // The supervisor's stats are not served, like for a container stopped in between.
+        let (socket_path, server) = serve_unix_socket(
+            "socket_collector",
+            3,
+            vec![
+                ("/containers/json", include_str!("../../test-data/docker_api_containers.json")),
+                (
+                    "/containers/4889ab0711ac17500a5de1c3d50eb6bfbc7eb367d349797f9fe8dcb92fd9e914/stats?stream=false",
+                    include_str!("../../test-data/docker_api_stats.json"),
+                ),
+            ],
+        );
+        let mut collector = BalenaStatsSocketCollector::new(&BalenaStatsCollectorConfig {
+            mode: CollectorType::SOCKET,
+            format: StatsFormat::DOCKER,
+            cli_path: "docker".to_string(),
+            socket_path: socket_path.to_string_lossy().to_string(),
+            network_interfaces: true,
+            file_path: "".to_string(),
+            file_read_mode: FileReadMode::FULL,
+            tail: TailConfig::default(),
+            staleness: None,
+            inspect_mode: None,
+            disk_usage: None,
+            top_processes: None,
+            proc_path: "/proc".to_string(),
+            cgroup_path: "/sys/fs/cgroup".to_string(),
+            collection_interval_in_seconds: 15,
+        });
+
+        let actual = collector.collect().unwrap().unwrap();
+        server.join().unwrap();
+
+        assert_eq!(actual.len(), 1);
+        assert_eq!(actual[0].service_name, "b");
+        assert_eq!(actual[0].network_interfaces.len(), 2);
+    }
+}
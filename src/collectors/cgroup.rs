@@ -0,0 +1,257 @@
+use crate::domain::{Pressure, PressureStats};
+use glob::Pattern;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Finds the cgroup directory of a container in the v1 and v2 layouts of docker, balena-engine and podman.
+pub(crate) struct CgroupResolver {
+    root: PathBuf,
+    // Misses are cached as well.
+    cache: HashMap<(String, &'static str), Option<PathBuf>>,
+}
+
+impl CgroupResolver {
+    pub(crate) fn new(root: PathBuf) -> Self {
+        CgroupResolver {
+            root,
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Directory in the v1 hierarchy of `controller` if mounted, otherwise in the unified v2 hierarchy.
+    pub(crate) fn find(&mut self, container_id: &str, controller: &'static str) -> Option<&Path> {
+        let root = &self.root;
+        self.cache
+            .entry((container_id.to_string(), controller))
+            .or_insert_with(|| find_dir(root, container_id, controller))
+            .as_deref()
+    }
+
+    /// Forgets containers not in `container_ids`.
+    pub(crate) fn retain(&mut self, container_ids: &[&str]) {
+        self.cache.retain(|(id, _), _| container_ids.contains(&id.as_str()));
+    }
+}
+
+fn find_dir(root: &Path, container_id: &str, controller: &str) -> Option<PathBuf> {
+    if container_id.is_empty() {
+        return None;
+    }
+    let v1_root = root.join(controller);
+    let base = if v1_root.is_dir() { v1_root } else { root.to_path_buf() };
+    let base = Pattern::escape(&base.to_string_lossy());
+    let id = Pattern::escape(container_id);
+    [format!("{}/*/{}*", base, id), format!("{}/*/*-{}*.scope", base, id)]
+        .iter()
+        .filter_map(|pattern| glob::glob(pattern).ok())
+        .flatten()
+        .filter_map(Result::ok)
+        .find(|path| path.is_dir())
+}
+
+/// CPU limit in cores from `cpu.max` (v2) or `cpu.cfs_quota_us`/`cpu.cfs_period_us` (v1); `None` if unlimited.
+pub(crate) fn read_cpu_quota(dir: &Path) -> Option<f32> {
+    let (quota, period) = match fs::read_to_string(dir.join("cpu.max")) {
+        Ok(max) => {
+            let (quota, period) = max.trim().split_once(' ')?;
+            (quota.parse::<f32>().ok()?, period.parse::<f32>().ok()?)
+        }
+        Err(_) => (
+            read_number(&dir.join("cpu.cfs_quota_us"))?,
+            read_number(&dir.join("cpu.cfs_period_us"))?,
+        ),
+    };
+    // v1 marks unlimited quotas with -1, v2 with `max`, which fails to parse above.
+    (quota > 0.0 && period > 0.0).then(|| quota / period)
+}
+
+fn read_number(path: &Path) -> Option<f32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Memory of a container's cgroup by kind; values the kernel doesn't account for are `None`.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct MemoryBreakdown {
+    /// Anonymous memory (heap, stacks), called RSS on v1.
+    pub(crate) anon_in_bytes: Option<u64>,
+    /// Page cache, called cache on v1.
+    pub(crate) file_in_bytes: Option<u64>,
+    pub(crate) swap_in_bytes: Option<u64>,
+    /// Usage without inactive page cache.
+    pub(crate) working_set_in_bytes: Option<u64>,
+    /// Times the limit was reached and reclaim failed; v2 only.
+    pub(crate) oom_events: Option<u64>,
+    pub(crate) oom_kill_events: Option<u64>,
+}
+
+/// From the memory files of the cgroup; `None` if the memory controller is not accessible.
+pub(crate) fn read_memory(dir: &Path) -> Option<MemoryBreakdown> {
+    let stat = read_key_values(&dir.join("memory.stat"))?;
+    let v2 = stat.contains_key("anon");
+    let (usage, events) = match v2 {
+        true => (read_u64(&dir.join("memory.current")), read_key_values(&dir.join("memory.events"))),
+        false => (read_u64(&dir.join("memory.usage_in_bytes")), read_key_values(&dir.join("memory.oom_control"))),
+    };
+    // v1 has `total_*` values including sub-cgroups; containers rarely have any.
+    let v1_value = |key: &str| stat.get(&format!("total_{}", key)).or_else(|| stat.get(key)).copied();
+    let inactive_file = match v2 {
+        true => stat.get("inactive_file").copied(),
+        false => v1_value("inactive_file"),
+    };
+    let events = events.unwrap_or_default();
+
+    Some(MemoryBreakdown {
+        anon_in_bytes: if v2 { stat.get("anon").copied() } else { v1_value("rss") },
+        file_in_bytes: if v2 { stat.get("file").copied() } else { v1_value("cache") },
+        // Without swap accounting, v1 has no swap value and v2 no `memory.swap.current`.
+        swap_in_bytes: if v2 { read_u64(&dir.join("memory.swap.current")) } else { v1_value("swap") },
+        working_set_in_bytes: usage.map(|usage| usage.saturating_sub(inactive_file.unwrap_or_default())),
+        oom_events: if v2 { events.get("oom").copied() } else { None },
+        oom_kill_events: events.get("oom_kill").copied(),
+    })
+}
+
+/// From a pressure file like `cpu.pressure`; `None` on cgroup v1 and kernels without PSI.
+pub(crate) fn read_pressure(path: &Path) -> Option<PressureStats> {
+    let content = fs::read_to_string(path).ok()?;
+    // Lines like `some avg10=1.25 avg60=0.50 avg300=0.10 total=123456`.
+    let mut lines: BTreeMap<&str, Pressure> = content
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .filter_map(|(kind, values)| Some((kind, parse_pressure(values)?)))
+        .collect();
+    Some(PressureStats {
+        some: lines.remove("some")?,
+        full: lines.remove("full"),
+    })
+}
+
+fn parse_pressure(values: &str) -> Option<Pressure> {
+    let values: BTreeMap<&str, &str> = values.split_whitespace().filter_map(|pair| pair.split_once('=')).collect();
+    Some(Pressure {
+        avg10_in_percent: values.get("avg10")?.parse().ok()?,
+        avg60_in_percent: values.get("avg60")?.parse().ok()?,
+        avg300_in_percent: values.get("avg300")?.parse().ok()?,
+        total_in_microseconds: values.get("total")?.parse().ok()?,
+    })
+}
+
+fn read_u64(path: &Path) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+// Lines like `anon 104857600`.
+fn read_key_values(path: &Path) -> Option<BTreeMap<String, u64>> {
+    let content = fs::read_to_string(path).ok()?;
+    Some(
+        content
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .filter_map(|(key, value)| Some((key.to_string(), value.trim().parse().ok()?)))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::v2_systemd("test-data/cgroup/v2", "0c05c278da1f", "system.slice/docker-0c05c278da1f")]
+    #[case::v2_cgroupfs("test-data/cgroup/v2", "4d6f35b38ac9", "docker/4d6f35b38ac9")]
+    #[case::v1("test-data/cgroup/v1", "0c05c278da1f", "cpu/docker/0c05c278da1f")]
+    fn should_find_cgroup_dir(#[case] root: &str, #[case] container_id: &str, #[case] expected_prefix: &str) {
+        let mut resolver = CgroupResolver::new(PathBuf::from(root));
+
+        let actual = resolver.find(container_id, "cpu").unwrap();
+
+        assert!(actual.starts_with(root));
+        assert!(actual.to_string_lossy().contains(expected_prefix), "{:?}", actual);
+    }
+
+    #[test]
+    fn should_not_find_unknown_or_empty_container_ids() {
+        let mut resolver = CgroupResolver::new(PathBuf::from("test-data/cgroup/v2"));
+
+        assert_eq!(resolver.find("ffffffffffff", "cpu"), None);
+        assert_eq!(resolver.find("", "cpu"), None);
+    }
+
+    #[rstest]
+    #[case::v2_limited("test-data/cgroup/v2", "0c05c278da1f", Some(1.5))]
+    #[case::v2_unlimited("test-data/cgroup/v2", "4d6f35b38ac9", None)]
+    #[case::v1_limited("test-data/cgroup/v1", "0c05c278da1f", Some(0.5))]
+    #[case::v1_unlimited("test-data/cgroup/v1", "4d6f35b38ac9", None)]
+    fn should_read_cpu_quota(#[case] root: &str, #[case] container_id: &str, #[case] expected: Option<f32>) {
+        let mut resolver = CgroupResolver::new(PathBuf::from(root));
+
+        let actual = read_cpu_quota(resolver.find(container_id, "cpu").unwrap());
+
+        assert_eq!(actual, expected);
+    }
+
+    #[rstest]
+    #[case::v2("test-data/cgroup/v2", Some(3))]
+    #[case::v1("test-data/cgroup/v1", None)]
+    fn should_read_memory_breakdown(#[case] root: &str, #[case] expected_oom_events: Option<u64>) {
+        let mut resolver = CgroupResolver::new(PathBuf::from(root));
+
+        let actual = read_memory(resolver.find("0c05c278da1f", "memory").unwrap());
+
+        assert_eq!(
+            actual,
+            Some(MemoryBreakdown {
+                anon_in_bytes: Some(104_857_600),
+                file_in_bytes: Some(52_428_800),
+                swap_in_bytes: Some(8_388_608),
+                working_set_in_bytes: Some(136_314_880),
+                oom_events: expected_oom_events,
+                oom_kill_events: Some(1),
+            })
+        );
+    }
+
+    #[test]
+    fn should_read_pressure() {
+        let mut resolver = CgroupResolver::new(PathBuf::from("test-data/cgroup/v2"));
+        let dir = resolver.find("0c05c278da1f", "cpu").unwrap();
+
+        let actual = read_pressure(&dir.join("cpu.pressure"));
+
+        assert_eq!(
+            actual,
+            Some(PressureStats {
+                some: Pressure {
+                    avg10_in_percent: 12.5,
+                    avg60_in_percent: 8.25,
+                    avg300_in_percent: 4.0,
+                    total_in_microseconds: 123_456_789,
+                },
+                full: Some(Pressure {
+                    avg10_in_percent: 3.1,
+                    avg60_in_percent: 2.0,
+                    avg300_in_percent: 1.0,
+                    total_in_microseconds: 23_456_789,
+                }),
+            })
+        );
+    }
+
+    #[test]
+    fn should_read_pressure_without_full_line_and_not_without_psi() {
+        let actual = read_pressure(Path::new("test-data/proc/pressure/cpu")).unwrap();
+
+        assert_eq!(actual.some.total_in_microseconds, 987_654_321);
+        assert_eq!(actual.full, None);
+        assert_eq!(read_pressure(Path::new("test-data/cgroup/v1/cpu/docker/cpu.pressure")), None);
+    }
+
+    #[test]
+    fn should_not_read_memory_without_memory_controller() {
+        let mut resolver = CgroupResolver::new(PathBuf::from("test-data/cgroup/v2"));
+
+        assert_eq!(read_memory(resolver.find("4d6f35b38ac9", "memory").unwrap()), None);
+    }
+}
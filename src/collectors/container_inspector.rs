@@ -0,0 +1,129 @@
+use crate::collectors::balena_stats_collector_config::{BalenaStatsCollectorConfig, InspectMode};
+use crate::collectors::docker_socket::DockerSocketClient;
+use crate::domain::ContainerStats;
+use crate::parsers::docker_inspect_parsers::{self, ContainerInspection};
+use crate::util::parallel::map_in_parallel;
+use chrono::{DateTime, Utc};
+use log::warn;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Adds state, health, restarts and uptime of the engine's view of each container.
+pub struct ContainerInspector {
+    mode: InspectMode,
+    cli_path: String,
+    client: DockerSocketClient,
+}
+
+impl ContainerInspector {
+    pub fn new(mode: InspectMode, config: &BalenaStatsCollectorConfig) -> Self {
+        ContainerInspector {
+            mode,
+            cli_path: config.cli_path.clone(),
+            client: DockerSocketClient::new(PathBuf::from(&config.socket_path), REQUEST_TIMEOUT),
+        }
+    }
+
+    /// Containers which can't be inspected keep their inspection fields unavailable.
+    pub fn inspect(&self, stats: &mut [ContainerStats]) {
+        if stats.is_empty() {
+            return;
+        }
+        let container_ids: Vec<&str> = stats.iter().map(ContainerStats::id).collect();
+        let inspections = match self.mode {
+            InspectMode::CLI => self.inspect_with_cli(&container_ids),
+            InspectMode::SOCKET => self.inspect_with_socket(&container_ids),
+        };
+        apply_inspections(stats, &inspections, Utc::now());
+    }
+
+    fn inspect_with_cli(&self, container_ids: &[&str]) -> Vec<ContainerInspection> {
+        let output = match Command::new(&self.cli_path)
+            .args(["inspect", "--type", "container"])
+            .args(container_ids)
+            .output()
+        {
+            Ok(output) => output,
+            Err(err) => {
+                warn!("Could not run {} inspect: {}", self.cli_path, err);
+                return vec![];
+            }
+        };
+        // Fails if any container is gone, but still prints the others.
+        if !output.status.success() {
+            warn!("{} inspect failed: {}", self.cli_path, String::from_utf8_lossy(&output.stderr).trim());
+        }
+        docker_inspect_parsers::parse_inspections(&String::from_utf8_lossy(&output.stdout)).unwrap_or_else(|err| {
+            warn!("Could not parse {} inspect output: {}", self.cli_path, err);
+            vec![]
+        })
+    }
+
+    fn inspect_with_socket(&self, container_ids: &[&str]) -> Vec<ContainerInspection> {
+        map_in_parallel(
+            container_ids,
+            |id| {
+                self.client
+                    .get(&format!("/containers/{}/json", id))
+                    .and_then(|json| docker_inspect_parsers::parse_inspection(&json))
+            },
+            |id| format!("Could not inspect container {}", id),
+        )
+    }
+}
+
+fn apply_inspections(stats: &mut [ContainerStats], inspections: &[ContainerInspection], now: DateTime<Utc>) {
+    for stat in stats.iter_mut() {
+        let id = stat.id();
+        let Some(inspection) = inspections.iter().find(|inspection| !id.is_empty() && inspection.id.starts_with(id))
+        else {
+            continue;
+        };
+        stat.state = Some(inspection.state.clone());
+        stat.health_status = inspection.health_status.clone();
+        stat.restart_count = Some(inspection.restart_count);
+        stat.started_at = inspection.started_at;
+        stat.uptime_in_seconds = inspection
+            .started_at
+            .filter(|_| inspection.running)
+            .map(|started_at| (now - started_at).num_seconds().max(0) as u64);
+        stat.exit_code = Some(inspection.exit_code);
+        stat.oom_killed = Some(inspection.oom_killed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_stats(container_id_short: &str) -> ContainerStats {
+        ContainerStats {
+            container_id_short: container_id_short.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn should_apply_inspections_by_id_prefix() {
+        let inspections = docker_inspect_parsers::parse_inspections(include_str!("../../test-data/docker_inspect.json"))
+            .unwrap();
+        let mut stats = vec![setup_stats("4889ab0711ac"), setup_stats("0c05c278da1f"), setup_stats("ffffffffffff")];
+        let now = DateTime::parse_from_rfc3339("2025-03-01T12:00:30Z").unwrap().to_utc();
+
+        apply_inspections(&mut stats, &inspections, now);
+
+        assert_eq!(stats[0].state, Some("running".to_string()));
+        assert_eq!(stats[0].health_status, Some("unhealthy".to_string()));
+        assert_eq!(stats[0].restart_count, Some(2));
+        assert_eq!(stats[0].uptime_in_seconds, Some(3629));
+        assert_eq!(stats[0].exit_code, Some(137));
+        assert_eq!(stats[0].oom_killed, Some(true));
+        assert_eq!(stats[1].state, Some("created".to_string()));
+        assert_eq!(stats[1].uptime_in_seconds, None);
+        assert_eq!(stats[2].state, None);
+        assert_eq!(stats[2].restart_count, None);
+    }
+}
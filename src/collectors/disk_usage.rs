@@ -0,0 +1,115 @@
+use crate::collectors::docker_socket::DockerSocketClient;
+use crate::domain::{ContainerStats, DiskUsage};
+use crate::parsers::docker_api_df_parsers::{self, ContainerDiskUsage};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// Sizes are calculated on request by walking all files.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+// Disk usage of all containers and the time the engine answered.
+type DiskUsageResult = (DateTime<Utc>, Vec<ContainerDiskUsage>);
+
+/// Collects the disk usage of containers on its own, slower interval in the background.
+pub struct DiskUsageCollector {
+    // The latest result not yet collected.
+    latest: Arc<Mutex<Option<DiskUsageResult>>>,
+}
+
+impl DiskUsageCollector {
+    pub fn start(socket_path: &str, interval: Duration) -> Self {
+        let latest = Arc::new(Mutex::new(None));
+        let client = DockerSocketClient::new(PathBuf::from(socket_path), REQUEST_TIMEOUT);
+        let result = latest.clone();
+        thread::Builder::new()
+            .name("disk usage".to_string())
+            .spawn(move || loop {
+                // Older engines ignore the types and calculate images and build cache as well.
+                match client
+                    .get("/system/df?type=container&type=volume")
+                    .and_then(|json| docker_api_df_parsers::parse_disk_usage(&json))
+                {
+                    Ok(disk_usage) => {
+                        info!("Collected disk usage of {} containers.", disk_usage.len());
+                        *result.lock().unwrap() = Some((Utc::now(), disk_usage));
+                    }
+                    Err(err) => warn!("Could not collect disk usage: {}", err),
+                }
+                thread::sleep(interval);
+            })
+            .unwrap_or_else(|err| panic!("Could not spawn disk usage thread: {}", err));
+        DiskUsageCollector { latest }
+    }
+
+    /// `None` until the background thread has a new result; every result is collected once.
+    pub fn collect(&self, stats: &[ContainerStats]) -> Option<Vec<DiskUsage>> {
+        let (timestamp, disk_usage) = self.latest.lock().unwrap().take()?;
+        Some(match_disk_usage(stats, &disk_usage, timestamp))
+    }
+}
+
+// Containers created since the request are left out until the next result.
+fn match_disk_usage(
+    stats: &[ContainerStats],
+    disk_usage: &[ContainerDiskUsage],
+    timestamp: DateTime<Utc>,
+) -> Vec<DiskUsage> {
+    stats
+        .iter()
+        .filter_map(|stat| {
+            let id = stat.id();
+            let usage = disk_usage.iter().find(|usage| !id.is_empty() && usage.id.starts_with(id))?;
+            Some(DiskUsage {
+                service_name: stat.service_name.clone(),
+                container_name: stat.container_name.clone(),
+                container_id: usage.id.clone(),
+                container_id_short: stat.container_id_short.clone(),
+                timestamp,
+                writable_layer_in_bytes: usage.writable_layer_in_bytes,
+                volumes_in_bytes: usage.volumes_in_bytes,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_match_disk_usage_by_id_prefix() {
+        let disk_usage =
+            docker_api_df_parsers::parse_disk_usage(include_str!("../../test-data/docker_api_system_df.json")).unwrap();
+        let stats = vec![
+            ContainerStats {
+                container_id_short: "4889ab0711ac".to_string(),
+                service_name: "b".to_string(),
+                ..Default::default()
+            },
+            ContainerStats {
+                container_id: "ffffffffffff".to_string(),
+                ..Default::default()
+            },
+        ];
+        let timestamp = DateTime::from_timestamp(1_740_830_400, 0).unwrap();
+
+        let actual = match_disk_usage(&stats, &disk_usage, timestamp);
+
+        assert_eq!(
+            actual,
+            vec![DiskUsage {
+                service_name: "b".to_string(),
+                container_name: "".to_string(),
+                container_id: "4889ab0711ac17500a5de1c3d50eb6bfbc7eb367d349797f9fe8dcb92fd9e914".to_string(),
+                container_id_short: "4889ab0711ac".to_string(),
+                timestamp,
+                writable_layer_in_bytes: Some(52_428_800),
+                volumes_in_bytes: Some(1_094_713_344),
+            }]
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use crate::util::http::parse_message;
+use anyhow::anyhow;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Minimal HTTP/1.1 client for the Docker Engine API on a unix socket.
+#[derive(Clone, Debug)]
+pub(crate) struct DockerSocketClient {
+    socket_path: PathBuf,
+    timeout: Duration,
+}
+
+impl DockerSocketClient {
+    pub(crate) fn new(socket_path: PathBuf, timeout: Duration) -> Self {
+        DockerSocketClient { socket_path, timeout }
+    }
+
+    /// Body of a successful `GET`; other status codes are errors with the engine's message.
+    pub(crate) fn get(&self, path: &str) -> anyhow::Result<String> {
+        let mut stream = UnixStream::connect(&self.socket_path)
+            .map_err(|err| anyhow!("Could not connect to {}: {}", self.socket_path.display(), err))?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        write!(stream, "GET {} HTTP/1.1\r\nHost: docker\r\nConnection: close\r\n\r\n", path)?;
+
+        let mut response = vec![];
+        stream.read_to_end(&mut response)?;
+        parse_response(&response)
+    }
+}
+
+fn parse_response(response: &[u8]) -> anyhow::Result<String> {
+    let (head, body) = parse_message(response)?.ok_or(anyhow!("Incomplete HTTP response"))?;
+    let status = head.status().ok_or(anyhow!("Invalid HTTP status line"))?;
+    let body = String::from_utf8_lossy(&body).to_string();
+
+    match status {
+        200..=299 => Ok(body),
+        _ => Err(anyhow!("Engine API responded with {}: {}", status, body.trim())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_support::serve_unix_socket;
+    use rstest::rstest;
+
+    #[test]
+    fn should_parse_response() {
+        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 13\r\n\r\n[{\"Id\":\"a\"}]\n";
+
+        assert_eq!(parse_response(response).unwrap(), "[{\"Id\":\"a\"}]\n");
+    }
+
+    #[rstest]
+    #[case::error_status("HTTP/1.1 404 Not Found\r\n\r\n{\"message\":\"No such container\"}")]
+    #[case::incomplete("HTTP/1.1 200 OK\r\nContent-Length: 13")]
+    #[case::truncated_chunk("HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nff\r\n[]")]
+    #[case::invalid_chunk_size("HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n[]")]
+    fn should_reject_invalid_responses(#[case] response: &str) {
+        assert!(parse_response(response.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn should_get_over_unix_socket() {
+        let (socket_path, server) = serve_unix_socket("docker_socket", 1, vec![("/containers/json", "[]")]);
+
+        let actual = DockerSocketClient::new(socket_path, Duration::from_secs(5)).get("/containers/json");
+
+        assert_eq!(actual.unwrap(), "[]");
+        assert_eq!(server.join().unwrap(), vec!["GET /containers/json HTTP/1.1"]);
+    }
+}
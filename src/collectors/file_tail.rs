@@ -0,0 +1,267 @@
+use crate::collectors::balena_stats_collector_config::{TailConfig, TailGroupBy};
+use chrono::{DateTime, Utc};
+use log::{debug, info};
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::mem;
+use std::os::unix::fs::MetadataExt;
+use std::path::PathBuf;
+
+/// Follows an appended file like `tail -F`, across rotation and truncation.
+pub(crate) struct FileTail {
+    path: PathBuf,
+    config: TailConfig,
+    file: Option<(File, u64)>,
+    offset: u64,
+    partial_line: Vec<u8>,
+    group: Vec<String>,
+}
+
+impl FileTail {
+    pub(crate) fn new(path: PathBuf, config: TailConfig) -> Self {
+        FileTail {
+            path,
+            config,
+            file: None,
+            offset: 0,
+            partial_line: vec![],
+            group: vec![],
+        }
+    }
+
+    /// Lines of the newest collection completed since the last call; older ones are skipped.
+    pub(crate) fn read_latest_group(&mut self) -> anyhow::Result<Option<Vec<String>>> {
+        let mut latest = None;
+        let mut completed = 0;
+        for line in self.read_new_lines()? {
+            if let Some(group) = self.push_line(line) {
+                latest = Some(group);
+                completed += 1;
+            }
+        }
+        if completed > 1 {
+            debug!("Skipped {} older collections of {}", completed - 1, self.path.display());
+        }
+        Ok(latest)
+    }
+
+    fn read_new_lines(&mut self) -> anyhow::Result<Vec<String>> {
+        let mut lines = vec![];
+        let inode = match fs::metadata(&self.path) {
+            Ok(metadata) => metadata.ino(),
+            // Rotated away and not recreated yet; the rest of the old file is still readable.
+            Err(err) => match &mut self.file {
+                Some((file, _)) => {
+                    read_appended(file, &mut self.offset, &mut self.partial_line, &mut lines)?;
+                    return Ok(lines);
+                }
+                None => return Err(err.into()),
+            },
+        };
+
+        if let Some((mut file, _)) = self.file.take_if(|(_, current)| *current != inode) {
+            info!("{} was rotated; continuing with the new file.", self.path.display());
+            read_appended(&mut file, &mut self.offset, &mut self.partial_line, &mut lines)?;
+            self.offset = 0;
+            self.partial_line.clear();
+        }
+        let (file, _) = match &mut self.file {
+            Some(file) => file,
+            None => self.file.insert((File::open(&self.path)?, inode)),
+        };
+        if file.metadata()?.len() < self.offset {
+            info!("{} was truncated; reading from the start.", self.path.display());
+            self.offset = 0;
+            self.partial_line.clear();
+        }
+        read_appended(file, &mut self.offset, &mut self.partial_line, &mut lines)?;
+        Ok(lines)
+    }
+
+    fn push_line(&mut self, line: String) -> Option<Vec<String>> {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            return None;
+        }
+        match self.config.group_by {
+            TailGroupBy::SEPARATOR if trimmed == self.config.separator => {
+                Some(mem::take(&mut self.group)).filter(|group| !group.is_empty())
+            }
+            // The timestamp line is kept as first line of its collection, e.g. for staleness detection.
+            TailGroupBy::TIMESTAMP if parse_timestamp(trimmed).is_some() => {
+                Some(mem::replace(&mut self.group, vec![line])).filter(|group| !group.is_empty())
+            }
+            _ => {
+                self.group.push(line);
+                None
+            }
+        }
+    }
+}
+
+// Reads from `offset` to the end; bytes of an incomplete last line are kept in `partial_line`.
+fn read_appended(
+    file: &mut File,
+    offset: &mut u64,
+    partial_line: &mut Vec<u8>,
+    lines: &mut Vec<String>,
+) -> anyhow::Result<()> {
+    file.seek(SeekFrom::Start(*offset))?;
+    *offset += file.read_to_end(partial_line)? as u64;
+
+    if let Some(end) = partial_line.iter().rposition(|byte| *byte == b'\n') {
+        let rest = partial_line.split_off(end + 1);
+        lines.extend(String::from_utf8_lossy(partial_line).lines().map(str::to_string));
+        *partial_line = rest;
+    }
+    Ok(())
+}
+
+/// Parses a line written by e.g. `date -Iseconds` or `date +%s`.
+pub(crate) fn parse_timestamp(line: &str) -> Option<DateTime<Utc>> {
+    let line = line.trim();
+    if line.len() >= 9 && line.chars().all(|char| char.is_ascii_digit()) {
+        return line.parse().ok().and_then(|seconds| DateTime::from_timestamp(seconds, 0));
+    }
+    DateTime::parse_from_rfc3339(line).ok().map(|timestamp| timestamp.to_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_support::temp_dir;
+    use rstest::rstest;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::path::Path;
+
+    fn setup_path(test_name: &str) -> PathBuf {
+        temp_dir(&format!("file_tail_{}", test_name)).join("stats.txt")
+    }
+
+    fn append(path: &Path, content: &str) {
+        let mut file = OpenOptions::new().create(true).append(true).open(path).unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+    }
+
+    fn separator_tail(path: &Path) -> FileTail {
+        FileTail::new(path.to_path_buf(), TailConfig::default())
+    }
+
+    #[test]
+    fn should_read_only_appended_lines() {
+        let path = setup_path("appended");
+        append(&path, "a1\na2\n---\n");
+        let mut tail = separator_tail(&path);
+
+        let first = tail.read_latest_group().unwrap();
+        let unchanged = tail.read_latest_group().unwrap();
+        append(&path, "b1\n---\n");
+        let second = tail.read_latest_group().unwrap();
+
+        assert_eq!(first, Some(vec!["a1".to_string(), "a2".to_string()]));
+        assert_eq!(unchanged, None);
+        assert_eq!(second, Some(vec!["b1".to_string()]));
+    }
+
+    #[test]
+    fn should_wait_for_incomplete_lines_and_groups() {
+        let path = setup_path("incomplete");
+        let mut tail = separator_tail(&path);
+        append(&path, "a1\na");
+
+        assert_eq!(tail.read_latest_group().unwrap(), None);
+        append(&path, "2\r\n--");
+        assert_eq!(tail.read_latest_group().unwrap(), None);
+        append(&path, "-\n");
+        assert_eq!(tail.read_latest_group().unwrap(), Some(vec!["a1".to_string(), "a2".to_string()]));
+    }
+
+    #[test]
+    fn should_keep_multibyte_characters_split_between_reads() {
+        let path = setup_path("multibyte");
+        let mut tail = separator_tail(&path);
+        let line = "{\"Name\":\"gr\u{fc}n\"}\n---\n".as_bytes();
+        let split = line.iter().position(|byte| *byte == 0xc3).unwrap() + 1;
+
+        OpenOptions::new().create(true).append(true).open(&path).unwrap().write_all(&line[..split]).unwrap();
+        assert_eq!(tail.read_latest_group().unwrap(), None);
+        OpenOptions::new().append(true).open(&path).unwrap().write_all(&line[split..]).unwrap();
+
+        assert_eq!(tail.read_latest_group().unwrap(), Some(vec!["{\"Name\":\"gr\u{fc}n\"}".to_string()]));
+    }
+
+    #[test]
+    fn should_return_latest_of_several_groups() {
+        let path = setup_path("several");
+        append(&path, "a1\n---\nb1\n---\nc1\n");
+        let mut tail = separator_tail(&path);
+
+        assert_eq!(tail.read_latest_group().unwrap(), Some(vec!["b1".to_string()]));
+    }
+
+    #[test]
+    fn should_group_by_timestamp_lines() {
+        let path = setup_path("timestamp");
+        append(&path, "2025-03-01T12:00:00+00:00\na1\n1740830415\nb1\n");
+        let mut tail = FileTail::new(
+            path.clone(),
+            TailConfig {
+                group_by: TailGroupBy::TIMESTAMP,
+                ..Default::default()
+            },
+        );
+
+        let first = tail.read_latest_group().unwrap();
+        append(&path, "2025-03-01T12:00:30Z\n");
+        let second = tail.read_latest_group().unwrap();
+
+        assert_eq!(first, Some(vec!["2025-03-01T12:00:00+00:00".to_string(), "a1".to_string()]));
+        assert_eq!(second, Some(vec!["1740830415".to_string(), "b1".to_string()]));
+    }
+
+    #[rstest]
+    #[case::rfc3339("2025-03-01T12:00:15+01:00", Some(1740826815))]
+    #[case::epoch_seconds("1740830415", Some(1740830415))]
+    #[case::short_number("42", None)]
+    #[case::stats_line("{\"Name\":\"a\"}", None)]
+    fn should_parse_timestamp_lines(#[case] line: &str, #[case] expected: Option<i64>) {
+        assert_eq!(parse_timestamp(line).map(|timestamp| timestamp.timestamp()), expected);
+    }
+
+    #[test]
+    fn should_read_from_start_after_truncation() {
+        let path = setup_path("truncation");
+        append(&path, "a1\na2\na3\n---\n");
+        let mut tail = separator_tail(&path);
+        tail.read_latest_group().unwrap();
+
+        fs::write(&path, "b1\n---\n").unwrap();
+
+        assert_eq!(tail.read_latest_group().unwrap(), Some(vec!["b1".to_string()]));
+    }
+
+    #[test]
+    fn should_finish_rotated_file_and_follow_new_one() {
+        let path = setup_path("rotation");
+        append(&path, "a1\n");
+        let mut tail = separator_tail(&path);
+        tail.read_latest_group().unwrap();
+
+        append(&path, "---\n");
+        fs::rename(&path, path.with_extension("txt.1")).unwrap();
+        let rotated = tail.read_latest_group().unwrap();
+        append(&path, "b1\n---\n");
+        let new = tail.read_latest_group().unwrap();
+
+        assert_eq!(rotated, Some(vec!["a1".to_string()]));
+        assert_eq!(new, Some(vec!["b1".to_string()]));
+    }
+
+    #[test]
+    fn should_fail_if_file_never_existed() {
+        let path = setup_path("missing");
+
+        assert!(separator_tail(&path).read_latest_group().is_err());
+    }
+}
@@ -2,4 +2,13 @@ pub mod balena_stats_cli_stdout_collector;
 pub mod balena_stats_collector;
 pub(crate) mod balena_stats_collector_config;
 pub mod balena_stats_file_collector;
-mod raw_stats_to_json_str;
\ No newline at end of file
+pub mod balena_stats_socket_collector;
+mod cgroup;
+pub mod container_inspector;
+pub mod disk_usage;
+mod docker_socket;
+mod file_tail;
+mod raw_stats_to_json_str;
+mod staleness;
+pub mod stats_enricher;
+pub mod top_processes;
\ No newline at end of file
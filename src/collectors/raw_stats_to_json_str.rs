@@ -1,16 +1,70 @@
+use log::warn;
+use serde_json::{Map, Value};
+
+/// Joins JSON object lines, quoted or not, into a JSON array; other lines are skipped.
 pub fn stdout_lines_to_json_array(stdout: &str) -> anyhow::Result<String> {
-    let lines = stdout.split('\n').collect::<Vec<&str>>();
-    let trimmed: Vec<&str> = lines
-        .into_iter()
-        .filter(|line| line.trim().len() > 0)
+    let lines: Vec<String> = stdout
+        .lines()
+        .map(normalize_line)
+        .filter(|line| !line.is_empty())
+        .filter(|line| {
+            let is_object = serde_json::from_str::<Map<String, Value>>(line).is_ok();
+            if !is_object {
+                warn!("Skipping stats line which is no JSON object: {}", line);
+            }
+            is_object
+        })
         .collect();
-    let joined = trimmed.join(",\n");
-    Ok(format!("[{}]", joined))
+    Ok(format!("[{}]", lines.join(",\n")))
+}
+
+// Removes a BOM, ANSI escape sequences, control characters like `\r` and the quotes around a line.
+fn normalize_line(line: &str) -> String {
+    let cleaned = strip_control_sequences(line.trim_start_matches('\u{feff}'));
+    let trimmed = cleaned.trim();
+    ['"', '\'']
+        .iter()
+        .find_map(|quote| trimmed.strip_prefix(*quote)?.strip_suffix(*quote))
+        .unwrap_or(trimmed)
+        .trim()
+        .to_string()
+}
+
+// Removes ASCII control characters except tabs, and ANSI escape sequences.
+fn strip_control_sequences(line: &str) -> String {
+    let mut stripped = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    while let Some(char) = chars.next() {
+        match char {
+            // CSI like `\x1b[2K`, ending with a byte in `@`..=`~`.
+            '\x1b' if chars.next_if_eq(&'[').is_some() => {
+                for char in chars.by_ref() {
+                    if ('@'..='~').contains(&char) {
+                        break;
+                    }
+                }
+            }
+            // OSC like `\x1b]0;title\x07`, ending with BEL or `\x1b\\`.
+            '\x1b' if chars.next_if_eq(&']').is_some() => {
+                while let Some(char) = chars.next() {
+                    if char == '\x07' || (char == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            '\t' => stripped.push(char),
+            _ if char < ' ' => {}
+            _ => stripped.push(char),
+        }
+    }
+    stripped
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+    use rstest::rstest;
 
     #[test]
     fn should_convert_stdout_lines_to_json_array() {
@@ -21,4 +75,97 @@ mod tests {
 
         assert_eq!(actual.unwrap(), expected)
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn should_convert_quoted_stdout_lines_to_json_array() {
+        let stdout = include_str!("../../test-data/balena_cli_stats_stdout_quote_lines.txt");
+        let expected = include_str!("../../test-data/balena_stats_stdout.json");
+
+        let actual = stdout_lines_to_json_array(stdout);
+
+        assert_eq!(actual.unwrap(), expected)
+    }
+
+    #[rstest]
+    #[case::raw("{\"Name\":\"a\"}", "{\"Name\":\"a\"}")]
+    #[case::double_quotes("\"{\"Name\":\"a\"}\"", "{\"Name\":\"a\"}")]
+    #[case::single_quotes("'{\"Name\":\"a\"}'", "{\"Name\":\"a\"}")]
+    #[case::crlf("\"{\"Name\":\"a\"}\"\r", "{\"Name\":\"a\"}")]
+    #[case::bom("\u{feff}{\"Name\":\"a\"}", "{\"Name\":\"a\"}")]
+    #[case::ansi("\x1b[2J\x1b[H{\"Name\":\"a\"}\x1b[0m", "{\"Name\":\"a\"}")]
+    #[case::osc("\x1b]0;docker\x07{\"Name\":\"a\"}", "{\"Name\":\"a\"}")]
+    #[case::multi_byte("\"{\"Name\":\"ü_🐳\"}\"", "{\"Name\":\"ü_🐳\"}")]
+    #[case::one_char("\"", "\"")]
+    #[case::quotes_only("\"\"", "")]
+    fn should_normalize_line(#[case] line: &str, #[case] expected: &str) {
+        assert_eq!(normalize_line(line), expected);
+    }
+
+    #[test]
+    fn should_skip_lines_which_are_no_json_objects() {
+        let stdout = "WARNING: No swap limit support\n{\"Name\":\"a\"}\n\"b\"\n[1]\n";
+
+        let actual = stdout_lines_to_json_array(stdout);
+
+        assert_eq!(actual.unwrap(), "[{\"Name\":\"a\"}]");
+    }
+
+    fn json_object() -> impl Strategy<Value = Map<String, Value>> {
+        prop::collection::btree_map("[A-Za-z]{1,8}", any::<String>(), 0..4)
+            .prop_map(|map| map.into_iter().map(|(key, value)| (key, Value::String(value))).collect())
+    }
+
+    fn noisy_line(line: String, quote: Option<char>, ansi: bool, crlf: bool) -> String {
+        let quoted = match quote {
+            Some(quote) => format!("{}{}{}", quote, line, quote),
+            None => line,
+        };
+        let colored = match ansi {
+            true => format!("\x1b[1;32m{}\x1b[0m", quoted),
+            false => quoted,
+        };
+        match crlf {
+            true => colored + "\r\n",
+            false => colored + "\n",
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn should_always_produce_a_json_array(stdout in any::<String>()) {
+            let actual = stdout_lines_to_json_array(&stdout).unwrap();
+
+            prop_assert!(serde_json::from_str::<Vec<Map<String, Value>>>(&actual).is_ok());
+        }
+
+        #[test]
+        fn should_always_produce_a_json_array_of_lossy_bytes(bytes in prop::collection::vec(any::<u8>(), 0..512)) {
+            let actual = stdout_lines_to_json_array(&String::from_utf8_lossy(&bytes)).unwrap();
+
+            prop_assert!(serde_json::from_str::<Vec<Map<String, Value>>>(&actual).is_ok());
+        }
+
+        #[test]
+        fn should_keep_objects_despite_quoting_and_noise(
+            lines in prop::collection::vec(
+                (json_object(), prop::option::of(prop::sample::select(vec!['"', '\''])), any::<bool>(), any::<bool>()),
+                0..8,
+            ),
+            bom in any::<bool>(),
+        ) {
+            let mut stdout = match bom {
+                true => "\u{feff}".to_string(),
+                false => String::new(),
+            };
+            for (object, quote, ansi, crlf) in &lines {
+                stdout += &noisy_line(serde_json::to_string(object).unwrap(), *quote, *ansi, *crlf);
+            }
+
+            let actual: Vec<Map<String, Value>> =
+                serde_json::from_str(&stdout_lines_to_json_array(&stdout).unwrap()).unwrap();
+
+            let expected: Vec<Map<String, Value>> = lines.into_iter().map(|(object, ..)| object).collect();
+            prop_assert_eq!(actual, expected);
+        }
+    }
+}
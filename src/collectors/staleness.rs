@@ -0,0 +1,99 @@
+use crate::collectors::balena_stats_collector_config::StalenessConfig;
+use crate::collectors::file_tail::parse_timestamp;
+use crate::domain::SourceStatus;
+use chrono::{DateTime, TimeDelta, Utc};
+
+/// Tracks the last update of a source written by someone else, e.g. a cron job on the host.
+pub(crate) struct StalenessCheck {
+    max_age: TimeDelta,
+    last_update: Option<DateTime<Utc>>,
+}
+
+impl StalenessCheck {
+    pub(crate) fn new(config: &StalenessConfig) -> Self {
+        StalenessCheck {
+            max_age: TimeDelta::seconds(config.max_age_in_seconds as i64),
+            last_update: None,
+        }
+    }
+
+    // Older updates are ignored, e.g. the rest of a rotated file read after the new one.
+    pub(crate) fn update(&mut self, last_update: DateTime<Utc>) {
+        self.last_update = self.last_update.max(Some(last_update));
+    }
+
+    /// Without any known update, the source is stale.
+    pub(crate) fn status(&self, now: DateTime<Utc>) -> SourceStatus {
+        SourceStatus {
+            stale: self.last_update.is_none_or(|last_update| now - last_update > self.max_age),
+            last_update: self.last_update,
+        }
+    }
+}
+
+/// Removes timestamp lines from raw stats, as no stats format allows them; returns the latest of them.
+pub(crate) fn split_off_timestamps(raw: &str) -> (Option<DateTime<Utc>>, String) {
+    let mut latest = None;
+    let mut lines = vec![];
+    for line in raw.lines() {
+        match parse_timestamp(line) {
+            Some(timestamp) => latest = latest.max(Some(timestamp)),
+            None => lines.push(line),
+        }
+    }
+    (latest, lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collectors::balena_stats_collector_config::StalenessSource;
+    use rstest::rstest;
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1740830400 + seconds, 0).unwrap()
+    }
+
+    fn setup_check() -> StalenessCheck {
+        StalenessCheck::new(&StalenessConfig {
+            max_age_in_seconds: 60,
+            source: StalenessSource::MTIME,
+        })
+    }
+
+    #[rstest]
+    #[case::fresh(30, false)]
+    #[case::at_max_age(60, false)]
+    #[case::older_than_max_age(61, true)]
+    fn should_detect_stale_source(#[case] now: i64, #[case] expected: bool) {
+        let mut check = setup_check();
+        check.update(at(0));
+
+        let actual = check.status(at(now));
+
+        assert_eq!(actual, SourceStatus { stale: expected, last_update: Some(at(0)) });
+    }
+
+    #[test]
+    fn should_be_stale_without_any_update() {
+        assert_eq!(setup_check().status(at(0)), SourceStatus { stale: true, last_update: None });
+    }
+
+    #[test]
+    fn should_ignore_older_updates() {
+        let mut check = setup_check();
+        check.update(at(10));
+        check.update(at(0));
+
+        assert_eq!(check.status(at(20)).last_update, Some(at(10)));
+    }
+
+    #[test]
+    fn should_split_off_timestamp_lines() {
+        let raw = "1740830400\n{\"Name\":\"a\"}\n2025-03-01T12:00:30Z\n{\"Name\":\"b\"}\n";
+
+        let actual = split_off_timestamps(raw);
+
+        assert_eq!(actual, (Some(at(30)), "{\"Name\":\"a\"}\n{\"Name\":\"b\"}".to_string()));
+    }
+}
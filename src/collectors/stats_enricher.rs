@@ -0,0 +1,176 @@
+use crate::collectors::cgroup::{read_cpu_quota, read_memory, read_pressure, CgroupResolver};
+use crate::collectors::container_inspector::ContainerInspector;
+use crate::domain::{ContainerStats, HostStats, PressureStats};
+use byte_unit::Byte;
+use log::warn;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Adds what the stats output lacks from the host's `/proc` and the containers' cgroups.
+pub struct StatsEnricher {
+    proc_path: PathBuf,
+    host_cpu_cores: Option<u16>,
+    cgroups: CgroupResolver,
+    inspector: Option<ContainerInspector>,
+}
+
+impl StatsEnricher {
+    pub fn new(proc_path: &str, cgroup_path: &str) -> Self {
+        // Read once; CPU hotplug is not expected on the devices this runs on.
+        let host_cpu_cores = read_cpu_cores(Path::new(proc_path));
+        if host_cpu_cores.is_none() {
+            warn!("Could not read CPU cores from {}; CPU usage will not be normalized", proc_path);
+        }
+        StatsEnricher {
+            proc_path: PathBuf::from(proc_path),
+            host_cpu_cores,
+            cgroups: CgroupResolver::new(PathBuf::from(cgroup_path)),
+            inspector: None,
+        }
+    }
+
+    pub fn with_inspector(self, inspector: ContainerInspector) -> Self {
+        StatsEnricher {
+            inspector: Some(inspector),
+            ..self
+        }
+    }
+
+    pub fn enrich(&mut self, stats: &mut [ContainerStats]) {
+        let container_ids: Vec<&str> = stats.iter().map(ContainerStats::id).collect();
+        self.cgroups.retain(&container_ids);
+
+        for stat in stats.iter_mut() {
+            stat.host_cpu_cores = self.host_cpu_cores;
+            stat.normalized_cpu_usage_in_percent = stat
+                .cpu_usage_in_percent
+                .zip(self.host_cpu_cores)
+                .map(|(usage, cores)| usage / f32::from(cores));
+            stat.cpu_quota_in_cores = self.cgroups.find(stat.id(), "cpu").and_then(read_cpu_quota);
+            if let Some(memory) = self.cgroups.find(stat.id(), "memory").and_then(read_memory) {
+                stat.mem_anon = memory.anon_in_bytes.map(Byte::from_u64);
+                stat.mem_file = memory.file_in_bytes.map(Byte::from_u64);
+                stat.mem_swap = memory.swap_in_bytes.map(Byte::from_u64);
+                stat.mem_working_set = memory.working_set_in_bytes.map(Byte::from_u64);
+                stat.mem_oom_events = memory.oom_events;
+                stat.mem_oom_kill_events = memory.oom_kill_events;
+            }
+            stat.cpu_pressure = self.read_cgroup_pressure(stat, "cpu");
+            stat.memory_pressure = self.read_cgroup_pressure(stat, "memory");
+            stat.io_pressure = self.read_cgroup_pressure(stat, "io");
+        }
+        if let Some(inspector) = &self.inspector {
+            inspector.inspect(stats);
+        }
+    }
+
+    /// Pressure of the whole host from `/proc/pressure`; not affected by PID namespaces.
+    pub fn read_host(&self) -> HostStats {
+        let host_pressure = self.proc_path.join("pressure");
+        HostStats {
+            cpu_pressure: read_pressure(&host_pressure.join("cpu")),
+            memory_pressure: read_pressure(&host_pressure.join("memory")),
+            io_pressure: read_pressure(&host_pressure.join("io")),
+        }
+    }
+
+    // Controller and file share the name, e.g. `cpu.pressure` in the cgroup of the `cpu` controller.
+    fn read_cgroup_pressure(&mut self, stats: &ContainerStats, controller: &'static str) -> Option<PressureStats> {
+        let dir = self.cgroups.find(stats.id(), controller)?;
+        read_pressure(&dir.join(format!("{}.pressure", controller)))
+    }
+}
+
+fn read_cpu_cores(proc_path: &Path) -> Option<u16> {
+    let cpuinfo = fs::read_to_string(proc_path.join("cpuinfo")).ok()?;
+    let cores = cpuinfo.lines().filter(|line| line.starts_with("processor")).count();
+    u16::try_from(cores).ok().filter(|cores| *cores > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::BytePrecision;
+    use std::collections::BTreeMap;
+
+    fn setup_stats(container_id: &str, cpu_usage_in_percent: f32) -> ContainerStats {
+        ContainerStats {
+            container_id: container_id.to_string(),
+            cpu_usage_in_percent: Some(cpu_usage_in_percent),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn should_read_cpu_cores() {
+        assert_eq!(read_cpu_cores(Path::new("test-data/proc")), Some(4));
+        assert_eq!(read_cpu_cores(Path::new("test-data/missing")), None);
+    }
+
+    #[test]
+    fn should_add_normalized_cpu_usage_and_quota() {
+        let mut enricher = StatsEnricher::new("test-data/proc", "test-data/cgroup/v2");
+        let mut stats = vec![setup_stats("0c05c278da1f", 150.0), setup_stats("4d6f35b38ac9", 6.0)];
+
+        enricher.enrich(&mut stats);
+
+        assert_eq!(stats[0].host_cpu_cores, Some(4));
+        assert_eq!(stats[0].normalized_cpu_usage_in_percent, Some(37.5));
+        assert_eq!(stats[0].cpu_quota_in_cores, Some(1.5));
+        assert_eq!(stats[1].normalized_cpu_usage_in_percent, Some(1.5));
+        assert_eq!(stats[1].cpu_quota_in_cores, None);
+    }
+
+    #[test]
+    fn should_add_memory_breakdown() {
+        let mut enricher = StatsEnricher::new("test-data/proc", "test-data/cgroup/v2");
+        let mut stats = vec![setup_stats("0c05c278da1f", 150.0), setup_stats("4d6f35b38ac9", 6.0)];
+
+        enricher.enrich(&mut stats);
+
+        assert_eq!(stats[0].mem_anon, Some(Byte::from_u64(104_857_600)));
+        assert_eq!(stats[0].mem_working_set, Some(Byte::from_u64(136_314_880)));
+        assert_eq!(stats[0].mem_oom_kill_events, Some(1));
+        assert_eq!(stats[0].precision_of("memory_anon_in_bytes"), Some(BytePrecision::EXACT));
+        assert_eq!(stats[0].precision_of("memory_usage_in_bytes"), Some(BytePrecision::ROUNDED));
+        assert_eq!(stats[1].mem_anon, None);
+    }
+
+    #[test]
+    fn should_add_container_pressure() {
+        let mut enricher = StatsEnricher::new("test-data/proc", "test-data/cgroup/v2");
+        let mut stats = vec![setup_stats("0c05c278da1f", 150.0), setup_stats("4d6f35b38ac9", 6.0)];
+
+        enricher.enrich(&mut stats);
+
+        let metrics: BTreeMap<&str, Option<f64>> = stats[0].metrics().into_iter().collect();
+        assert_eq!(metrics["cpu_pressure_some_avg10_in_percent"], Some(12.5));
+        assert_eq!(metrics["memory_pressure_full_total_in_microseconds"], Some(4_567.0));
+        assert_eq!(metrics["io_pressure_some_avg60_in_percent"], Some(0.75));
+        assert!(!metrics.contains_key("host_cpu_pressure_some_avg300_in_percent"));
+        assert_eq!(stats[1].cpu_pressure, None);
+    }
+
+    #[test]
+    fn should_read_host_pressure() {
+        let enricher = StatsEnricher::new("test-data/proc", "test-data/cgroup/v2");
+
+        let actual: BTreeMap<&str, Option<f64>> = enricher.read_host().metrics().into_iter().collect();
+
+        assert_eq!(actual["host_cpu_pressure_some_avg300_in_percent"], Some(2.5));
+        assert_eq!(actual["host_cpu_pressure_full_avg10_in_percent"], None);
+        assert_eq!(actual["host_io_pressure_full_avg10_in_percent"], Some(0.5));
+    }
+
+    #[test]
+    fn should_leave_values_unavailable_without_host_access() {
+        let mut enricher = StatsEnricher::new("test-data/missing", "test-data/missing");
+        let mut stats = vec![setup_stats("0c05c278da1f", 150.0)];
+
+        enricher.enrich(&mut stats);
+
+        assert_eq!(stats[0].cpu_usage_in_percent, Some(150.0));
+        assert_eq!(stats[0].normalized_cpu_usage_in_percent, None);
+        assert_eq!(stats[0].cpu_quota_in_cores, None);
+    }
+}
@@ -0,0 +1,232 @@
+use crate::collectors::balena_stats_collector_config::TopProcessesConfig;
+use crate::collectors::cgroup::CgroupResolver;
+use crate::domain::{ContainerStats, ProcessStats, TopProcesses};
+use chrono::{DateTime, TimeDelta, Utc};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// `USER_HZ`, the unit of CPU times in `/proc/{pid}/stat`; 100 on all architectures Linux runs on today.
+const CLOCK_TICKS_PER_SECOND: f64 = 100.0;
+
+struct ProcessSample {
+    pid: u32,
+    name: String,
+    command: String,
+    cpu_ticks: u64,
+    mem_usage_in_bytes: u64,
+}
+
+/// Ranks the processes of every container by CPU and memory on its own, slower interval.
+pub struct TopProcessesCollector {
+    proc_path: PathBuf,
+    cgroups: CgroupResolver,
+    config: TopProcessesConfig,
+    // CPU ticks by PID at the previous collection.
+    previous: Option<(DateTime<Utc>, HashMap<u32, u64>)>,
+}
+
+impl TopProcessesCollector {
+    pub fn new(config: TopProcessesConfig, proc_path: &str, cgroup_path: &str) -> Self {
+        TopProcessesCollector {
+            proc_path: PathBuf::from(proc_path),
+            cgroups: CgroupResolver::new(PathBuf::from(cgroup_path)),
+            config,
+            previous: None,
+        }
+    }
+
+    /// `None` until the interval has passed; the first collection only records CPU times.
+    pub fn collect(&mut self, stats: &[ContainerStats], now: DateTime<Utc>) -> Option<Vec<TopProcesses>> {
+        let interval = TimeDelta::seconds(self.config.interval_in_seconds as i64);
+        if self.previous.as_ref().is_some_and(|(at, _)| now - *at < interval) {
+            return None;
+        }
+        let container_ids: Vec<&str> = stats.iter().map(ContainerStats::id).collect();
+        self.cgroups.retain(&container_ids);
+
+        let samples: Vec<(&ContainerStats, Vec<ProcessSample>)> = stats
+            .iter()
+            .map(|stat| {
+                let pids = self.cgroups.find(stat.id(), "cpu").map(read_pids).unwrap_or_default();
+                let samples = pids.into_iter().filter_map(|pid| read_process(&self.proc_path, pid)).collect();
+                (stat, samples)
+            })
+            .collect();
+        let cpu_ticks = samples
+            .iter()
+            .flat_map(|(_, samples)| samples.iter().map(|sample| (sample.pid, sample.cpu_ticks)))
+            .collect();
+
+        let top_processes = self.previous.take().map(|(previous_at, previous)| {
+            let elapsed_in_seconds = (now - previous_at).num_milliseconds() as f64 / 1000.0;
+            samples
+                .into_iter()
+                .map(|(stat, samples)| {
+                    let (by_cpu, by_memory) = rank(samples, &previous, elapsed_in_seconds, self.config.count);
+                    TopProcesses {
+                        service_name: stat.service_name.clone(),
+                        container_name: stat.container_name.clone(),
+                        container_id_short: stat.container_id_short.clone(),
+                        timestamp: now,
+                        by_cpu,
+                        by_memory,
+                    }
+                })
+                .collect()
+        });
+        self.previous = Some((now, cpu_ticks));
+        top_processes
+    }
+}
+
+/// The top `count` processes by CPU and by memory.
+fn rank(
+    samples: Vec<ProcessSample>,
+    previous: &HashMap<u32, u64>,
+    elapsed_in_seconds: f64,
+    count: usize,
+) -> (Vec<ProcessStats>, Vec<ProcessStats>) {
+    let mut processes: Vec<ProcessStats> = samples
+        .into_iter()
+        .map(|sample| {
+            // Processes started since the previous collection used all their CPU time within the interval.
+            let ticks = sample.cpu_ticks.saturating_sub(previous.get(&sample.pid).copied().unwrap_or_default());
+            let cpu_usage_in_percent = (elapsed_in_seconds > 0.0)
+                .then(|| (ticks as f64 / CLOCK_TICKS_PER_SECOND / elapsed_in_seconds * 100.0) as f32);
+            ProcessStats {
+                pid: sample.pid,
+                name: sample.name,
+                command: sample.command,
+                cpu_usage_in_percent,
+                mem_usage_in_bytes: sample.mem_usage_in_bytes,
+            }
+        })
+        .collect();
+
+    processes.sort_by_key(|process| Reverse(process.mem_usage_in_bytes));
+    let by_memory = processes.iter().take(count).cloned().collect();
+    processes.sort_by(|a, b| {
+        let cpu_usage = |process: &ProcessStats| process.cpu_usage_in_percent.unwrap_or_default();
+        cpu_usage(b).total_cmp(&cpu_usage(a))
+    });
+    processes.truncate(count);
+    (processes, by_memory)
+}
+
+fn read_pids(cgroup_dir: &Path) -> Vec<u32> {
+    fs::read_to_string(cgroup_dir.join("cgroup.procs"))
+        .map(|procs| procs.lines().filter_map(|pid| pid.trim().parse().ok()).collect())
+        .unwrap_or_default()
+}
+
+// Processes may exit while being read; these are skipped.
+fn read_process(proc_path: &Path, pid: u32) -> Option<ProcessSample> {
+    let dir = proc_path.join(pid.to_string());
+    let stat = fs::read_to_string(dir.join("stat")).ok()?;
+    // The name in parentheses may contain spaces and parentheses itself.
+    let (name_part, rest) = stat.rsplit_once(')')?;
+    let name = name_part.split_once('(')?.1.to_string();
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    // `utime` and `stime` are fields 14 and 15, counting the PID as 1 and the name as 2.
+    let cpu_ticks = fields.get(11)?.parse::<u64>().ok()? + fields.get(12)?.parse::<u64>().ok()?;
+
+    // Exact whatever the page size, unlike the RSS pages in `stat`.
+    let status = fs::read_to_string(dir.join("status")).ok()?;
+    let rss_in_kilobytes: u64 = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))
+        .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+        .unwrap_or_default();
+    // Kernel threads have an empty command line.
+    let command = fs::read(dir.join("cmdline"))
+        .map(|cmdline| {
+            cmdline
+                .split(|byte| *byte == 0)
+                .filter(|arg| !arg.is_empty())
+                .map(|arg| String::from_utf8_lossy(arg).to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .unwrap_or_default();
+
+    Some(ProcessSample {
+        pid,
+        name,
+        command,
+        cpu_ticks,
+        mem_usage_in_bytes: rss_in_kilobytes * 1024,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_collector(count: usize) -> TopProcessesCollector {
+        TopProcessesCollector::new(
+            TopProcessesConfig {
+                count,
+                interval_in_seconds: 60,
+            },
+            "test-data/proc",
+            "test-data/cgroup/v2",
+        )
+    }
+
+    fn setup_stats() -> Vec<ContainerStats> {
+        vec![ContainerStats {
+            container_id_short: "0c05c278da1f".to_string(),
+            service_name: "b".to_string(),
+            ..Default::default()
+        }]
+    }
+
+    fn at(timestamp: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(timestamp).unwrap().to_utc()
+    }
+
+    #[test]
+    fn should_read_process() {
+        let actual = read_process(Path::new("test-data/proc"), 102).unwrap();
+
+        assert_eq!(actual.name, "python3 -u");
+        assert_eq!(actual.command, "python3 -u worker.py");
+        assert_eq!(actual.cpu_ticks, 10_000);
+        assert_eq!(actual.mem_usage_in_bytes, 32_768_000);
+        assert!(read_process(Path::new("test-data/proc"), 999).is_none());
+    }
+
+    #[test]
+    fn should_rank_by_cpu_since_previous_collection_and_by_memory() {
+        let mut collector = setup_collector(2);
+        let previous = HashMap::from([(101, 1000), (102, 4000), (103, 2)]);
+        collector.previous = Some((at("2025-03-01T12:00:00Z"), previous));
+
+        let actual = collector.collect(&setup_stats(), at("2025-03-01T12:01:40Z")).unwrap();
+
+        assert_eq!(actual.len(), 1);
+        assert_eq!(actual[0].service_name, "b");
+        let by_cpu: Vec<(u32, Option<f32>)> =
+            actual[0].by_cpu.iter().map(|process| (process.pid, process.cpu_usage_in_percent)).collect();
+        assert_eq!(by_cpu, vec![(102, Some(60.0)), (101, Some(10.0))]);
+        let by_memory: Vec<u32> = actual[0].by_memory.iter().map(|process| process.pid).collect();
+        assert_eq!(by_memory, vec![101, 102]);
+    }
+
+    #[test]
+    fn should_only_record_cpu_times_at_first_and_wait_for_interval() {
+        let mut collector = setup_collector(5);
+
+        let first = collector.collect(&setup_stats(), at("2025-03-01T12:00:00Z"));
+        let too_early = collector.collect(&setup_stats(), at("2025-03-01T12:00:30Z"));
+        let due = collector.collect(&setup_stats(), at("2025-03-01T12:01:00Z"));
+
+        assert_eq!(first, None);
+        assert_eq!(too_early, None);
+        let due = due.unwrap();
+        assert_eq!(due[0].by_cpu.len(), 3);
+        assert_eq!(due[0].by_cpu[0].cpu_usage_in_percent, Some(0.0));
+    }
+}
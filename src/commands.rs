@@ -0,0 +1,51 @@
+use crate::exporters::csv_archive;
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use std::io::stdout;
+
+pub const EXPORT_ARCHIVE: &str = "export-archive";
+
+/// `export-archive --from <RFC 3339> --to <RFC 3339>`: writes archived rows of the given range as CSV to stdout.
+pub fn export_archive(args: &[String]) -> anyhow::Result<()> {
+    let from = parse_timestamp_arg(args, "--from")?;
+    let to = parse_timestamp_arg(args, "--to")?;
+    if from > to {
+        return Err(anyhow!("--from must not be after --to"));
+    }
+
+    csv_archive::export_range(from, to, &csv_archive::CONFIG, stdout().lock())
+}
+
+fn parse_timestamp_arg(args: &[String], name: &str) -> anyhow::Result<DateTime<Utc>> {
+    let value = args
+        .iter()
+        .position(|arg| arg == name)
+        .and_then(|index| args.get(index + 1))
+        .ok_or(anyhow!("Missing argument {} <RFC 3339 timestamp>", name))?;
+
+    DateTime::parse_from_rfc3339(value)
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+        .map_err(|err| anyhow!("Invalid timestamp for {}: {}", name, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_timestamp_arg() {
+        let args = vec!["--from".to_string(), "2025-03-01T12:00:00+01:00".to_string()];
+
+        let actual = parse_timestamp_arg(&args, "--from").unwrap();
+
+        assert_eq!(actual.to_rfc3339(), "2025-03-01T11:00:00+00:00");
+    }
+
+    #[test]
+    fn should_fail_on_missing_arg() {
+        let args = vec!["--from".to_string()];
+
+        assert!(parse_timestamp_arg(&args, "--from").is_err());
+        assert!(parse_timestamp_arg(&args, "--to").is_err());
+    }
+}
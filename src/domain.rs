@@ -1,18 +1,500 @@
 use byte_unit::Byte;
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Serialize, Serializer};
+use std::collections::{BTreeMap, BTreeSet};
 
-#[derive(Debug, PartialEq)]
+/// Serialized field names are the metric names of `metrics`, except for derived and nested metrics.
+#[derive(Debug, Default, PartialEq, Serialize)]
 pub struct ContainerStats {
     pub(crate) container_id: String,
     pub(crate) container_id_short: String,
     pub(crate) container_name: String,
     pub(crate) service_name: String,
+    /// Of one core, so up to 400% on four cores.
     pub(crate) cpu_usage_in_percent: Option<f32>,
+    /// Of all host cores, so up to 100%; comparable between devices.
+    pub(crate) normalized_cpu_usage_in_percent: Option<f32>,
+    /// CPU limit in cores, e.g. 1.5 for `--cpus 1.5`; `None` if unlimited or unknown.
+    pub(crate) cpu_quota_in_cores: Option<f32>,
+    pub(crate) host_cpu_cores: Option<u16>,
+    #[serde(rename = "memory_usage_in_percent")]
     pub(crate) mem_usage_in_percent: Option<f32>,
+    #[serde(rename = "memory_usage_in_bytes", serialize_with = "serialize_bytes")]
     pub(crate) mem_usage: Option<Byte>,
+    #[serde(rename = "memory_limit_in_bytes", serialize_with = "serialize_bytes")]
     pub(crate) mem_limit: Option<Byte>,
+    /// Anonymous memory from the container's cgroup, called RSS on cgroup v1.
+    #[serde(rename = "memory_anon_in_bytes", serialize_with = "serialize_bytes")]
+    pub(crate) mem_anon: Option<Byte>,
+    /// Page cache.
+    #[serde(rename = "memory_file_in_bytes", serialize_with = "serialize_bytes")]
+    pub(crate) mem_file: Option<Byte>,
+    #[serde(rename = "memory_swap_in_bytes", serialize_with = "serialize_bytes")]
+    pub(crate) mem_swap: Option<Byte>,
+    /// Usage without inactive page cache.
+    #[serde(rename = "memory_working_set_in_bytes", serialize_with = "serialize_bytes")]
+    pub(crate) mem_working_set: Option<Byte>,
+    /// Times the memory limit was hit and reclaim failed, since container start; cgroup v2 only.
+    #[serde(rename = "memory_oom_events")]
+    pub(crate) mem_oom_events: Option<u64>,
+    /// Processes killed by the OOM killer since container start.
+    #[serde(rename = "memory_oom_kill_events")]
+    pub(crate) mem_oom_kill_events: Option<u64>,
+    #[serde(rename = "network_input_in_bytes", serialize_with = "serialize_bytes")]
     pub(crate) network_input: Option<Byte>,
+    #[serde(rename = "network_output_in_bytes", serialize_with = "serialize_bytes")]
     pub(crate) network_output: Option<Byte>,
+    /// By interface name; only known to collectors with per-interface counters.
+    pub(crate) network_interfaces: BTreeMap<String, NetworkInterfaceStats>,
+    #[serde(rename = "block_device_input_in_bytes", serialize_with = "serialize_bytes")]
     pub(crate) block_device_input: Option<Byte>,
+    #[serde(rename = "block_device_output_in_bytes", serialize_with = "serialize_bytes")]
     pub(crate) block_device_output: Option<Byte>,
     pub(crate) amount_of_pids: Option<u16>,
+    /// Pressure stall information of the container's cgroup; cgroup v2 only.
+    pub(crate) cpu_pressure: Option<PressureStats>,
+    pub(crate) memory_pressure: Option<PressureStats>,
+    pub(crate) io_pressure: Option<PressureStats>,
+    /// Engine state like `running` or `restarting`; this and the following fields are only known with inspection.
+    pub(crate) state: Option<String>,
+    /// `starting`, `healthy` or `unhealthy`; `None` without health check.
+    pub(crate) health_status: Option<String>,
+    pub(crate) restart_count: Option<u32>,
+    pub(crate) started_at: Option<DateTime<Utc>>,
+    pub(crate) uptime_in_seconds: Option<u64>,
+    /// Of the last exit, e.g. 137 after being killed.
+    pub(crate) exit_code: Option<i64>,
+    /// Whether the last exit was caused by running out of memory.
+    pub(crate) oom_killed: Option<bool>,
+    pub(crate) byte_precision: BytePrecision,
+    /// Only known when the collector provides them; used for filtering, not exported.
+    #[serde(skip)]
+    pub(crate) labels: BTreeMap<String, String>,
+}
+
+/// Counters of one network interface of a container since container start.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct NetworkInterfaceStats {
+    pub(crate) input_in_bytes: u64,
+    pub(crate) input_packets: u64,
+    pub(crate) input_errors: u64,
+    pub(crate) input_dropped: u64,
+    pub(crate) output_in_bytes: u64,
+    pub(crate) output_packets: u64,
+    pub(crate) output_errors: u64,
+    pub(crate) output_dropped: u64,
+}
+
+/// Pressure stall information, e.g. of `cpu.pressure`; older kernels have no `full` for CPU.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PressureStats {
+    pub(crate) some: Pressure,
+    pub(crate) full: Option<Pressure>,
+}
+
+/// Share of time stalled over 10, 60 and 300 seconds, and the total stall time.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Pressure {
+    pub(crate) avg10_in_percent: f32,
+    pub(crate) avg60_in_percent: f32,
+    pub(crate) avg300_in_percent: f32,
+    pub(crate) total_in_microseconds: u64,
+}
+
+impl Pressure {
+    fn values(&self) -> [f64; 4] {
+        [
+            f64::from(self.avg10_in_percent),
+            f64::from(self.avg60_in_percent),
+            f64::from(self.avg300_in_percent),
+            self.total_in_microseconds as f64,
+        ]
+    }
+}
+
+// Metric names have to be `'static`, hence the macro.
+macro_rules! pressure_metrics {
+    ($prefix:literal, $pressure:expr) => {{
+        let some = $pressure.as_ref().map(|pressure| pressure.some.values());
+        let full = $pressure.as_ref().and_then(|pressure| pressure.full.as_ref()).map(Pressure::values);
+        [
+            (concat!($prefix, "_some_avg10_in_percent"), some.map(|values| values[0])),
+            (concat!($prefix, "_some_avg60_in_percent"), some.map(|values| values[1])),
+            (concat!($prefix, "_some_avg300_in_percent"), some.map(|values| values[2])),
+            (concat!($prefix, "_some_total_in_microseconds"), some.map(|values| values[3])),
+            (concat!($prefix, "_full_avg10_in_percent"), full.map(|values| values[0])),
+            (concat!($prefix, "_full_avg60_in_percent"), full.map(|values| values[1])),
+            (concat!($prefix, "_full_avg300_in_percent"), full.map(|values| values[2])),
+            (concat!($prefix, "_full_total_in_microseconds"), full.map(|values| values[3])),
+        ]
+    }};
+}
+
+/// Service name under which exporters keyed by service publish the host's metrics.
+pub(crate) const HOST_SERVICE_NAME: &str = "host";
+
+/// Pressure stall information of the whole host, collected once per collection.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct HostStats {
+    pub(crate) cpu_pressure: Option<PressureStats>,
+    pub(crate) memory_pressure: Option<PressureStats>,
+    pub(crate) io_pressure: Option<PressureStats>,
+}
+
+impl HostStats {
+    /// Numeric metrics by their exported name, prefixed with `host_`; unavailable values are `None`.
+    pub fn metrics(&self) -> Vec<(&'static str, Option<f64>)> {
+        let mut metrics = vec![];
+        metrics.extend(pressure_metrics!("host_cpu_pressure", self.cpu_pressure));
+        metrics.extend(pressure_metrics!("host_memory_pressure", self.memory_pressure));
+        metrics.extend(pressure_metrics!("host_io_pressure", self.io_pressure));
+        metrics
+    }
+
+    /// Whether any pressure could be read; none is without PSI support of the kernel.
+    pub fn is_available(&self) -> bool {
+        self.cpu_pressure.is_some() || self.memory_pressure.is_some() || self.io_pressure.is_some()
+    }
+}
+
+/// A process inside a container.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ProcessStats {
+    /// In the PID namespace of the host.
+    pub(crate) pid: u32,
+    pub(crate) name: String,
+    pub(crate) command: String,
+    /// Of one core, averaged since the previous collection; `None` without a previous one.
+    pub(crate) cpu_usage_in_percent: Option<f32>,
+    pub(crate) mem_usage_in_bytes: u64,
+}
+
+/// The processes of a service's container using the most CPU and memory.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TopProcesses {
+    pub(crate) service_name: String,
+    pub(crate) container_name: String,
+    pub(crate) container_id_short: String,
+    pub(crate) timestamp: DateTime<Utc>,
+    pub(crate) by_cpu: Vec<ProcessStats>,
+    pub(crate) by_memory: Vec<ProcessStats>,
+}
+
+/// Disk usage of a service's container, collected on its own, slower interval.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct DiskUsage {
+    pub(crate) service_name: String,
+    pub(crate) container_name: String,
+    pub(crate) container_id: String,
+    pub(crate) container_id_short: String,
+    /// When the engine answered, as calculating the sizes takes a while.
+    pub(crate) timestamp: DateTime<Utc>,
+    /// Files written by the container outside of volumes.
+    #[serde(rename = "disk_writable_layer_in_bytes")]
+    pub(crate) writable_layer_in_bytes: Option<u64>,
+    /// Sum of the named volumes the container mounts.
+    #[serde(rename = "disk_volumes_in_bytes")]
+    pub(crate) volumes_in_bytes: Option<u64>,
+}
+
+impl DiskUsage {
+    /// Numeric metrics by their exported name, all exact byte values; unavailable values are `None`.
+    pub fn metrics(&self) -> [(&'static str, Option<f64>); 2] {
+        [
+            ("disk_writable_layer_in_bytes", self.writable_layer_in_bytes.map(|bytes| bytes as f64)),
+            ("disk_volumes_in_bytes", self.volumes_in_bytes.map(|bytes| bytes as f64)),
+        ]
+    }
+}
+
+/// Where a collection came from; the collector mode.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum CollectionSource {
+    CLI,
+    FILE,
+    SOCKET,
+}
+
+/// Stats of all containers from one collection.
+#[derive(Debug, PartialEq)]
+pub struct Collection {
+    /// Increases by one with every collection attempt since start, so gaps show attempts without stats; starts at 1.
+    pub(crate) sequence: u64,
+    pub(crate) source: CollectionSource,
+    pub(crate) started_at: DateTime<Utc>,
+    pub(crate) ended_at: DateTime<Utc>,
+    /// Time the stats refer to; the end of the collection unless the source tells.
+    pub(crate) timestamp: DateTime<Utc>,
+    pub(crate) stats: Vec<ContainerStats>,
+    pub(crate) host: HostStats,
+}
+
+impl Collection {
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+
+    pub fn duration(&self) -> TimeDelta {
+        self.ended_at - self.started_at
+    }
+}
+
+#[cfg(test)]
+impl Collection {
+    /// Collection of `stats` ended at the RFC 3339 `timestamp`, as exporters see it.
+    pub(crate) fn at(timestamp: &str, stats: Vec<ContainerStats>) -> Self {
+        let timestamp = DateTime::parse_from_rfc3339(timestamp).unwrap().to_utc();
+        Collection {
+            sequence: 1,
+            source: CollectionSource::CLI,
+            started_at: timestamp - TimeDelta::seconds(2),
+            ended_at: timestamp,
+            timestamp,
+            stats,
+            host: HostStats::default(),
+        }
+    }
+}
+
+/// Precision of the byte values of a `ContainerStats`.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub enum BytePrecision {
+    /// Parsed from human-readable strings like `318.6MiB`, which are rounded to 3-4 significant digits.
+    #[default]
+    ROUNDED,
+    /// Integer byte counts, e.g. from the Docker API or the CRI.
+    EXACT,
+}
+
+impl BytePrecision {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BytePrecision::ROUNDED => "ROUNDED",
+            BytePrecision::EXACT => "EXACT",
+        }
+    }
+}
+
+/// How the value of a metric evolves between collections.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MetricKind {
+    /// Current value, which may go up and down.
+    GAUGE,
+    /// Total which only increases while the container runs and restarts with it.
+    COUNTER,
+    /// Total which only increases and started before the container, i.e. at its creation or the host's boot.
+    LIFETIME,
+}
+
+impl MetricKind {
+    pub fn of(metric: &str) -> MetricKind {
+        match metric {
+            _ if COUNTER_METRICS.contains(&metric) => MetricKind::COUNTER,
+            _ if LIFETIME_COUNTER_METRICS.contains(&metric) => MetricKind::LIFETIME,
+            _ => MetricKind::GAUGE,
+        }
+    }
+
+    pub fn is_counter(&self) -> bool {
+        *self != MetricKind::GAUGE
+    }
+}
+
+/// Services with more than one container, e.g. while an update runs the old and new release side by side.
+pub(crate) fn shared_services<'a>(service_names: impl IntoIterator<Item = &'a str>) -> BTreeSet<&'a str> {
+    let mut seen = BTreeSet::new();
+    service_names.into_iter().filter(|service_name| !seen.insert(*service_name)).collect()
+}
+
+/// Whether the stats source still delivers current data.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SourceStatus {
+    pub(crate) stale: bool,
+    /// `None` if the source never reported a time of update.
+    pub(crate) last_update: Option<DateTime<Utc>>,
+}
+
+impl ContainerStats {
+    /// Full id, or the short one where the parser only knows that; cgroups and the engine match ids by prefix anyway.
+    pub(crate) fn id(&self) -> &str {
+        match self.container_id.is_empty() {
+            true => &self.container_id_short,
+            false => &self.container_id,
+        }
+    }
+
+    /// Numeric metrics by their exported name; unavailable values are `None`.
+    pub fn metrics(&self) -> Vec<(&'static str, Option<f64>)> {
+        let mut metrics = vec![
+            ("cpu_usage_in_percent", self.cpu_usage_in_percent.map(f64::from)),
+            ("normalized_cpu_usage_in_percent", self.normalized_cpu_usage_in_percent.map(f64::from)),
+            ("cpu_quota_in_cores", self.cpu_quota_in_cores.map(f64::from)),
+            ("host_cpu_cores", self.host_cpu_cores.map(f64::from)),
+            ("memory_usage_in_percent", self.mem_usage_in_percent.map(f64::from)),
+            ("memory_usage_in_bytes", bytes_as_f64(self.mem_usage)),
+            ("memory_limit_in_bytes", bytes_as_f64(self.mem_limit)),
+            ("memory_anon_in_bytes", bytes_as_f64(self.mem_anon)),
+            ("memory_file_in_bytes", bytes_as_f64(self.mem_file)),
+            ("memory_swap_in_bytes", bytes_as_f64(self.mem_swap)),
+            ("memory_working_set_in_bytes", bytes_as_f64(self.mem_working_set)),
+            ("memory_oom_events", self.mem_oom_events.map(|events| events as f64)),
+            ("memory_oom_kill_events", self.mem_oom_kill_events.map(|events| events as f64)),
+            ("network_input_in_bytes", bytes_as_f64(self.network_input)),
+            ("network_output_in_bytes", bytes_as_f64(self.network_output)),
+            ("block_device_input_in_bytes", bytes_as_f64(self.block_device_input)),
+            ("block_device_output_in_bytes", bytes_as_f64(self.block_device_output)),
+            ("amount_of_pids", self.amount_of_pids.map(f64::from)),
+            ("healthy", self.health_status.as_deref().and_then(health_as_f64)),
+            ("restart_count", self.restart_count.map(f64::from)),
+            ("uptime_in_seconds", self.uptime_in_seconds.map(|uptime| uptime as f64)),
+            ("exit_code", self.exit_code.map(|exit_code| exit_code as f64)),
+            ("oom_killed", self.oom_killed.map(|oom_killed| f64::from(u8::from(oom_killed)))),
+        ];
+        metrics.extend(pressure_metrics!("cpu_pressure", self.cpu_pressure));
+        metrics.extend(pressure_metrics!("memory_pressure", self.memory_pressure));
+        metrics.extend(pressure_metrics!("io_pressure", self.io_pressure));
+        metrics
+    }
+
+    /// Names of all metrics, including those per network interface, of disk usage and of the host.
+    pub fn metric_names() -> Vec<&'static str> {
+        let mut names: Vec<&'static str> =
+            ContainerStats::default().metrics().into_iter().map(|(name, _)| name).collect();
+        let with_interface = ContainerStats {
+            network_interfaces: BTreeMap::from([(String::new(), NetworkInterfaceStats::default())]),
+            ..Default::default()
+        };
+        names.extend(with_interface.interface_metrics().into_iter().map(|(_, name, _)| name));
+        names.extend(DiskUsage::default().metrics().map(|(name, _)| name));
+        names.extend(HostStats::default().metrics().into_iter().map(|(name, _)| name));
+        names
+    }
+
+    /// Numeric metrics of every network interface as `(interface, metric, value)`.
+    pub fn interface_metrics(&self) -> Vec<(&str, &'static str, f64)> {
+        self.network_interfaces
+            .iter()
+            .flat_map(|(interface, stats)| {
+                [
+                    ("network_interface_input_in_bytes", stats.input_in_bytes),
+                    ("network_interface_input_packets", stats.input_packets),
+                    ("network_interface_input_errors", stats.input_errors),
+                    ("network_interface_input_dropped", stats.input_dropped),
+                    ("network_interface_output_in_bytes", stats.output_in_bytes),
+                    ("network_interface_output_packets", stats.output_packets),
+                    ("network_interface_output_errors", stats.output_errors),
+                    ("network_interface_output_dropped", stats.output_dropped),
+                ]
+                .map(|(metric, value)| (interface.as_str(), metric, value as f64))
+            })
+            .collect()
+    }
+
+    /// Precision of the value of `metric`; `None` for metrics which are no byte values.
+    pub fn precision_of(&self, metric: &str) -> Option<BytePrecision> {
+        match metric {
+            _ if EXACT_BYTE_METRICS.contains(&metric) => Some(BytePrecision::EXACT),
+            _ => metric.ends_with("_in_bytes").then_some(self.byte_precision),
+        }
+    }
+}
+
+// 1 if healthy, 0 if unhealthy; unknown while the first health check is still pending.
+fn health_as_f64(health_status: &str) -> Option<f64> {
+    match health_status {
+        "healthy" => Some(1.0),
+        "unhealthy" => Some(0.0),
+        _ => None,
+    }
+}
+
+// Read from cgroup files or the engine API.
+const EXACT_BYTE_METRICS: [&str; 4] = [
+    "memory_anon_in_bytes",
+    "memory_file_in_bytes",
+    "memory_swap_in_bytes",
+    "memory_working_set_in_bytes",
+];
+
+// Totals of the container's cgroup or network namespace, both recreated on every start.
+const COUNTER_METRICS: [&str; 20] = [
+    "network_input_in_bytes",
+    "network_output_in_bytes",
+    "block_device_input_in_bytes",
+    "block_device_output_in_bytes",
+    "memory_oom_events",
+    "memory_oom_kill_events",
+    "cpu_pressure_some_total_in_microseconds",
+    "cpu_pressure_full_total_in_microseconds",
+    "memory_pressure_some_total_in_microseconds",
+    "memory_pressure_full_total_in_microseconds",
+    "io_pressure_some_total_in_microseconds",
+    "io_pressure_full_total_in_microseconds",
+    "network_interface_input_in_bytes",
+    "network_interface_input_packets",
+    "network_interface_input_errors",
+    "network_interface_input_dropped",
+    "network_interface_output_in_bytes",
+    "network_interface_output_packets",
+    "network_interface_output_errors",
+    "network_interface_output_dropped",
+];
+
+// Restarts count since the container's creation, host pressure since boot.
+const LIFETIME_COUNTER_METRICS: [&str; 7] = [
+    "restart_count",
+    "host_cpu_pressure_some_total_in_microseconds",
+    "host_cpu_pressure_full_total_in_microseconds",
+    "host_memory_pressure_some_total_in_microseconds",
+    "host_memory_pressure_full_total_in_microseconds",
+    "host_io_pressure_some_total_in_microseconds",
+    "host_io_pressure_full_total_in_microseconds",
+];
+
+fn bytes_as_f64(value: Option<Byte>) -> Option<f64> {
+    value.map(|byte| byte.as_u64() as f64)
+}
+
+// Byte values are serialized as plain integers.
+fn serialize_bytes<S: Serializer>(value: &Option<Byte>, serializer: S) -> Result<S::Ok, S::Error> {
+    value.map(|byte| byte.as_u64()).serialize(serializer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_serialize_fields_with_metric_names() {
+        let serde_json::Value::Object(fields) = serde_json::to_value(ContainerStats::default()).unwrap() else {
+            panic!("Stats are not serialized as object");
+        };
+
+        let unmatched: Vec<&str> = ContainerStats::metric_names()
+            .into_iter()
+            .filter(|metric| !fields.contains_key(*metric))
+            .filter(|metric| *metric != "healthy" && !metric.contains("_pressure_"))
+            .filter(|metric| !metric.starts_with("network_interface_") && !metric.starts_with("disk_"))
+            .collect();
+        assert_eq!(unmatched, Vec::<&str>::new());
+    }
+
+    #[test]
+    fn should_only_declare_known_metrics_as_counters() {
+        let names = ContainerStats::metric_names();
+
+        let unknown: Vec<&str> = COUNTER_METRICS
+            .into_iter()
+            .chain(LIFETIME_COUNTER_METRICS)
+            .filter(|metric| !names.contains(metric))
+            .collect();
+
+        assert_eq!(unknown, Vec::<&str>::new());
+        assert_eq!(MetricKind::of("restart_count"), MetricKind::LIFETIME);
+        assert_eq!(MetricKind::of("memory_oom_events"), MetricKind::COUNTER);
+        assert_eq!(MetricKind::of("cpu_pressure_some_avg10_in_percent"), MetricKind::GAUGE);
+    }
 }
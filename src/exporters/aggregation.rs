@@ -0,0 +1,250 @@
+use crate::domain::{ContainerStats, HostStats, HOST_SERVICE_NAME};
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct MetricAggregate {
+    pub(crate) min: f64,
+    pub(crate) max: f64,
+    pub(crate) sum: f64,
+    pub(crate) count: u32,
+    pub(crate) last: f64,
+}
+
+impl MetricAggregate {
+    fn new(value: f64) -> Self {
+        MetricAggregate {
+            min: value,
+            max: value,
+            sum: value,
+            count: 1,
+            last: value,
+        }
+    }
+
+    fn add(&mut self, value: f64) {
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.sum += value;
+        self.count += 1;
+        self.last = value;
+    }
+
+    pub(crate) fn avg(&self) -> f64 {
+        self.sum / self.count as f64
+    }
+
+    /// Statistic name as used in topics, e.g. `.../cpu_usage_in_percent/avg`, with its value.
+    pub(crate) fn statistics(&self) -> [(&'static str, f64); 4] {
+        [
+            ("min", self.min),
+            ("max", self.max),
+            ("avg", self.avg()),
+            ("last", self.last),
+        ]
+    }
+}
+
+/// Aggregates keyed by (service name, metric, interface of per-interface metrics).
+pub(crate) type Aggregates = BTreeMap<(String, String, Option<String>), MetricAggregate>;
+
+/// Aggregates metrics per service, the host's as service `host`, over wall-clock windows aligned to the epoch, e.g.
+/// 12:00:00 to 12:05:00 for 300s.
+pub(crate) struct Aggregator {
+    window: i64,
+    window_end: Option<DateTime<Utc>>,
+    aggregates: Aggregates,
+}
+
+impl Aggregator {
+    pub(crate) fn new(window_in_seconds: u64) -> Self {
+        Aggregator {
+            window: i64::try_from(window_in_seconds).unwrap_or(i64::MAX).max(1),
+            window_end: None,
+            aggregates: BTreeMap::new(),
+        }
+    }
+
+    /// Adds a collection; returns the end and aggregates of the previous window once `timestamp` is past it.
+    pub(crate) fn add(
+        &mut self,
+        stats: &[ContainerStats],
+        host: &HostStats,
+        metrics: &[String],
+        timestamp: DateTime<Utc>,
+    ) -> Option<(DateTime<Utc>, Aggregates)> {
+        let completed = match self.window_end {
+            Some(window_end) if timestamp < window_end => None,
+            previous_window_end => {
+                self.window_end = self.end_of_window(timestamp);
+                previous_window_end.map(|window_end| (window_end, std::mem::take(&mut self.aggregates)))
+            }
+        };
+
+        let container_values = stats.iter().flat_map(|stat| {
+            stat.metrics()
+                .into_iter()
+                .filter_map(|(metric, value)| Some((stat.service_name.as_str(), None, metric, value?)))
+                .chain(
+                    stat.interface_metrics()
+                        .into_iter()
+                        .map(|(interface, metric, value)| (stat.service_name.as_str(), Some(interface), metric, value)),
+                )
+        });
+        let host_values =
+            host.metrics().into_iter().filter_map(|(metric, value)| Some((HOST_SERVICE_NAME, None, metric, value?)));
+        for (service_name, interface, metric, value) in container_values.chain(host_values) {
+            if !metrics.iter().any(|selected| selected == metric) {
+                continue;
+            }
+            self.aggregates
+                .entry((service_name.to_string(), metric.to_string(), interface.map(str::to_string)))
+                .and_modify(|aggregate| aggregate.add(value))
+                .or_insert_with(|| MetricAggregate::new(value));
+        }
+        completed
+    }
+
+    fn end_of_window(&self, timestamp: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let window_start = timestamp.timestamp().div_euclid(self.window) * self.window;
+        DateTime::from_timestamp(window_start.saturating_add(self.window), 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{NetworkInterfaceStats, Pressure, PressureStats};
+    use rstest::rstest;
+
+    fn setup_stats(service_name: &str, cpu_usage_in_percent: Option<f32>) -> ContainerStats {
+        ContainerStats {
+            service_name: service_name.to_string(),
+            cpu_usage_in_percent,
+            mem_usage_in_percent: Some(50.0),
+            ..Default::default()
+        }
+    }
+
+    fn at(timestamp: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(timestamp).unwrap().to_utc()
+    }
+
+    fn key(service_name: &str, metric: &str, interface: Option<&str>) -> (String, String, Option<String>) {
+        (service_name.to_string(), metric.to_string(), interface.map(str::to_string))
+    }
+
+    #[rstest]
+    #[case::start("2025-03-01T12:00:00Z", "2025-03-01T12:05:00Z")]
+    #[case::within("2025-03-01T12:04:59Z", "2025-03-01T12:05:00Z")]
+    #[case::before_epoch("1969-12-31T23:59:59Z", "1970-01-01T00:00:00Z")]
+    fn should_align_windows_to_wall_clock(#[case] timestamp: &str, #[case] expected: &str) {
+        let actual = Aggregator::new(300).end_of_window(at(timestamp));
+
+        assert_eq!(actual, Some(at(expected)));
+    }
+
+    #[test]
+    fn should_emit_min_max_avg_last_per_service_after_window() {
+        let mut aggregator = Aggregator::new(45);
+        let metrics = vec!["cpu_usage_in_percent".to_string()];
+        let host = HostStats::default();
+
+        let first = [setup_stats("a", Some(2.0)), setup_stats("b", Some(1.0))];
+        assert_eq!(aggregator.add(&first, &host, &metrics, at("2025-03-01T12:00:00Z")), None);
+        let second = [setup_stats("a", Some(6.0)), setup_stats("b", None)];
+        assert_eq!(aggregator.add(&second, &host, &metrics, at("2025-03-01T12:00:15Z")), None);
+        assert_eq!(aggregator.add(&[setup_stats("a", Some(1.0))], &host, &metrics, at("2025-03-01T12:00:30Z")), None);
+        let (window_end, actual) = aggregator.add(&[], &host, &metrics, at("2025-03-01T12:00:45Z")).unwrap();
+
+        assert_eq!(window_end, at("2025-03-01T12:00:45Z"));
+        assert_eq!(actual.len(), 2);
+        let a = &actual[&key("a", "cpu_usage_in_percent", None)];
+        assert_eq!(a.statistics(), [("min", 1.0), ("max", 6.0), ("avg", 3.0), ("last", 1.0)]);
+        let b = &actual[&key("b", "cpu_usage_in_percent", None)];
+        assert_eq!(b.statistics(), [("min", 1.0), ("max", 1.0), ("avg", 1.0), ("last", 1.0)]);
+    }
+
+    #[test]
+    fn should_aggregate_interface_metrics_per_interface() {
+        let mut aggregator = Aggregator::new(60);
+        let metrics = vec!["network_interface_input_in_bytes".to_string()];
+        let host = HostStats::default();
+        let with_interfaces = |eth0: u64, eth1: u64| ContainerStats {
+            service_name: "a".to_string(),
+            network_interfaces: BTreeMap::from([
+                (
+                    "eth0".to_string(),
+                    NetworkInterfaceStats {
+                        input_in_bytes: eth0,
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "eth1".to_string(),
+                    NetworkInterfaceStats {
+                        input_in_bytes: eth1,
+                        ..Default::default()
+                    },
+                ),
+            ]),
+            ..Default::default()
+        };
+
+        aggregator.add(&[with_interfaces(100, 10)], &host, &metrics, at("2025-03-01T12:00:00Z"));
+        aggregator.add(&[with_interfaces(300, 20)], &host, &metrics, at("2025-03-01T12:00:30Z"));
+        let (_, actual) = aggregator.add(&[], &host, &metrics, at("2025-03-01T12:01:00Z")).unwrap();
+
+        assert_eq!(actual.len(), 2);
+        let eth0 = &actual[&key("a", "network_interface_input_in_bytes", Some("eth0"))];
+        assert_eq!(eth0.statistics(), [("min", 100.0), ("max", 300.0), ("avg", 200.0), ("last", 300.0)]);
+        assert_eq!(actual[&key("a", "network_interface_input_in_bytes", Some("eth1"))].last, 20.0);
+    }
+
+    #[test]
+    fn should_aggregate_host_metrics_once_as_service_host() {
+        let mut aggregator = Aggregator::new(60);
+        let metrics = vec!["host_io_pressure_some_avg10_in_percent".to_string()];
+        let host = HostStats {
+            io_pressure: Some(PressureStats {
+                some: Pressure {
+                    avg10_in_percent: 0.5,
+                    avg60_in_percent: 0.25,
+                    avg300_in_percent: 0.125,
+                    total_in_microseconds: 4_000,
+                },
+                full: None,
+            }),
+            ..Default::default()
+        };
+
+        let stats = [setup_stats("a", Some(1.0)), setup_stats("b", Some(2.0))];
+        aggregator.add(&stats, &host, &metrics, at("2025-03-01T12:00:00Z"));
+        let (_, actual) = aggregator.add(&[], &HostStats::default(), &metrics, at("2025-03-01T12:01:00Z")).unwrap();
+
+        assert_eq!(actual.len(), 1);
+        let io = &actual[&key(HOST_SERVICE_NAME, "host_io_pressure_some_avg10_in_percent", None)];
+        assert_eq!(io.count, 1);
+        assert_eq!(io.last, 0.5);
+    }
+
+    #[test]
+    fn should_not_stretch_windows_over_missed_collections() {
+        let mut aggregator = Aggregator::new(60);
+        let metrics = vec!["cpu_usage_in_percent".to_string()];
+        let host = HostStats::default();
+
+        aggregator.add(&[setup_stats("a", Some(2.0))], &host, &metrics, at("2025-03-01T12:00:50Z"));
+        // Collections between 12:01:00 and 12:02:10 failed.
+        let (window_end, actual) =
+            aggregator.add(&[setup_stats("a", Some(4.0))], &host, &metrics, at("2025-03-01T12:02:10Z")).unwrap();
+        let (next_window_end, next) = aggregator.add(&[], &host, &metrics, at("2025-03-01T12:03:00Z")).unwrap();
+
+        assert_eq!(window_end, at("2025-03-01T12:01:00Z"));
+        assert_eq!(actual[&key("a", "cpu_usage_in_percent", None)].last, 2.0);
+        assert_eq!(next_window_end, at("2025-03-01T12:03:00Z"));
+        let a = &next[&key("a", "cpu_usage_in_percent", None)];
+        assert_eq!(a.count, 1);
+        assert_eq!(a.last, 4.0);
+    }
+}
@@ -0,0 +1,376 @@
+use crate::domain::{Collection, ContainerStats};
+use crate::exporters::exporter::Exporter;
+use crate::exporters::number_format::format_value;
+use crate::util::config::{build_path, get_config, CONFIG_DIR};
+use chrono::{DateTime, Duration, NaiveDate, SecondsFormat, Utc};
+use lazy_static::lazy_static;
+use log::{error, info, warn};
+use serde::Deserialize;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const FILE_PREFIX: &str = "stats-";
+const FILE_SUFFIX: &str = ".csv";
+const IDENTITY_COLUMNS: [&str; 6] = [
+    "timestamp",
+    "device_id",
+    "container_id",
+    "container_id_short",
+    "container_name",
+    "service_name",
+];
+const TEXT_COLUMNS: [&str; 4] = ["state", "health_status", "started_at", "byte_precision"];
+
+#[derive(Clone, Deserialize, Debug, PartialEq)]
+pub(crate) struct CsvArchiveConfig {
+    directory: String,
+    device_id: String,
+    retention_in_days: i64,
+    max_total_size_in_bytes: u64,
+}
+
+lazy_static! {
+    pub(crate) static ref CONFIG: CsvArchiveConfig = get_config(build_path(vec![&CONFIG_DIR, "csv_archive.config.json"]));
+}
+
+pub struct CsvArchiveExporter;
+
+impl Exporter for CsvArchiveExporter {
+    fn export(&mut self, collection: &Collection) {
+        let timestamp = collection.timestamp();
+        append_to_archive(&collection.stats, timestamp, &CONFIG)
+            .and_then(|_| enforce_limits(timestamp.date_naive(), &CONFIG))
+            .unwrap_or_else(|err| error!("Archiving stats failed! Because of {}", err))
+    }
+}
+
+/// Writes the archived rows in `[from, to]` as one CSV document with the current columns.
+pub fn export_range(
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    config: &CsvArchiveConfig,
+    out: impl Write,
+) -> anyhow::Result<()> {
+    let header = header();
+    let mut writer = csv::Writer::from_writer(out);
+    writer.write_record(&header)?;
+
+    for (date, path) in list_archive_files(config)? {
+        if date < from.date_naive() || date > to.date_naive() {
+            continue;
+        }
+        let mut reader = csv::Reader::from_path(&path)?;
+        let file_header = reader.headers()?.clone();
+        for record in reader.records() {
+            let record = record?;
+            let Some(timestamp) = record
+                .get(0)
+                .and_then(|value| DateTime::parse_from_rfc3339(value).ok())
+                .map(|timestamp| timestamp.with_timezone(&Utc))
+            else {
+                warn!("Skipping archived row without valid timestamp in {:?}", path);
+                continue;
+            };
+            if timestamp < from || timestamp > to {
+                continue;
+            }
+            let row: Vec<&str> = header
+                .iter()
+                .map(|column| {
+                    file_header
+                        .iter()
+                        .position(|file_column| file_column == column)
+                        .and_then(|index| record.get(index))
+                        .unwrap_or("")
+                })
+                .collect();
+            writer.write_record(row)?;
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+fn append_to_archive(
+    stats: &[ContainerStats],
+    timestamp: DateTime<Utc>,
+    config: &CsvArchiveConfig,
+) -> anyhow::Result<()> {
+    fs::create_dir_all(&config.directory)?;
+    let header = header();
+    let path = current_archive_path(config, timestamp.date_naive(), &header)?;
+    let is_new_file = !path.exists();
+
+    let file = OpenOptions::new().create(true).append(true).open(&path)?;
+    let mut writer = csv::Writer::from_writer(file);
+    if is_new_file {
+        writer.write_record(&header)?;
+    }
+    for stat in stats {
+        writer.write_record(to_record(stat, timestamp, &config.device_id))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn enforce_limits(today: NaiveDate, config: &CsvArchiveConfig) -> anyhow::Result<()> {
+    let oldest_date_to_keep = today - Duration::days(config.retention_in_days);
+    let mut files = list_archive_files(config)?;
+
+    for (date, path) in files.iter().filter(|(date, _)| *date < oldest_date_to_keep) {
+        info!("Removing archive file of {} because of retention", date);
+        fs::remove_file(path)?;
+    }
+    files.retain(|(date, _)| *date >= oldest_date_to_keep);
+
+    let mut total_size: u64 = files
+        .iter()
+        .map(|(_, path)| fs::metadata(path).map(|metadata| metadata.len()).unwrap_or(0))
+        .sum();
+    // The file of the current day is never removed, even if it alone exceeds the limit.
+    for (date, path) in files.iter().filter(|(date, _)| *date < today) {
+        if total_size <= config.max_total_size_in_bytes {
+            break;
+        }
+        warn!("Removing archive file of {} because archive exceeds size limit", date);
+        total_size -= fs::metadata(path)?.len();
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Archive files sorted by date and part, oldest first.
+fn list_archive_files(config: &CsvArchiveConfig) -> anyhow::Result<Vec<(NaiveDate, PathBuf)>> {
+    let directory = Path::new(&config.directory);
+    if !directory.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut files: Vec<(NaiveDate, u32, PathBuf)> = fs::read_dir(directory)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_str()?.to_string();
+            let name = file_name.strip_prefix(FILE_PREFIX)?.strip_suffix(FILE_SUFFIX)?;
+            let (date, part) = match name.split_once('_') {
+                Some((date, part)) => (date, part.parse().ok()?),
+                None => (name, 1),
+            };
+            let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+            Some((date, part, entry.path()))
+        })
+        .collect();
+    files.sort();
+    Ok(files.into_iter().map(|(date, _, path)| (date, path)).collect())
+}
+
+fn archive_path(config: &CsvArchiveConfig, date: NaiveDate, part: u32) -> PathBuf {
+    let suffix = if part > 1 { format!("_{}", part) } else { String::new() };
+    Path::new(&config.directory).join(format!("{}{}{}{}", FILE_PREFIX, date.format("%Y-%m-%d"), suffix, FILE_SUFFIX))
+}
+
+// The last file of the day, or a new part if its header differs from `header`.
+fn current_archive_path(config: &CsvArchiveConfig, date: NaiveDate, header: &[String]) -> anyhow::Result<PathBuf> {
+    let mut part = 1;
+    while archive_path(config, date, part + 1).exists() {
+        part += 1;
+    }
+    let path = archive_path(config, date, part);
+    if !path.exists() {
+        return Ok(path);
+    }
+    if csv::Reader::from_path(&path)?.headers()?.iter().eq(header.iter()) {
+        return Ok(path);
+    }
+    info!("Columns changed; archiving to part {} of {}", part + 1, date);
+    Ok(archive_path(config, date, part + 1))
+}
+
+fn header() -> Vec<String> {
+    let metrics = ContainerStats::default().metrics().into_iter().map(|(metric, _)| metric);
+    IDENTITY_COLUMNS
+        .into_iter()
+        .chain(metrics)
+        .chain(TEXT_COLUMNS)
+        .map(String::from)
+        .collect()
+}
+
+// Same order as `header`.
+fn to_record(stats: &ContainerStats, timestamp: DateTime<Utc>, device_id: &str) -> Vec<String> {
+    let mut record = vec![
+        timestamp.to_rfc3339_opts(SecondsFormat::Secs, true),
+        device_id.to_string(),
+        stats.container_id.clone(),
+        stats.container_id_short.clone(),
+        stats.container_name.clone(),
+        stats.service_name.clone(),
+    ];
+    record.extend(stats.metrics().into_iter().map(|(_, value)| value.map(format_value).unwrap_or_default()));
+    record.extend([
+        stats.state.clone().unwrap_or_default(),
+        stats.health_status.clone().unwrap_or_default(),
+        stats
+            .started_at
+            .map(|started_at| started_at.to_rfc3339_opts(SecondsFormat::Secs, true))
+            .unwrap_or_default(),
+        stats.byte_precision.as_str().to_string(),
+    ]);
+    record
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_support::temp_dir;
+    use byte_unit::{Byte, Unit};
+
+    fn setup_test_data(service_name: &str) -> ContainerStats {
+        ContainerStats {
+            container_id: String::from(
+                "4889ab0711ac17500a5de1c3d50eb6bfbc7eb367d349797f9fe8dcb92fd9e914",
+            ),
+            container_id_short: String::from("4889ab0711ac"),
+            container_name: format!("{}_10800414_3361262_f54e4ffc136d1344ee98993b36b9deeb", service_name),
+            service_name: service_name.to_string(),
+            cpu_usage_in_percent: Some(1.75),
+            mem_usage_in_percent: Some(31.12),
+            mem_usage: Byte::from_i64_with_unit(318, Unit::MiB),
+            mem_limit: Byte::from_i64_with_unit(1, Unit::GiB),
+            network_input: Byte::from_i64_with_unit(541, Unit::MB),
+            network_output: None,
+            block_device_input: Byte::from_i64_with_unit(0, Unit::B),
+            block_device_output: Byte::from_i64_with_unit(0, Unit::B),
+            amount_of_pids: Some(26),
+            ..Default::default()
+        }
+    }
+
+    fn temp_config(test_name: &str, max_total_size_in_bytes: u64) -> CsvArchiveConfig {
+        let dir = temp_dir(&format!("csv_archive_{}", test_name));
+        CsvArchiveConfig {
+            directory: dir.to_str().unwrap().to_string(),
+            device_id: "d35a7ea843c61c723a12f19a41c26ef1".to_string(),
+            retention_in_days: 2,
+            max_total_size_in_bytes,
+        }
+    }
+
+    fn timestamp(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn should_build_header_from_identity_metric_and_text_columns() {
+        let actual = header();
+
+        assert_eq!(
+            actual[..8].join(","),
+            "timestamp,device_id,container_id,container_id_short,container_name,service_name,cpu_usage_in_percent,\
+            normalized_cpu_usage_in_percent"
+        );
+        assert!(actual.contains(&"cpu_pressure_some_avg10_in_percent".to_string()));
+        assert!(!actual.iter().any(|column| column.starts_with("network_interface_")));
+        assert_eq!(actual[actual.len() - 4..].join(","), "state,health_status,started_at,byte_precision");
+    }
+
+    #[test]
+    fn should_map_to_record_in_header_order() {
+        let stats = ContainerStats {
+            state: Some("running".to_string()),
+            restart_count: Some(2),
+            ..setup_test_data("b")
+        };
+
+        let actual = to_record(&stats, timestamp("2025-03-01T12:00:00Z"), "my-device");
+
+        let cell = |column: &str| actual[header().iter().position(|name| name == column).unwrap()].as_str();
+        assert_eq!(actual.len(), header().len());
+        assert_eq!(actual[..6].join(","), "2025-03-01T12:00:00Z,my-device,\
+            4889ab0711ac17500a5de1c3d50eb6bfbc7eb367d349797f9fe8dcb92fd9e914,4889ab0711ac,\
+            b_10800414_3361262_f54e4ffc136d1344ee98993b36b9deeb,b");
+        assert_eq!(cell("cpu_usage_in_percent"), "1.75");
+        assert_eq!(cell("memory_usage_in_percent"), "31.12");
+        assert_eq!(cell("memory_usage_in_bytes"), "333447168");
+        assert_eq!(cell("network_output_in_bytes"), "");
+        assert_eq!(cell("restart_count"), "2");
+        assert_eq!(cell("state"), "running");
+        assert_eq!(cell("byte_precision"), "ROUNDED");
+    }
+
+    #[test]
+    fn should_start_new_part_of_day_when_columns_changed() {
+        let config = temp_config("columns", u64::MAX);
+        fs::create_dir_all(&config.directory).unwrap();
+        let date = NaiveDate::from_ymd_opt(2025, 3, 1).unwrap();
+        fs::write(archive_path(&config, date, 1), "timestamp,device_id,state\n").unwrap();
+
+        append_to_archive(&[setup_test_data("a")], timestamp("2025-03-01T12:00:00Z"), &config).unwrap();
+        append_to_archive(&[setup_test_data("b")], timestamp("2025-03-01T12:01:00Z"), &config).unwrap();
+
+        let files = list_archive_files(&config).unwrap();
+        assert_eq!(files.len(), 2);
+        assert!(files[1].1.ends_with("stats-2025-03-01_2.csv"));
+        assert_eq!(fs::read_to_string(&files[1].1).unwrap().lines().count(), 3);
+        let mut out: Vec<u8> = vec![];
+        export_range(timestamp("2025-03-01T00:00:00Z"), timestamp("2025-03-02T00:00:00Z"), &config, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 3);
+    }
+
+    #[test]
+    fn should_append_to_daily_files_and_export_range() {
+        let config = temp_config("range", u64::MAX);
+        append_to_archive(&[setup_test_data("a")], timestamp("2025-03-01T23:59:00Z"), &config).unwrap();
+        append_to_archive(&[setup_test_data("b")], timestamp("2025-03-02T00:01:00Z"), &config).unwrap();
+        append_to_archive(&[setup_test_data("c")], timestamp("2025-03-02T12:00:00Z"), &config).unwrap();
+        let mut out: Vec<u8> = vec![];
+
+        export_range(timestamp("2025-03-01T23:00:00Z"), timestamp("2025-03-02T01:00:00Z"), &config, &mut out).unwrap();
+
+        let exported = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = exported.lines().collect();
+        assert_eq!(list_archive_files(&config).unwrap().len(), 2);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], header().join(","));
+        assert!(lines[1].starts_with("2025-03-01T23:59:00Z,d35a7ea843c61c723a12f19a41c26ef1"));
+        assert!(lines[2].starts_with("2025-03-02T00:01:00Z,d35a7ea843c61c723a12f19a41c26ef1"));
+    }
+
+    #[test]
+    fn should_remove_files_beyond_retention() {
+        let config = temp_config("retention", u64::MAX);
+        for day in ["2025-03-01", "2025-03-02", "2025-03-03", "2025-03-04"] {
+            let at = timestamp(&format!("{}T12:00:00Z", day));
+            append_to_archive(&[setup_test_data("b")], at, &config).unwrap();
+        }
+
+        enforce_limits(NaiveDate::from_ymd_opt(2025, 3, 4).unwrap(), &config).unwrap();
+
+        let dates: Vec<String> = list_archive_files(&config)
+            .unwrap()
+            .into_iter()
+            .map(|(date, _)| date.to_string())
+            .collect();
+        assert_eq!(dates, vec!["2025-03-02", "2025-03-03", "2025-03-04"]);
+    }
+
+    #[test]
+    fn should_remove_oldest_files_beyond_size_limit_but_keep_today() {
+        let config = temp_config("size", 1);
+        for day in ["2025-03-03", "2025-03-04"] {
+            let at = timestamp(&format!("{}T12:00:00Z", day));
+            append_to_archive(&[setup_test_data("b")], at, &config).unwrap();
+        }
+
+        enforce_limits(NaiveDate::from_ymd_opt(2025, 3, 4).unwrap(), &config).unwrap();
+
+        let dates: Vec<String> = list_archive_files(&config)
+            .unwrap()
+            .into_iter()
+            .map(|(date, _)| date.to_string())
+            .collect();
+        assert_eq!(dates, vec!["2025-03-04"]);
+    }
+}
@@ -0,0 +1,183 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Deserialize, Debug, PartialEq)]
+pub(crate) enum DeadbandMode {
+    /// Change in the unit of the metric, e.g. percentage points for `cpu_usage_in_percent`.
+    ABSOLUTE,
+    /// Change relative to the last published value.
+    PERCENT,
+}
+
+#[derive(Clone, Deserialize, Debug, PartialEq)]
+pub(crate) struct Deadband {
+    pub(crate) mode: DeadbandMode,
+    pub(crate) value: f64,
+}
+
+impl Deadband {
+    // Values within the deadband are not published; zero publishes every change.
+    fn is_exceeded(&self, last: f64, value: f64) -> bool {
+        let change = (value - last).abs();
+        match self.mode {
+            DeadbandMode::ABSOLUTE => change > self.value,
+            DeadbandMode::PERCENT => change > last.abs() * self.value / 100.0,
+        }
+    }
+}
+
+#[derive(Clone, Deserialize, Debug, PartialEq)]
+pub(crate) struct DeadbandConfig {
+    /// By metric name; metrics without a deadband are published with every collection.
+    #[serde(default)]
+    pub(crate) metrics: BTreeMap<String, Deadband>,
+    #[serde(default = "default_max_silence_in_seconds")]
+    pub(crate) max_silence_in_seconds: u64,
+}
+
+fn default_max_silence_in_seconds() -> u64 {
+    300
+}
+
+/// Suppresses values within the deadband of their metric, unless silent for `max_silence_in_seconds`.
+pub(crate) struct DeadbandFilter {
+    config: DeadbandConfig,
+    // Last published value and its time by topic.
+    published: HashMap<String, (f64, DateTime<Utc>)>,
+}
+
+impl DeadbandFilter {
+    pub(crate) fn new(config: DeadbandConfig) -> Self {
+        DeadbandFilter {
+            config,
+            published: HashMap::new(),
+        }
+    }
+
+    /// Whether `value` of `metric` is to be published to `topic` at `now`; if so, it is remembered as published.
+    pub(crate) fn should_publish(&mut self, topic: &str, metric: &str, value: f64, now: DateTime<Utc>) -> bool {
+        let Some(deadband) = self.config.metrics.get(metric) else {
+            return true;
+        };
+        let max_silence = TimeDelta::seconds(self.config.max_silence_in_seconds as i64);
+        let publish = match self.published.get(topic) {
+            None => true,
+            Some((last, published_at)) => now - *published_at >= max_silence || deadband.is_exceeded(*last, value),
+        };
+        if publish {
+            self.published.insert(topic.to_string(), (value, now));
+        }
+        publish
+    }
+
+    /// Forgets topics silent for `max_silence_in_seconds`, e.g. of removed containers.
+    pub(crate) fn expire(&mut self, now: DateTime<Utc>) {
+        let max_silence = TimeDelta::seconds(self.config.max_silence_in_seconds as i64);
+        self.published.retain(|_, (_, published_at)| now - *published_at < max_silence);
+    }
+
+    /// Forgets all published values, e.g. after a reconnect.
+    pub(crate) fn reset(&mut self) {
+        self.published.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn setup_filter() -> DeadbandFilter {
+        DeadbandFilter::new(DeadbandConfig {
+            metrics: BTreeMap::from([
+                (
+                    "cpu_usage_in_percent".to_string(),
+                    Deadband {
+                        mode: DeadbandMode::ABSOLUTE,
+                        value: 1.0,
+                    },
+                ),
+                (
+                    "memory_usage_in_bytes".to_string(),
+                    Deadband {
+                        mode: DeadbandMode::PERCENT,
+                        value: 5.0,
+                    },
+                ),
+            ]),
+            max_silence_in_seconds: 60,
+        })
+    }
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_740_830_400 + seconds, 0).unwrap()
+    }
+
+    #[test]
+    fn should_deserialize_config_with_default_max_silence() {
+        let json = r#"{"metrics": {"cpu_usage_in_percent": {"mode": "ABSOLUTE", "value": 1.0}}}"#;
+
+        let actual: DeadbandConfig = serde_json::from_str(json).unwrap();
+
+        assert_eq!(actual.max_silence_in_seconds, 300);
+        assert_eq!(actual.metrics["cpu_usage_in_percent"].mode, DeadbandMode::ABSOLUTE);
+    }
+
+    #[rstest]
+    #[case::absolute_within("cpu_usage_in_percent", 10.0, 11.0, false)]
+    #[case::absolute_beyond("cpu_usage_in_percent", 10.0, 8.5, true)]
+    #[case::percent_within("memory_usage_in_bytes", 1000.0, 1040.0, false)]
+    #[case::percent_beyond("memory_usage_in_bytes", 1000.0, 940.0, true)]
+    #[case::percent_from_zero("memory_usage_in_bytes", 0.0, 1.0, true)]
+    #[case::without_deadband("amount_of_pids", 26.0, 26.0, true)]
+    fn should_publish_changes_beyond_deadband(
+        #[case] metric: &str,
+        #[case] first: f64,
+        #[case] second: f64,
+        #[case] expected: bool,
+    ) {
+        let mut filter = setup_filter();
+
+        assert!(filter.should_publish("root/b/metric", metric, first, at(0)));
+        assert_eq!(filter.should_publish("root/b/metric", metric, second, at(10)), expected);
+    }
+
+    #[test]
+    fn should_compare_to_last_published_value_per_topic() {
+        let mut filter = setup_filter();
+        let metric = "cpu_usage_in_percent";
+
+        assert!(filter.should_publish("root/a/cpu_usage_in_percent", metric, 10.0, at(0)));
+        assert!(filter.should_publish("root/b/cpu_usage_in_percent", metric, 10.0, at(0)));
+        assert!(!filter.should_publish("root/a/cpu_usage_in_percent", metric, 10.8, at(10)));
+        // Small changes don't add up unnoticed.
+        assert!(filter.should_publish("root/a/cpu_usage_in_percent", metric, 11.6, at(20)));
+        assert!(!filter.should_publish("root/b/cpu_usage_in_percent", metric, 10.0, at(20)));
+    }
+
+    #[test]
+    fn should_publish_after_max_silence_and_after_reset() {
+        let mut filter = setup_filter();
+        let metric = "cpu_usage_in_percent";
+
+        assert!(filter.should_publish("root/b/cpu_usage_in_percent", metric, 10.0, at(0)));
+        assert!(!filter.should_publish("root/b/cpu_usage_in_percent", metric, 10.0, at(59)));
+        assert!(filter.should_publish("root/b/cpu_usage_in_percent", metric, 10.0, at(60)));
+        filter.reset();
+        assert!(filter.should_publish("root/b/cpu_usage_in_percent", metric, 10.0, at(70)));
+    }
+
+    #[test]
+    fn should_expire_silent_topics() {
+        let mut filter = setup_filter();
+        let metric = "cpu_usage_in_percent";
+        filter.should_publish("root/a/cpu_usage_in_percent", metric, 10.0, at(0));
+        filter.should_publish("root/b/cpu_usage_in_percent", metric, 10.0, at(30));
+
+        filter.expire(at(60));
+
+        assert_eq!(filter.published.keys().collect::<Vec<_>>(), vec!["root/b/cpu_usage_in_percent"]);
+    }
+}
@@ -0,0 +1,14 @@
+use crate::domain::{Collection, DiskUsage, SourceStatus, TopProcesses};
+
+pub trait Exporter {
+    fn export(&mut self, collection: &Collection);
+
+    /// Called when the stats source turns stale or current again; nothing is exported by default.
+    fn export_source_status(&mut self, _status: &SourceStatus) {}
+
+    /// Called on the slower interval of top processes, with one entry per container; nothing is exported by default.
+    fn export_top_processes(&mut self, _top_processes: &[TopProcesses]) {}
+
+    /// Called with every new disk usage collection, with one entry per container; nothing is exported by default.
+    fn export_disk_usage(&mut self, _disk_usage: &[DiskUsage]) {}
+}
@@ -0,0 +1,26 @@
+use crate::util::config::{build_path, get_config, CONFIG_DIR};
+use serde::Deserialize;
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(PartialEq, Deserialize, Clone, Debug)]
+pub enum ExporterType {
+    MQTT,
+    #[serde(rename = "JSON_LINES")]
+    JsonLines,
+    #[serde(rename = "CSV_ARCHIVE")]
+    CsvArchive,
+    #[serde(rename = "SQLITE_HISTORY")]
+    SqliteHistory,
+    OTLP,
+    STATSD,
+    WEBHOOK,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ExportersConfig {
+    pub exporters: Vec<ExporterType>,
+}
+
+pub fn get_exporters_config() -> ExportersConfig {
+    get_config(build_path(vec![&CONFIG_DIR, "exporters.config.json"]))
+}
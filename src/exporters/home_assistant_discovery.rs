@@ -0,0 +1,274 @@
+use crate::domain::MetricKind;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+#[derive(Clone, Deserialize, Debug, PartialEq)]
+pub(crate) struct HomeAssistantDiscoveryConfig {
+    #[serde(default = "default_discovery_prefix")]
+    pub(crate) discovery_prefix: String,
+}
+
+fn default_discovery_prefix() -> String {
+    "homeassistant".to_string()
+}
+
+/// A published metric of a service, announced as one Home Assistant sensor.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Sensor {
+    pub(crate) service_name: String,
+    /// Short id of the container, while its service runs several.
+    pub(crate) container: Option<String>,
+    pub(crate) metric: String,
+    /// Of per-interface metrics, which get one sensor per interface.
+    pub(crate) interface: Option<String>,
+    pub(crate) state_topic: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct DiscoveryMessage {
+    pub(crate) topic: String,
+    pub(crate) payload: String,
+}
+
+type SensorKey = (String, Option<String>, String, Option<String>);
+
+/// Tracks announced sensors to publish discovery configs of new or changed ones only.
+pub(crate) struct HomeAssistantDiscovery {
+    config: HomeAssistantDiscoveryConfig,
+    device_id: String,
+    unit: String,
+    announced: HashMap<SensorKey, String>,
+}
+
+impl HomeAssistantDiscovery {
+    pub(crate) fn new(config: HomeAssistantDiscoveryConfig, device_id: &str, unit: &str) -> Self {
+        HomeAssistantDiscovery {
+            config,
+            device_id: device_id.to_string(),
+            unit: unit.to_string(),
+            announced: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn announcements(&mut self, sensors: &[Sensor]) -> Vec<DiscoveryMessage> {
+        let mut messages = vec![];
+        for sensor in sensors {
+            let key = (
+                sensor.service_name.clone(),
+                sensor.container.clone(),
+                sensor.metric.clone(),
+                sensor.interface.clone(),
+            );
+            if self.announced.get(&key) == Some(&sensor.state_topic) {
+                continue;
+            }
+            messages.push(self.build_message(sensor));
+            self.announced.insert(key, sensor.state_topic.clone());
+        }
+        messages
+    }
+
+    fn build_message(&self, sensor: &Sensor) -> DiscoveryMessage {
+        let name = match &sensor.interface {
+            Some(interface) => format!("{}_{}", sensor.metric, interface),
+            None => sensor.metric.clone(),
+        };
+        let service = match &sensor.container {
+            Some(container) => format!("{} {}", sensor.service_name, container),
+            None => sensor.service_name.clone(),
+        };
+        let object_id = sanitize(&format!("{}_{}", service.replace(' ', "_"), name));
+        let (unit_of_measurement, device_class, state_class) = describe_metric(&sensor.metric);
+
+        let mut payload = json!({
+            "name": format!("{} {}", service, name.replace('_', " ")),
+            "unique_id": sanitize(&format!("{}_{}", self.device_id, object_id)),
+            "state_topic": sensor.state_topic,
+            "value_template": "{{ value_json.value }}",
+            "state_class": state_class,
+            "device": {
+                "identifiers": [self.device_id],
+                "name": format!("{} ({})", self.device_id, self.unit),
+                "model": "balena-multi-container-telemetry",
+            },
+        });
+        if let Value::Object(fields) = &mut payload {
+            if let Some(unit_of_measurement) = unit_of_measurement {
+                fields.insert("unit_of_measurement".to_string(), json!(unit_of_measurement));
+            }
+            if let Some(device_class) = device_class {
+                fields.insert("device_class".to_string(), json!(device_class));
+            }
+        }
+
+        DiscoveryMessage {
+            topic: format!(
+                "{}/sensor/{}/{}/config",
+                self.config.discovery_prefix,
+                sanitize(&self.device_id),
+                object_id
+            ),
+            payload: payload.to_string(),
+        }
+    }
+}
+
+/// Unit of measurement, device class and state class of a metric.
+fn describe_metric(metric: &str) -> (Option<&'static str>, Option<&'static str>, &'static str) {
+    let state_class = match MetricKind::of(metric).is_counter() {
+        true => "total_increasing",
+        false => "measurement",
+    };
+    let (unit_of_measurement, device_class) = if metric.ends_with("_in_percent") {
+        (Some("%"), None)
+    } else if metric.ends_with("_in_bytes") {
+        (Some("B"), Some("data_size"))
+    } else if metric.ends_with("_in_seconds") {
+        (Some("s"), Some("duration"))
+    } else if metric.ends_with("_in_microseconds") {
+        (Some("µs"), Some("duration"))
+    } else {
+        (None, None)
+    };
+    (unit_of_measurement, device_class, state_class)
+}
+
+// Home Assistant only allows [a-zA-Z0-9_-] in node and object ids.
+fn sanitize(id: &str) -> String {
+    id.chars()
+        .map(|char| if char.is_ascii_alphanumeric() || char == '_' || char == '-' { char } else { '_' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn setup_discovery() -> HomeAssistantDiscovery {
+        HomeAssistantDiscovery::new(
+            HomeAssistantDiscoveryConfig {
+                discovery_prefix: default_discovery_prefix(),
+            },
+            "d35a7ea843c61c723a12f19a41c26ef1",
+            "my-unit",
+        )
+    }
+
+    fn sensor(service_name: &str, metric: &str, state_topic: &str) -> Sensor {
+        Sensor {
+            service_name: service_name.to_string(),
+            container: None,
+            metric: metric.to_string(),
+            interface: None,
+            state_topic: state_topic.to_string(),
+        }
+    }
+
+    #[test]
+    fn should_build_discovery_message() {
+        let mut discovery = setup_discovery();
+
+        let actual = discovery.announcements(&[sensor("b", "cpu_usage_in_percent", "root/b/cpu_usage_in_percent")]);
+
+        assert_eq!(actual.len(), 1);
+        assert_eq!(
+            actual[0].topic,
+            "homeassistant/sensor/d35a7ea843c61c723a12f19a41c26ef1/b_cpu_usage_in_percent/config"
+        );
+        let payload: Value = serde_json::from_str(&actual[0].payload).unwrap();
+        assert_eq!(
+            payload,
+            json!({
+                "name": "b cpu usage in percent",
+                "unique_id": "d35a7ea843c61c723a12f19a41c26ef1_b_cpu_usage_in_percent",
+                "state_topic": "root/b/cpu_usage_in_percent",
+                "value_template": "{{ value_json.value }}",
+                "state_class": "measurement",
+                "unit_of_measurement": "%",
+                "device": {
+                    "identifiers": ["d35a7ea843c61c723a12f19a41c26ef1"],
+                    "name": "d35a7ea843c61c723a12f19a41c26ef1 (my-unit)",
+                    "model": "balena-multi-container-telemetry",
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn should_announce_one_sensor_per_interface() {
+        let mut discovery = setup_discovery();
+        let interface_sensor = |interface: &str| Sensor {
+            interface: Some(interface.to_string()),
+            ..sensor(
+                "b",
+                "network_interface_input_packets",
+                &format!("root/b/network_interface_input_packets/{}", interface),
+            )
+        };
+
+        let actual = discovery.announcements(&[interface_sensor("eth0"), interface_sensor("eth1")]);
+
+        assert_eq!(actual.len(), 2);
+        assert!(actual[0].topic.ends_with("/b_network_interface_input_packets_eth0/config"));
+        let payload: Value = serde_json::from_str(&actual[1].payload).unwrap();
+        assert_eq!(payload["name"], "b network interface input packets eth1");
+        assert_eq!(payload["state_class"], "total_increasing");
+        assert_eq!(payload.get("unit_of_measurement"), None);
+    }
+
+    #[test]
+    fn should_announce_only_new_sensors_or_changed_topics() {
+        let mut discovery = setup_discovery();
+        discovery.announcements(&[sensor("b", "cpu_usage_in_percent", "root/b/cpu_usage_in_percent")]);
+
+        let unchanged = discovery.announcements(&[sensor("b", "cpu_usage_in_percent", "root/b/cpu_usage_in_percent")]);
+        let new_service = discovery.announcements(&[
+            sensor("b", "cpu_usage_in_percent", "root/b/cpu_usage_in_percent"),
+            sensor("my.service", "cpu_usage_in_percent", "root/my.service/cpu_usage_in_percent"),
+        ]);
+        let changed_topic = discovery.announcements(&[sensor("b", "cpu_usage_in_percent", "root/b/1234/cpu_usage_in_percent")]);
+
+        assert!(unchanged.is_empty());
+        assert_eq!(new_service.len(), 1);
+        assert!(new_service[0].topic.ends_with("/my_service_cpu_usage_in_percent/config"));
+        assert_eq!(changed_topic.len(), 1);
+    }
+
+    #[test]
+    fn should_announce_one_sensor_per_container_of_a_service() {
+        let mut discovery = setup_discovery();
+        let container_sensor = |container: &str| Sensor {
+            container: Some(container.to_string()),
+            ..sensor("b", "cpu_usage_in_percent", "root/b/cpu_usage_in_percent")
+        };
+
+        let actual = discovery.announcements(&[container_sensor("4889ab0711ac"), container_sensor("f54e4ffc136d")]);
+
+        assert_eq!(actual.len(), 2);
+        assert!(actual[0].topic.ends_with("/b_4889ab0711ac_cpu_usage_in_percent/config"));
+        assert!(actual[1].topic.ends_with("/b_f54e4ffc136d_cpu_usage_in_percent/config"));
+        let payload: Value = serde_json::from_str(&actual[1].payload).unwrap();
+        assert_eq!(payload["name"], "b f54e4ffc136d cpu usage in percent");
+        assert_eq!(payload["unique_id"], "d35a7ea843c61c723a12f19a41c26ef1_b_f54e4ffc136d_cpu_usage_in_percent");
+    }
+
+    #[rstest]
+    #[case::percent("memory_usage_in_percent", (Some("%"), None, "measurement"))]
+    #[case::bytes("memory_usage_in_bytes", (Some("B"), Some("data_size"), "measurement"))]
+    #[case::counter("network_input_in_bytes", (Some("B"), Some("data_size"), "total_increasing"))]
+    #[case::packets("network_interface_input_packets", (None, None, "total_increasing"))]
+    #[case::seconds("uptime_in_seconds", (Some("s"), Some("duration"), "measurement"))]
+    #[case::stall_total("io_pressure_some_total_in_microseconds", (Some("µs"), Some("duration"), "total_increasing"))]
+    #[case::events("memory_oom_kill_events", (None, None, "total_increasing"))]
+    #[case::restarts("restart_count", (None, None, "total_increasing"))]
+    #[case::disk("disk_volumes_in_bytes", (Some("B"), Some("data_size"), "measurement"))]
+    #[case::plain("amount_of_pids", (None, None, "measurement"))]
+    fn should_describe_metric(
+        #[case] metric: &str,
+        #[case] expected: (Option<&'static str>, Option<&'static str>, &'static str),
+    ) {
+        assert_eq!(describe_metric(metric), expected);
+    }
+}
@@ -0,0 +1,380 @@
+use crate::domain::{Collection, CollectionSource, ContainerStats, DiskUsage, HostStats, TopProcesses};
+use crate::exporters::exporter::Exporter;
+use crate::util::config::{build_path, get_config, CONFIG_DIR};
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::{stdout, Write};
+use std::path::{Path, PathBuf};
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Deserialize, Debug, PartialEq)]
+enum JsonLinesTarget {
+    STDOUT,
+    FILE,
+}
+
+#[derive(Clone, Deserialize, Debug, PartialEq)]
+struct JsonLinesConfig {
+    target: JsonLinesTarget,
+    file_path: String,
+    max_file_size_in_bytes: u64,
+    max_rotated_files: u32,
+}
+
+#[derive(Serialize)]
+struct JsonLine<'a> {
+    timestamp: DateTime<Utc>,
+    sequence: u64,
+    source: CollectionSource,
+    #[serde(flatten)]
+    stats: &'a ContainerStats,
+}
+
+/// Closes every collection with the pressure of the whole host, if available.
+#[derive(Serialize)]
+struct HostJsonLine<'a> {
+    timestamp: DateTime<Utc>,
+    sequence: u64,
+    source: CollectionSource,
+    host: &'a HostStats,
+}
+
+lazy_static! {
+    static ref CONFIG: JsonLinesConfig = get_config(build_path(vec![&CONFIG_DIR, "json_lines.config.json"]));
+}
+
+pub struct JsonLinesExporter;
+
+impl Exporter for JsonLinesExporter {
+    fn export(&mut self, collection: &Collection) {
+        write_lines(&map_to_json_lines(collection));
+    }
+
+    fn export_disk_usage(&mut self, disk_usage: &[DiskUsage]) {
+        write_lines(&map_to_json_lines_of(disk_usage, |usage| &usage.container_name));
+    }
+
+    fn export_top_processes(&mut self, top_processes: &[TopProcesses]) {
+        write_lines(&map_to_json_lines_of(top_processes, |top| &top.container_name));
+    }
+}
+
+fn write_lines(lines: &[String]) {
+    let result = match CONFIG.target {
+        JsonLinesTarget::STDOUT => write_to_stdout(lines),
+        JsonLinesTarget::FILE => write_to_rotating_file(lines, &CONFIG),
+    };
+    result.unwrap_or_else(|err| error!("Writing JSON lines failed! Because of {}", err))
+}
+
+fn map_to_json_lines(collection: &Collection) -> Vec<String> {
+    let host_line = HostJsonLine {
+        timestamp: collection.timestamp(),
+        sequence: collection.sequence,
+        source: collection.source,
+        host: &collection.host,
+    };
+    let host_line = match collection.host.is_available() {
+        true => serde_json::to_string(&host_line)
+            .map_err(|err| warn!("Could not serialize host stats: {}", err))
+            .ok(),
+        false => None,
+    };
+    collection
+        .stats
+        .iter()
+        .map(|stats| JsonLine {
+            timestamp: collection.timestamp(),
+            sequence: collection.sequence,
+            source: collection.source,
+            stats,
+        })
+        .filter_map(|line| {
+            serde_json::to_string(&line)
+                .map_err(|err| warn!("Could not serialize stats of {}: {}", line.stats.container_name, err))
+                .ok()
+        })
+        .chain(host_line)
+        .collect()
+}
+
+/// One line per entry of results collected on their own interval, e.g. top processes, which carry their own timestamp.
+fn map_to_json_lines_of<T: Serialize>(entries: &[T], container_name: impl Fn(&T) -> &str) -> Vec<String> {
+    entries
+        .iter()
+        .filter_map(|entry| {
+            serde_json::to_string(entry)
+                .map_err(|err| warn!("Could not serialize stats of {}: {}", container_name(entry), err))
+                .ok()
+        })
+        .collect()
+}
+
+fn write_to_stdout(lines: &[String]) -> anyhow::Result<()> {
+    let mut handle = stdout().lock();
+    for line in lines {
+        writeln!(handle, "{}", line)?;
+    }
+    handle.flush()?;
+    Ok(())
+}
+
+fn write_to_rotating_file(lines: &[String], config: &JsonLinesConfig) -> anyhow::Result<()> {
+    let path = PathBuf::from(&config.file_path);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    rotate_if_needed(&path, config)?;
+
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    for line in lines {
+        writeln!(file, "{}", line)?;
+    }
+    Ok(())
+}
+
+// Same scheme as the log4rs fixed_window roller: file.1 is the newest rotated file.
+fn rotate_if_needed(path: &Path, config: &JsonLinesConfig) -> anyhow::Result<()> {
+    let size = match fs::metadata(path) {
+        Ok(metadata) => metadata.len(),
+        Err(_) => return Ok(()),
+    };
+    if size < config.max_file_size_in_bytes {
+        return Ok(());
+    }
+
+    if config.max_rotated_files == 0 {
+        fs::remove_file(path)?;
+        return Ok(());
+    }
+    for index in (1..config.max_rotated_files).rev() {
+        let from = rotated_path(path, index);
+        if from.exists() {
+            fs::rename(&from, rotated_path(path, index + 1))?;
+        }
+    }
+    fs::rename(path, rotated_path(path, 1))?;
+    Ok(())
+}
+
+fn rotated_path(path: &Path, index: u32) -> PathBuf {
+    PathBuf::from(format!("{}.{}", path.display(), index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Pressure, PressureStats, ProcessStats};
+    use crate::util::test_support::temp_dir;
+    use byte_unit::{Byte, Unit};
+
+    fn setup_test_data() -> ContainerStats {
+        ContainerStats {
+            container_id: String::from(
+                "4889ab0711ac17500a5de1c3d50eb6bfbc7eb367d349797f9fe8dcb92fd9e914",
+            ),
+            container_id_short: String::from("4889ab0711ac"),
+            container_name: String::from("b_10800414_3361262_f54e4ffc136d1344ee98993b36b9deeb"),
+            service_name: "b".to_string(),
+            cpu_usage_in_percent: Some(1.75),
+            mem_usage_in_percent: Some(31.12),
+            mem_usage: Byte::from_i64_with_unit(318, Unit::MiB),
+            mem_limit: Byte::from_i64_with_unit(1, Unit::GiB),
+            network_input: Byte::from_i64_with_unit(541, Unit::MB),
+            network_output: None,
+            block_device_input: Byte::from_i64_with_unit(0, Unit::B),
+            block_device_output: Byte::from_i64_with_unit(0, Unit::B),
+            amount_of_pids: Some(26),
+            ..Default::default()
+        }
+    }
+
+    fn temp_config(test_name: &str, max_file_size_in_bytes: u64) -> JsonLinesConfig {
+        let dir = temp_dir(&format!("json_lines_{}", test_name));
+        JsonLinesConfig {
+            target: JsonLinesTarget::FILE,
+            file_path: dir.join("stats.jsonl").to_str().unwrap().to_string(),
+            max_file_size_in_bytes,
+            max_rotated_files: 2,
+        }
+    }
+
+    #[test]
+    fn should_map_to_json_lines() {
+        let collection = Collection::at("2025-03-01T12:00:00Z", vec![setup_test_data()]);
+        let expected = concat!(
+            "{\"timestamp\":\"2025-03-01T12:00:00Z\",",
+            "\"sequence\":1,",
+            "\"source\":\"CLI\",",
+            "\"container_id\":\"4889ab0711ac17500a5de1c3d50eb6bfbc7eb367d349797f9fe8dcb92fd9e914\",",
+            "\"container_id_short\":\"4889ab0711ac\",",
+            "\"container_name\":\"b_10800414_3361262_f54e4ffc136d1344ee98993b36b9deeb\",",
+            "\"service_name\":\"b\",",
+            "\"cpu_usage_in_percent\":1.75,",
+            "\"normalized_cpu_usage_in_percent\":null,",
+            "\"cpu_quota_in_cores\":null,",
+            "\"host_cpu_cores\":null,",
+            "\"memory_usage_in_percent\":31.12,",
+            "\"memory_usage_in_bytes\":333447168,",
+            "\"memory_limit_in_bytes\":1073741824,",
+            "\"memory_anon_in_bytes\":null,",
+            "\"memory_file_in_bytes\":null,",
+            "\"memory_swap_in_bytes\":null,",
+            "\"memory_working_set_in_bytes\":null,",
+            "\"memory_oom_events\":null,",
+            "\"memory_oom_kill_events\":null,",
+            "\"network_input_in_bytes\":541000000,",
+            "\"network_output_in_bytes\":null,",
+            "\"network_interfaces\":{},",
+            "\"block_device_input_in_bytes\":0,",
+            "\"block_device_output_in_bytes\":0,",
+            "\"amount_of_pids\":26,",
+            "\"cpu_pressure\":null,",
+            "\"memory_pressure\":null,",
+            "\"io_pressure\":null,",
+            "\"state\":null,",
+            "\"health_status\":null,",
+            "\"restart_count\":null,",
+            "\"started_at\":null,",
+            "\"uptime_in_seconds\":null,",
+            "\"exit_code\":null,",
+            "\"oom_killed\":null,",
+            "\"byte_precision\":\"ROUNDED\"}"
+        );
+
+        let actual = map_to_json_lines(&collection);
+
+        assert_eq!(actual, vec![expected.to_string()])
+    }
+
+    #[test]
+    fn should_close_collection_with_available_host_pressure() {
+        let mut collection = Collection::at("2025-03-01T12:00:00Z", vec![setup_test_data()]);
+        collection.host.io_pressure = Some(PressureStats {
+            some: Pressure {
+                avg10_in_percent: 0.5,
+                avg60_in_percent: 0.25,
+                avg300_in_percent: 0.125,
+                total_in_microseconds: 4_000,
+            },
+            full: None,
+        });
+        let expected_host = concat!(
+            "{\"timestamp\":\"2025-03-01T12:00:00Z\",",
+            "\"sequence\":1,",
+            "\"source\":\"CLI\",",
+            "\"host\":{\"cpu_pressure\":null,\"memory_pressure\":null,\"io_pressure\":{\"some\":",
+            "{\"avg10_in_percent\":0.5,\"avg60_in_percent\":0.25,\"avg300_in_percent\":0.125,",
+            "\"total_in_microseconds\":4000},\"full\":null}}}"
+        );
+
+        let actual = map_to_json_lines(&collection);
+
+        assert_eq!(actual.len(), 2);
+        assert_eq!(actual[1], expected_host);
+    }
+
+    #[test]
+    fn should_map_disk_usage_to_json_lines() {
+        let disk_usage = DiskUsage {
+            service_name: "b".to_string(),
+            container_name: "b_1".to_string(),
+            container_id: "4889ab0711ac17500a5de1c3d50eb6bfbc7eb367d349797f9fe8dcb92fd9e914".to_string(),
+            container_id_short: "4889ab0711ac".to_string(),
+            timestamp: DateTime::from_timestamp(1_740_830_400, 0).unwrap(),
+            writable_layer_in_bytes: Some(52_428_800),
+            volumes_in_bytes: None,
+        };
+        let expected = concat!(
+            "{\"service_name\":\"b\",",
+            "\"container_name\":\"b_1\",",
+            "\"container_id\":\"4889ab0711ac17500a5de1c3d50eb6bfbc7eb367d349797f9fe8dcb92fd9e914\",",
+            "\"container_id_short\":\"4889ab0711ac\",",
+            "\"timestamp\":\"2025-03-01T12:00:00Z\",",
+            "\"disk_writable_layer_in_bytes\":52428800,",
+            "\"disk_volumes_in_bytes\":null}"
+        );
+
+        let actual = map_to_json_lines_of(&[disk_usage], |usage| &usage.container_name);
+
+        assert_eq!(actual, vec![expected.to_string()]);
+    }
+
+    #[test]
+    fn should_map_top_processes_to_json_lines() {
+        let process = ProcessStats {
+            pid: 102,
+            name: "node".to_string(),
+            command: "node index.js".to_string(),
+            cpu_usage_in_percent: Some(60.0),
+            mem_usage_in_bytes: 32_768_000,
+        };
+        let top_processes = TopProcesses {
+            service_name: "b".to_string(),
+            container_name: "b_1".to_string(),
+            container_id_short: "4889ab0711ac".to_string(),
+            timestamp: DateTime::from_timestamp(1_740_830_400, 0).unwrap(),
+            by_cpu: vec![process.clone()],
+            by_memory: vec![process],
+        };
+        let process_json = concat!(
+            "[{\"pid\":102,\"name\":\"node\",\"command\":\"node index.js\",",
+            "\"cpu_usage_in_percent\":60.0,\"mem_usage_in_bytes\":32768000}]"
+        );
+        let expected = format!(
+            concat!(
+                "{{\"service_name\":\"b\",",
+                "\"container_name\":\"b_1\",",
+                "\"container_id_short\":\"4889ab0711ac\",",
+                "\"timestamp\":\"2025-03-01T12:00:00Z\",",
+                "\"by_cpu\":{},",
+                "\"by_memory\":{}}}"
+            ),
+            process_json, process_json
+        );
+
+        let actual = map_to_json_lines_of(&[top_processes], |top| &top.container_name);
+
+        assert_eq!(actual, vec![expected]);
+    }
+
+    #[test]
+    fn should_get_config() {
+        let actual: JsonLinesConfig = get_config(build_path(vec!["test-data/config/json_lines.config.json"]));
+
+        assert_eq!(actual.target, JsonLinesTarget::FILE);
+        assert_eq!(actual.max_file_size_in_bytes, 1024);
+        assert_eq!(actual.max_rotated_files, 2);
+    }
+
+    #[test]
+    fn should_append_lines_to_file() {
+        let config = temp_config("append", 1024);
+        let path = PathBuf::from(&config.file_path);
+
+        write_to_rotating_file(&["{\"a\":1}".to_string()], &config).unwrap();
+        write_to_rotating_file(&["{\"a\":2}".to_string()], &config).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{\"a\":1}\n{\"a\":2}\n");
+        assert!(!rotated_path(&path, 1).exists());
+    }
+
+    #[test]
+    fn should_rotate_file_and_drop_oldest() {
+        let config = temp_config("rotate", 4);
+        let path = PathBuf::from(&config.file_path);
+
+        for value in 1..=4 {
+            write_to_rotating_file(&[format!("{{\"a\":{}}}", value)], &config).unwrap();
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{\"a\":4}\n");
+        assert_eq!(fs::read_to_string(rotated_path(&path, 1)).unwrap(), "{\"a\":3}\n");
+        assert_eq!(fs::read_to_string(rotated_path(&path, 2)).unwrap(), "{\"a\":2}\n");
+        assert!(!rotated_path(&path, 3).exists());
+    }
+}
@@ -1 +1,16 @@
-pub mod mqtt;
\ No newline at end of file
+pub(crate) mod aggregation;
+pub mod csv_archive;
+pub(crate) mod deadband;
+pub mod exporter;
+pub(crate) mod exporter_config;
+pub(crate) mod home_assistant_discovery;
+pub mod json_lines;
+pub mod mqtt;
+pub(crate) mod number_format;
+pub mod otlp;
+pub(crate) mod retry_buffer;
+pub(crate) mod sparkplug_b;
+pub mod sqlite_history;
+pub mod statsd;
+pub(crate) mod topic_template;
+pub mod webhook;
\ No newline at end of file
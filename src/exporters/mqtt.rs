@@ -1,26 +1,71 @@
-use crate::domain::ContainerStats;
+use crate::domain::{
+    shared_services, BytePrecision, Collection, ContainerStats, DiskUsage, HostStats, SourceStatus, TopProcesses,
+    HOST_SERVICE_NAME,
+};
+use crate::exporters::aggregation::{Aggregates, Aggregator};
+use crate::exporters::deadband::{DeadbandConfig, DeadbandFilter};
+use crate::exporters::exporter::Exporter;
+use crate::exporters::home_assistant_discovery::{HomeAssistantDiscovery, HomeAssistantDiscoveryConfig, Sensor};
+use crate::exporters::number_format::format_value;
+use crate::exporters::sparkplug_b::{
+    command_topic, death_message, is_rebirth_request, SparkplugBConfig, SparkplugMessage, SparkplugNode,
+};
+use crate::exporters::topic_template::{read_hostname, StaticValues, TopicTemplate, TopicValues};
 use crate::util::config::{build_path, get_config, CONFIG_DIR};
 use anyhow::anyhow;
+use chrono::{DateTime, SecondsFormat, Utc};
 use lazy_static::lazy_static;
-use log::error;
+use log::{error, info, warn};
 use paho_mqtt as mqtt;
-use paho_mqtt::Client;
+use paho_mqtt::AsyncClient;
 use serde::Deserialize;
+use std::collections::BTreeSet;
 use std::string::ToString;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
 use std::time::Duration;
 
 #[derive(Clone, Debug, PartialEq)]
 struct MqttMessage {
     topic: String,
-    value: f32,
+    metric: String,
+    value: f64,
+    precision: Option<BytePrecision>,
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Deserialize, Debug, Default, PartialEq)]
+enum PayloadFormat {
+    #[default]
+    JSON,
+    #[serde(rename = "SPARKPLUG_B")]
+    SparkplugB,
 }
 
 #[derive(Clone, Deserialize, Debug, PartialEq)]
 struct MqttConfig {
     broker_url: String,
     root_topic_template: String,
+    topic_template: Option<String>,
     device_id: String,
     unit: String,
+    #[serde(default = "default_metrics")]
+    metrics: Vec<String>,
+    aggregation_window_in_seconds: Option<u64>,
+    home_assistant_discovery: Option<HomeAssistantDiscoveryConfig>,
+    #[serde(default)]
+    payload_format: PayloadFormat,
+    sparkplug_b: Option<SparkplugBConfig>,
+    source_status_topic: Option<String>,
+    top_processes_topic: Option<String>,
+    deadband: Option<DeadbandConfig>,
+}
+
+fn default_metrics() -> Vec<String> {
+    vec![
+        "memory_usage_in_percent".to_string(),
+        "cpu_usage_in_percent".to_string(),
+    ]
 }
 
 lazy_static! {
@@ -28,80 +73,660 @@ lazy_static! {
 }
 
 lazy_static! {
-    static ref CLIENT: Client = build_client_and_connect(CONFIG.clone());
+    static ref CLIENT: AsyncClient = build_client_and_connect(CONFIG.clone());
 }
 
-pub fn export(stats: Vec<ContainerStats>) {
-    map_to_mqtt_messages(stats)
-        .into_iter()
-        .for_each(|message| publish(message));
+lazy_static! {
+    // Sparkplug B bdSeq of the current connection; seeded from the start time.
+    static ref BD_SEQ: AtomicU64 = AtomicU64::new(Utc::now().timestamp().rem_euclid(256) as u64);
+}
+
+/// Incremented with every (re)connect.
+static CONNECTIONS: AtomicU64 = AtomicU64::new(0);
+static REBIRTH_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+pub struct MqttExporter {
+    topic_template: TopicTemplate,
+    aggregator: Option<Aggregator>,
+    home_assistant_discovery: Option<HomeAssistantDiscovery>,
+    sparkplug_node: Option<SparkplugNode>,
+    source_status_topic: Option<String>,
+    top_processes_topic: Option<TopicTemplate>,
+    deadband: Option<DeadbandFilter>,
+    connections: u64,
 }
 
-fn publish(message: MqttMessage) {
-    let payload: String = "{\"value\": ".to_string() + &message.value.to_string() + "}";
+impl MqttExporter {
+    pub fn new(collection_interval_in_seconds: u64) -> anyhow::Result<Self> {
+        let topic_template = build_topic_template(&CONFIG, &read_hostname())?;
+
+        let known_metrics = ContainerStats::metric_names();
+        CONFIG
+            .metrics
+            .iter()
+            .filter(|metric| !known_metrics.contains(&metric.as_str()))
+            .for_each(|metric| warn!("Unknown metric {} in MQTT config will not be published", metric));
+        CONFIG
+            .deadband
+            .iter()
+            .flat_map(|deadband| deadband.metrics.keys())
+            .filter(|metric| !CONFIG.metrics.contains(metric))
+            .for_each(|metric| warn!("Deadband of {} in MQTT config applies to no published metric", metric));
+
+        let aggregator = CONFIG.aggregation_window_in_seconds.map(|window| {
+            if window % collection_interval_in_seconds.max(1) != 0 {
+                warn!(
+                    "Aggregation window of {}s is no multiple of the collection interval of {}s",
+                    window, collection_interval_in_seconds
+                );
+            }
+            Aggregator::new(window)
+        });
+        let home_assistant_discovery = CONFIG
+            .home_assistant_discovery
+            .clone()
+            .map(|config| HomeAssistantDiscovery::new(config, &CONFIG.device_id, &CONFIG.unit));
+        let sparkplug_node = build_sparkplug_node(&CONFIG)?;
+        let source_status_topic = build_source_status_topic(&CONFIG, &read_hostname())?;
+        let top_processes_topic = build_top_processes_topic(&CONFIG, &read_hostname())?;
+        if sparkplug_node.is_some()
+            && (aggregator.is_some() || home_assistant_discovery.is_some() || CONFIG.deadband.is_some())
+        {
+            warn!("Aggregation, Home Assistant discovery and deadbands are not supported with Sparkplug B payloads");
+        }
+        Ok(MqttExporter {
+            topic_template,
+            aggregator,
+            home_assistant_discovery,
+            sparkplug_node,
+            source_status_topic,
+            top_processes_topic,
+            deadband: CONFIG.deadband.clone().map(DeadbandFilter::new),
+            connections: 0,
+        })
+    }
+}
+
+impl Exporter for MqttExporter {
+    fn export(&mut self, collection: &Collection) {
+        let stats = &collection.stats;
+        if let Some(node) = &mut self.sparkplug_node {
+            let connections = current_connections();
+            if connections != self.connections || REBIRTH_REQUESTED.swap(false, Ordering::SeqCst) {
+                self.connections = connections;
+                node.rebirth(BD_SEQ.load(Ordering::SeqCst));
+            }
+            let timestamp = collection.timestamp().timestamp_millis() as u64;
+            node.messages(stats, &collection.host, timestamp).into_iter().for_each(publish_sparkplug);
+            return;
+        }
+
+        if let Some(discovery) = &mut self.home_assistant_discovery {
+            // With aggregation, Home Assistant shows the window average.
+            let statistic = self.aggregator.as_ref().map(|_| "avg");
+            let sensors = map_to_sensors(stats, &collection.host, &CONFIG.metrics, &self.topic_template, statistic);
+            discovery
+                .announcements(&sensors)
+                .into_iter()
+                .for_each(|message| publish_retained(message.topic, message.payload));
+        }
+
+        // Aggregates are stamped with the end of their window.
+        let (mut messages, timestamp) = match &mut self.aggregator {
+            None => (
+                map_to_mqtt_messages(stats, &collection.host, &CONFIG.metrics, &self.topic_template),
+                collection.timestamp(),
+            ),
+            Some(aggregator) => match aggregator.add(stats, &collection.host, &CONFIG.metrics, collection.timestamp()) {
+                Some((window_end, aggregates)) => {
+                    (map_aggregates_to_mqtt_messages(&aggregates, &self.topic_template), window_end)
+                }
+                None => (vec![], collection.timestamp()),
+            },
+        };
+        if let Some(deadband) = &mut self.deadband {
+            let connections = current_connections();
+            apply_deadband(deadband, &mut messages, connections, &mut self.connections, collection.timestamp());
+        }
+        messages
+            .into_iter()
+            .for_each(|message| publish(message, timestamp));
+    }
+
+    // Retained for subscribers joining later.
+    fn export_source_status(&mut self, status: &SourceStatus) {
+        if let Some(topic) = &self.source_status_topic {
+            match serde_json::to_string(status) {
+                Ok(payload) => publish_retained(topic.clone(), payload),
+                Err(err) => error!("Could not serialize source status! Because of {}", err),
+            }
+        }
+    }
+
+    fn export_top_processes(&mut self, top_processes: &[TopProcesses]) {
+        let Some(topic_template) = &self.top_processes_topic else {
+            return;
+        };
+        for (topic, payload) in map_to_top_processes_messages(top_processes, topic_template) {
+            let msg = mqtt::Message::new(topic, payload, 0);
+            CLIENT
+                .publish(msg.clone())
+                .wait()
+                .unwrap_or_else(|err| error!("Publishing of top processes to {} failed! Because of {}", msg.topic(), err))
+        }
+    }
+
+    // Published as collected, without aggregation and deadband; not part of Sparkplug B births.
+    fn export_disk_usage(&mut self, disk_usage: &[DiskUsage]) {
+        if self.sparkplug_node.is_some() {
+            return;
+        }
+        if let Some(discovery) = &mut self.home_assistant_discovery {
+            let sensors = map_disk_usage_to_sensors(disk_usage, &CONFIG.metrics, &self.topic_template);
+            discovery
+                .announcements(&sensors)
+                .into_iter()
+                .for_each(|message| publish_retained(message.topic, message.payload));
+        }
+        for usage in disk_usage {
+            map_disk_usage_to_mqtt_messages(usage, &CONFIG.metrics, &self.topic_template)
+                .into_iter()
+                .for_each(|message| publish(message, usage.timestamp));
+        }
+    }
+}
+
+// Connects with the first call, so the initial connect doesn't count as reconnect with the next collection.
+fn current_connections() -> u64 {
+    lazy_static::initialize(&CLIENT);
+    CONNECTIONS.load(Ordering::SeqCst)
+}
+
+// Values published before a reconnect may be lost with the clean session, so the deadbands start over.
+fn apply_deadband(
+    deadband: &mut DeadbandFilter,
+    messages: &mut Vec<MqttMessage>,
+    connections: u64,
+    known_connections: &mut u64,
+    now: DateTime<Utc>,
+) {
+    if connections != *known_connections {
+        *known_connections = connections;
+        deadband.reset();
+    }
+    deadband.expire(now);
+    messages.retain(|message| deadband.should_publish(&message.topic, &message.metric, message.value, now));
+}
+
+fn map_to_top_processes_messages(
+    top_processes: &[TopProcesses],
+    topic_template: &TopicTemplate,
+) -> Vec<(String, String)> {
+    top_processes
+        .iter()
+        .filter_map(|top| {
+            let topic = topic_template.render(&TopicValues {
+                service_name: &top.service_name,
+                container_name: &top.container_name,
+                container_id_short: &top.container_id_short,
+                metric: "top_processes",
+            });
+            serde_json::to_string(top)
+                .map_err(|err| error!("Could not serialize top processes! Because of {}", err))
+                .ok()
+                .map(|payload| (topic, payload))
+        })
+        .collect()
+}
+
+fn publish(message: MqttMessage, timestamp: DateTime<Utc>) {
+    let payload = build_payload(&message, timestamp);
     let msg = mqtt::Message::new(message.topic, payload, 0);
     CLIENT
         .publish(msg.clone())
+        .wait()
         .unwrap_or_else(|err| error!("Publishing of msg {} failed! Because of {}", msg, err))
 }
 
-fn map_to_mqtt_messages(stats: Vec<ContainerStats>) -> Vec<MqttMessage> {
+fn publish_retained(topic: String, payload: String) {
+    let msg = mqtt::Message::new_retained(topic, payload, 1);
+    CLIENT
+        .publish(msg.clone())
+        .wait()
+        .unwrap_or_else(|err| error!("Publishing of retained msg {} failed! Because of {}", msg, err))
+}
+
+fn publish_sparkplug(message: SparkplugMessage) {
+    let msg = mqtt::Message::new(message.topic, message.payload, 0);
+    CLIENT
+        .publish(msg.clone())
+        .wait()
+        .unwrap_or_else(|err| error!("Publishing of Sparkplug B msg to {} failed! Because of {}", msg.topic(), err))
+}
+
+fn map_to_mqtt_messages(
+    stats: &[ContainerStats],
+    host: &HostStats,
+    metrics: &[String],
+    topic_template: &TopicTemplate,
+) -> Vec<MqttMessage> {
     stats
         .iter()
-        .flat_map(|stat| map_to_mqtt_message(stat, &CONFIG))
+        .flat_map(|stat| map_to_mqtt_message(stat, metrics, topic_template))
+        .chain(map_host_to_mqtt_messages(host, metrics, topic_template))
         .collect()
 }
 
-fn map_to_mqtt_message(stats: &ContainerStats, config: &MqttConfig) -> Vec<MqttMessage> {
-    let base_topic = &config.root_topic_template
-        .replace("{device_id}", &config.device_id)
-        .replace("{unit}", &config.unit)
-        .replace("{service_name}", &stats.service_name);
-
-    [
-        build_messsage(
-            &(base_topic.clone() + "/memory_usage_in_percent"),
-            stats.mem_usage_in_percent,
-        ),
-        build_messsage(
-            &(base_topic.clone() + "/cpu_usage_in_percent"),
-            stats.cpu_usage_in_percent,
-        ),
-    ]
+fn map_host_to_mqtt_messages(host: &HostStats, metrics: &[String], topic_template: &TopicTemplate) -> Vec<MqttMessage> {
+    host.metrics()
         .into_iter()
+        .filter(|(metric, _)| metrics.iter().any(|selected| selected == metric))
+        .filter_map(|(metric, value)| {
+            build_messsage(&render_host_topic(topic_template, metric), metric, value, None).ok()
+        })
+        .collect()
+}
+
+// Host metrics are published like those of a service named `host`.
+fn render_host_topic(topic_template: &TopicTemplate, metric: &str) -> String {
+    topic_template.render(&TopicValues {
+        service_name: HOST_SERVICE_NAME,
+        container_name: HOST_SERVICE_NAME,
+        container_id_short: HOST_SERVICE_NAME,
+        metric,
+    })
+}
+
+fn map_to_mqtt_message(
+    stats: &ContainerStats,
+    metrics: &[String],
+    topic_template: &TopicTemplate,
+) -> Vec<MqttMessage> {
+    let values = stats.metrics();
+
+    metrics
+        .iter()
+        .filter_map(|selected| values.iter().find(|(metric, _)| metric == selected))
+        .map(|(metric, value)| {
+            let topic = topic_template.render(&TopicValues {
+                service_name: &stats.service_name,
+                container_name: &stats.container_name,
+                container_id_short: &stats.container_id_short,
+                metric,
+            });
+            build_messsage(&topic, metric, *value, stats.precision_of(metric))
+        })
         .filter_map(|result| result.ok())
+        .chain(map_interfaces_to_mqtt_messages(stats, metrics, topic_template))
+        .collect()
+}
+
+// The interface is appended to the metric's topic, e.g. `.../b/network_interface_input_dropped/eth0`.
+fn map_interfaces_to_mqtt_messages(
+    stats: &ContainerStats,
+    metrics: &[String],
+    topic_template: &TopicTemplate,
+) -> Vec<MqttMessage> {
+    stats
+        .interface_metrics()
+        .into_iter()
+        .filter(|(_, metric, _)| metrics.iter().any(|selected| selected == metric))
+        .map(|(interface, metric, value)| {
+            let metric_topic = topic_template.render(&TopicValues {
+                service_name: &stats.service_name,
+                container_name: &stats.container_name,
+                container_id_short: &stats.container_id_short,
+                metric,
+            });
+            MqttMessage {
+                topic: format!("{}/{}", metric_topic, interface),
+                metric: metric.to_string(),
+                value,
+                precision: stats.precision_of(metric),
+            }
+        })
         .collect()
 }
 
-fn build_messsage(topic: &str, value: Option<f32>) -> anyhow::Result<MqttMessage> {
+fn map_disk_usage_to_mqtt_messages(
+    disk_usage: &DiskUsage,
+    metrics: &[String],
+    topic_template: &TopicTemplate,
+) -> Vec<MqttMessage> {
+    disk_usage
+        .metrics()
+        .into_iter()
+        .filter(|(metric, _)| metrics.iter().any(|selected| selected == metric))
+        .filter_map(|(metric, value)| {
+            let topic = topic_template.render(&TopicValues {
+                service_name: &disk_usage.service_name,
+                container_name: &disk_usage.container_name,
+                container_id_short: &disk_usage.container_id_short,
+                metric,
+            });
+            build_messsage(&topic, metric, value, Some(BytePrecision::EXACT)).ok()
+        })
+        .collect()
+}
+
+fn map_aggregates_to_mqtt_messages(
+    aggregates: &Aggregates,
+    topic_template: &TopicTemplate,
+) -> Vec<MqttMessage> {
+    aggregates
+        .iter()
+        .flat_map(|((service_name, metric, interface), aggregate)| {
+            let metric_topic = topic_template.render(&TopicValues {
+                service_name,
+                // Rejected in topic templates with aggregation.
+                container_name: "",
+                container_id_short: "",
+                metric,
+            });
+            let metric_topic = match interface {
+                Some(interface) => format!("{}/{}", metric_topic, interface),
+                None => metric_topic,
+            };
+            aggregate
+                .statistics()
+                .into_iter()
+                .map(move |(statistic, value)| MqttMessage {
+                    topic: format!("{}/{}", metric_topic, statistic),
+                    metric: metric.clone(),
+                    value,
+                    precision: None,
+                })
+        })
+        .collect()
+}
+
+// Aggregates merge the containers of a service, so only collected values get a sensor per container.
+fn map_to_sensors(
+    stats: &[ContainerStats],
+    host: &HostStats,
+    metrics: &[String],
+    topic_template: &TopicTemplate,
+    statistic: Option<&str>,
+) -> Vec<Sensor> {
+    let state_topic = |topic: String| match statistic {
+        Some(statistic) => format!("{}/{}", topic, statistic),
+        None => topic,
+    };
+    let host_sensors = host
+        .metrics()
+        .into_iter()
+        .filter(|(metric, value)| value.is_some() && metrics.iter().any(|selected| selected == metric))
+        .map(|(metric, _)| Sensor {
+            service_name: HOST_SERVICE_NAME.to_string(),
+            container: None,
+            metric: metric.to_string(),
+            interface: None,
+            state_topic: state_topic(render_host_topic(topic_template, metric)),
+        })
+        .collect::<Vec<_>>();
+    let shared = match statistic {
+        Some(_) => BTreeSet::new(),
+        None => shared_services(stats.iter().map(|stat| stat.service_name.as_str())),
+    };
+    stats
+        .iter()
+        .flat_map(|stat| {
+            let values = stat
+                .metrics()
+                .into_iter()
+                .filter(|(_, value)| value.is_some())
+                .map(|(metric, _)| (metric, None))
+                .chain(stat.interface_metrics().into_iter().map(|(interface, metric, _)| (metric, Some(interface))));
+            let container = TopicValues {
+                service_name: &stat.service_name,
+                container_name: &stat.container_name,
+                container_id_short: &stat.container_id_short,
+                metric: "",
+            };
+            let shared = &shared;
+            values
+                .filter(|(metric, _)| metrics.iter().any(|selected| selected == metric))
+                .map(move |(metric, interface)| {
+                    let sensor = build_sensor(&container, shared, metric, interface, topic_template);
+                    Sensor {
+                        state_topic: state_topic(sensor.state_topic.clone()),
+                        ..sensor
+                    }
+                })
+        })
+        .chain(host_sensors)
+        .collect()
+}
+
+fn map_disk_usage_to_sensors(
+    disk_usage: &[DiskUsage],
+    metrics: &[String],
+    topic_template: &TopicTemplate,
+) -> Vec<Sensor> {
+    let shared = shared_services(disk_usage.iter().map(|usage| usage.service_name.as_str()));
+    disk_usage
+        .iter()
+        .flat_map(|usage| {
+            let container = TopicValues {
+                service_name: &usage.service_name,
+                container_name: &usage.container_name,
+                container_id_short: &usage.container_id_short,
+                metric: "",
+            };
+            usage
+                .metrics()
+                .into_iter()
+                .filter(|(metric, value)| value.is_some() && metrics.iter().any(|selected| selected == metric))
+                .map(|(metric, _)| build_sensor(&container, &shared, metric, None, topic_template))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+fn build_sensor(
+    container: &TopicValues,
+    shared: &BTreeSet<&str>,
+    metric: &str,
+    interface: Option<&str>,
+    topic_template: &TopicTemplate,
+) -> Sensor {
+    let topic = topic_template.render(&TopicValues { metric, ..*container });
+    Sensor {
+        service_name: container.service_name.to_string(),
+        container: shared
+            .contains(container.service_name)
+            .then(|| container.container_id_short.to_string()),
+        metric: metric.to_string(),
+        interface: interface.map(str::to_string),
+        state_topic: match interface {
+            Some(interface) => format!("{}/{}", topic, interface),
+            None => topic,
+        },
+    }
+}
+
+// Without a full `topic_template`, topics are the root topic extended by the metric name.
+fn build_topic_template(config: &MqttConfig, hostname: &str) -> anyhow::Result<TopicTemplate> {
+    let static_values = StaticValues {
+        device_id: &config.device_id,
+        unit: &config.unit,
+        hostname,
+    };
+    let topic_template = match &config.topic_template {
+        Some(template) => {
+            let topic_template = TopicTemplate::parse(template, &static_values)?;
+            if !topic_template.contains_metric() {
+                return Err(anyhow!("topic_template {} must contain {{metric}}", template));
+            }
+            topic_template
+        }
+        None => TopicTemplate::parse(&format!("{}/{{metric}}", config.root_topic_template), &static_values)?,
+    };
+    // Aggregates are per service, so there is no single container to render.
+    if config.aggregation_window_in_seconds.is_some() && topic_template.contains_container() {
+        return Err(anyhow!("Topic templates must not contain container placeholders with aggregation"));
+    }
+    Ok(topic_template)
+}
+
+fn build_source_status_topic(config: &MqttConfig, hostname: &str) -> anyhow::Result<Option<String>> {
+    let static_values = StaticValues {
+        device_id: &config.device_id,
+        unit: &config.unit,
+        hostname,
+    };
+    config
+        .source_status_topic
+        .as_ref()
+        .map(|template| {
+            TopicTemplate::parse(template, &static_values)?
+                .as_static()
+                .ok_or(anyhow!("source_status_topic {} must not contain per-container placeholders", template))
+        })
+        .transpose()
+}
+
+// One topic per service, so `{service_name}` or another per-container placeholder is required.
+fn build_top_processes_topic(config: &MqttConfig, hostname: &str) -> anyhow::Result<Option<TopicTemplate>> {
+    let static_values = StaticValues {
+        device_id: &config.device_id,
+        unit: &config.unit,
+        hostname,
+    };
+    config
+        .top_processes_topic
+        .as_ref()
+        .map(|template| {
+            let topic_template = TopicTemplate::parse(template, &static_values)?;
+            match topic_template.as_static() {
+                Some(_) => Err(anyhow!("top_processes_topic {} must contain a per-container placeholder", template)),
+                None => Ok(topic_template),
+            }
+        })
+        .transpose()
+}
+
+fn build_sparkplug_node(config: &MqttConfig) -> anyhow::Result<Option<SparkplugNode>> {
+    match config.payload_format {
+        PayloadFormat::JSON => Ok(None),
+        PayloadFormat::SparkplugB => config
+            .sparkplug_b
+            .clone()
+            .map(|sparkplug_config| Some(SparkplugNode::new(sparkplug_config)))
+            .ok_or(anyhow!("payload_format SPARKPLUG_B requires a sparkplug_b config")),
+    }
+}
+
+fn build_messsage(
+    topic: &str,
+    metric: &str,
+    value: Option<f64>,
+    precision: Option<BytePrecision>,
+) -> anyhow::Result<MqttMessage> {
     value
         .ok_or(anyhow!("Value not available"))
         .map(|val| MqttMessage {
             topic: String::from(topic),
+            metric: String::from(metric),
             value: val,
+            precision,
         })
 }
 
-fn build_client_and_connect(config: MqttConfig) -> Client {
+// Byte values are annotated with their precision.
+fn build_payload(message: &MqttMessage, timestamp: DateTime<Utc>) -> String {
+    let timestamp = timestamp.to_rfc3339_opts(SecondsFormat::Secs, true);
+    match message.precision {
+        None => format!("{{\"value\": {}, \"timestamp\": \"{}\"}}", format_value(message.value), timestamp),
+        Some(precision) => format!(
+            "{{\"value\": {}, \"precision\": \"{}\", \"timestamp\": \"{}\"}}",
+            format_value(message.value),
+            precision.as_str(),
+            timestamp
+        ),
+    }
+}
+
+fn build_client_and_connect(config: MqttConfig) -> AsyncClient {
     let client_options = mqtt::CreateOptionsBuilder::new()
-        .server_uri(config.broker_url)
-        .finalize();
-    let client = Client::new(client_options).expect("Error during client creation");
-    let connection_options = mqtt::ConnectOptionsBuilder::new()
-        .clean_session(true)
-        .automatic_reconnect(Duration::from_secs(1), Duration::from_secs(30))
+        .server_uri(config.broker_url.clone())
         .finalize();
+    let client = AsyncClient::new(client_options).expect("Error during client creation");
+
+    // Sparkplug B announces the node death through the will and listens for rebirth commands.
+    let sparkplug_config = match config.payload_format {
+        PayloadFormat::JSON => None,
+        PayloadFormat::SparkplugB => config.sparkplug_b.clone(),
+    };
+    if let Some(sparkplug_config) = &sparkplug_config {
+        client.set_message_callback(|_, message| {
+            if message.is_some_and(|message| is_rebirth_request(message.payload())) {
+                info!("Sparkplug B rebirth requested");
+                REBIRTH_REQUESTED.store(true, Ordering::SeqCst);
+            }
+        });
+        let sparkplug_config = sparkplug_config.clone();
+        client.set_connection_lost_callback(move |client| {
+            let client = client.clone();
+            let sparkplug_config = sparkplug_config.clone();
+            thread::spawn(move || reconnect(&client, &sparkplug_config));
+        });
+    }
+    let command_topic = sparkplug_config.as_ref().map(command_topic);
+    client.set_connected_callback(move |client| {
+        CONNECTIONS.fetch_add(1, Ordering::SeqCst);
+        // Subscriptions are lost with a clean session, so subscribe on every (re)connect.
+        if let Some(command_topic) = &command_topic {
+            client.subscribe(command_topic, 1);
+        }
+    });
 
     client
-        .connect(connection_options)
+        .connect(build_connect_options(sparkplug_config.as_ref()))
+        .wait()
         .expect("Failed to connect to broker");
 
     client
 }
 
+fn build_connect_options(sparkplug_config: Option<&SparkplugBConfig>) -> mqtt::ConnectOptions {
+    let mut connection_options = mqtt::ConnectOptionsBuilder::new();
+    connection_options.clean_session(true);
+    match sparkplug_config {
+        None => {
+            connection_options.automatic_reconnect(Duration::from_secs(1), Duration::from_secs(30));
+        }
+        // Every connect has its own bdSeq and will; reconnects are done by `reconnect`.
+        Some(sparkplug_config) => {
+            let bd_seq = next_bd_seq();
+            let death = death_message(sparkplug_config, bd_seq);
+            connection_options.will_message(mqtt::Message::new(death.topic, death.payload, 1));
+        }
+    }
+    connection_options.finalize()
+}
+
+fn next_bd_seq() -> u64 {
+    let previous = BD_SEQ
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |bd_seq| Some((bd_seq + 1) % 256))
+        .unwrap_or_default();
+    (previous + 1) % 256
+}
+
+fn reconnect(client: &AsyncClient, sparkplug_config: &SparkplugBConfig) {
+    let mut delay = Duration::from_secs(1);
+    while let Err(err) = client.connect(build_connect_options(Some(sparkplug_config))).wait() {
+        warn!("Reconnecting to broker failed! Because of {}", err);
+        thread::sleep(delay);
+        delay = (delay * 2).min(Duration::from_secs(30));
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::BTreeMap;
+    use crate::domain::{NetworkInterfaceStats, Pressure, PressureStats, ProcessStats};
     use byte_unit::{Byte, Unit};
 
     #[test]
@@ -122,27 +747,407 @@ mod tests {
             block_device_input: Byte::from_i64_with_unit(0, Unit::B),
             block_device_output: Byte::from_i64_with_unit(0, Unit::B),
             amount_of_pids: Some(26),
+            ..Default::default()
         };
         let expected = [
             MqttMessage {
                 topic: "root/d35a7ea843c61c723a12f19a41c26ef1/telemetry/my-unit/b/memory_usage_in_percent"
                     .to_string(),
-                value: input.mem_usage_in_percent.unwrap(),
+                metric: "memory_usage_in_percent".to_string(),
+                value: f64::from(input.mem_usage_in_percent.unwrap()),
+                precision: None,
             },
             MqttMessage {
                 topic: "root/d35a7ea843c61c723a12f19a41c26ef1/telemetry/my-unit/b/cpu_usage_in_percent"
                     .to_string(),
-                value: input.cpu_usage_in_percent.unwrap(),
+                metric: "cpu_usage_in_percent".to_string(),
+                value: f64::from(input.cpu_usage_in_percent.unwrap()),
+                precision: None,
             },
         ]
             .to_vec();
         let config: MqttConfig = get_config(build_path(vec!["test-data/config/mqtt.config.json"]));
+        let topic_template = build_topic_template(&config, "my-host").unwrap();
 
-        let actual = map_to_mqtt_message(&input, &config);
+        let actual = map_to_mqtt_message(&input, &config.metrics, &topic_template);
 
         assert_eq!(actual, expected)
     }
 
+    #[test]
+    fn should_map_configured_metrics_only() {
+        let input = ContainerStats {
+            service_name: "b".to_string(),
+            cpu_usage_in_percent: Some(1.75),
+            mem_usage_in_percent: Some(31.12),
+            amount_of_pids: Some(26),
+            ..Default::default()
+        };
+        let config: MqttConfig = get_config(build_path(vec!["test-data/config/mqtt.config.json"]));
+        let topic_template = build_topic_template(&config, "my-host").unwrap();
+        let metrics = vec!["amount_of_pids".to_string(), "network_input_in_bytes".to_string()];
+
+        let actual = map_to_mqtt_message(&input, &metrics, &topic_template);
+
+        assert_eq!(
+            actual,
+            vec![MqttMessage {
+                topic: "root/d35a7ea843c61c723a12f19a41c26ef1/telemetry/my-unit/b/amount_of_pids".to_string(),
+                metric: "amount_of_pids".to_string(),
+                value: 26.0,
+                precision: None,
+            }]
+        )
+    }
+
+    #[test]
+    fn should_map_selected_interface_metrics_to_interface_topics() {
+        let input = ContainerStats {
+            service_name: "b".to_string(),
+            network_interfaces: BTreeMap::from([(
+                "eth0".to_string(),
+                NetworkInterfaceStats {
+                    input_dropped: 7,
+                    input_in_bytes: 1000,
+                    ..Default::default()
+                },
+            )]),
+            byte_precision: BytePrecision::EXACT,
+            ..Default::default()
+        };
+        let config: MqttConfig = get_config(build_path(vec!["test-data/config/mqtt.config.json"]));
+        let topic_template = build_topic_template(&config, "my-host").unwrap();
+        let metrics = vec!["network_interface_input_dropped".to_string()];
+
+        let actual = map_to_mqtt_message(&input, &metrics, &topic_template);
+
+        assert_eq!(
+            actual,
+            vec![MqttMessage {
+                topic: "root/d35a7ea843c61c723a12f19a41c26ef1/telemetry/my-unit/b/network_interface_input_dropped/eth0"
+                    .to_string(),
+                metric: "network_interface_input_dropped".to_string(),
+                value: 7.0,
+                precision: None,
+            }]
+        )
+    }
+
+    #[test]
+    fn should_build_payload_of_negative_value() {
+        let input = ContainerStats {
+            service_name: "b".to_string(),
+            exit_code: Some(-1),
+            ..Default::default()
+        };
+        let config: MqttConfig = get_config(build_path(vec!["test-data/config/mqtt.config.json"]));
+        let topic_template = build_topic_template(&config, "my-host").unwrap();
+        let timestamp = Collection::at("2025-03-01T12:00:00Z", vec![]).timestamp();
+
+        let actual = map_to_mqtt_message(&input, &["exit_code".to_string()], &topic_template);
+
+        assert_eq!(build_payload(&actual[0], timestamp), "{\"value\": -1, \"timestamp\": \"2025-03-01T12:00:00Z\"}");
+    }
+
+    #[test]
+    fn should_annotate_byte_values_with_precision() {
+        let input = ContainerStats {
+            service_name: "b".to_string(),
+            cpu_usage_in_percent: Some(31.12),
+            mem_usage: Some(Byte::from_u64(8_589_934_593)),
+            byte_precision: BytePrecision::EXACT,
+            ..Default::default()
+        };
+        let config: MqttConfig = get_config(build_path(vec!["test-data/config/mqtt.config.json"]));
+        let topic_template = build_topic_template(&config, "my-host").unwrap();
+        let metrics = vec!["cpu_usage_in_percent".to_string(), "memory_usage_in_bytes".to_string()];
+
+        let timestamp = Collection::at("2025-03-01T12:00:00Z", vec![]).timestamp();
+
+        let actual: Vec<String> = map_to_mqtt_message(&input, &metrics, &topic_template)
+            .iter()
+            .map(|message| build_payload(message, timestamp))
+            .collect();
+
+        assert_eq!(
+            actual,
+            vec![
+                "{\"value\": 31.12, \"timestamp\": \"2025-03-01T12:00:00Z\"}",
+                "{\"value\": 8589934593, \"precision\": \"EXACT\", \"timestamp\": \"2025-03-01T12:00:00Z\"}",
+            ]
+        );
+    }
+
+    #[test]
+    fn should_map_aggregates_to_statistic_topics() {
+        let input = ContainerStats {
+            service_name: "b".to_string(),
+            cpu_usage_in_percent: Some(1.5),
+            ..Default::default()
+        };
+        let config: MqttConfig = get_config(build_path(vec!["test-data/config/mqtt.config.json"]));
+        let topic_template = build_topic_template(&config, "my-host").unwrap();
+        let mut aggregator = Aggregator::new(15);
+        let host = HostStats::default();
+        let start = Collection::at("2025-03-01T12:00:00Z", vec![]).timestamp();
+        aggregator.add(&[input], &host, &config.metrics, start);
+        let end = Collection::at("2025-03-01T12:00:15Z", vec![]).timestamp();
+        let (_, aggregates) = aggregator.add(&[], &host, &config.metrics, end).unwrap();
+
+        let actual = map_aggregates_to_mqtt_messages(&aggregates, &topic_template);
+
+        let topic = "root/d35a7ea843c61c723a12f19a41c26ef1/telemetry/my-unit/b/cpu_usage_in_percent";
+        assert_eq!(
+            actual,
+            ["min", "max", "avg", "last"]
+                .map(|statistic| MqttMessage {
+                    topic: format!("{}/{}", topic, statistic),
+                    metric: "cpu_usage_in_percent".to_string(),
+                    value: 1.5,
+                    precision: None,
+                })
+                .to_vec()
+        )
+    }
+
+    #[test]
+    fn should_map_to_full_topic_template() {
+        let input = ContainerStats {
+            container_id_short: "4889ab0711ac".to_string(),
+            container_name: "b_10800414_3361262_f54e4ffc136d1344ee98993b36b9deeb".to_string(),
+            service_name: "b".to_string(),
+            cpu_usage_in_percent: Some(1.75),
+            ..Default::default()
+        };
+        let mut config: MqttConfig = get_config(build_path(vec!["test-data/config/mqtt.config.json"]));
+        config.topic_template = Some("uns/{unit}/{hostname}/{service_name}/{container_id_short}/{metric}".to_string());
+        let topic_template = build_topic_template(&config, "my-host").unwrap();
+
+        let actual = map_to_mqtt_message(&input, &config.metrics, &topic_template);
+
+        assert_eq!(
+            actual,
+            vec![MqttMessage {
+                topic: "uns/my-unit/my-host/b/4889ab0711ac/cpu_usage_in_percent".to_string(),
+                metric: "cpu_usage_in_percent".to_string(),
+                value: 1.75,
+                precision: None,
+            }]
+        )
+    }
+
+    #[test]
+    fn should_reject_invalid_topic_templates() {
+        let mut config: MqttConfig = get_config(build_path(vec!["test-data/config/mqtt.config.json"]));
+        config.topic_template = Some("uns/{service_name}".to_string());
+        assert!(build_topic_template(&config, "my-host").is_err());
+
+        config.topic_template = None;
+        config.root_topic_template = "root/{device}/{service_name}".to_string();
+        assert!(build_topic_template(&config, "my-host").is_err());
+
+        config.topic_template = Some("uns/{service_name}/{container_name}/{metric}".to_string());
+        assert!(build_topic_template(&config, "my-host").is_ok());
+        config.aggregation_window_in_seconds = Some(300);
+        assert!(build_topic_template(&config, "my-host").is_err());
+    }
+
+    #[test]
+    fn should_map_host_metrics_once_to_host_topics_and_sensors() {
+        let host = HostStats {
+            cpu_pressure: Some(PressureStats {
+                some: Pressure {
+                    avg10_in_percent: 2.5,
+                    avg60_in_percent: 1.5,
+                    avg300_in_percent: 0.5,
+                    total_in_microseconds: 4_000,
+                },
+                full: None,
+            }),
+            ..Default::default()
+        };
+        let mut config: MqttConfig = get_config(build_path(vec!["test-data/config/mqtt.config.json"]));
+        config.metrics = vec!["host_cpu_pressure_some_avg10_in_percent".to_string()];
+        let topic_template = build_topic_template(&config, "my-host").unwrap();
+        let stats = [ContainerStats::default(), ContainerStats::default()];
+
+        let messages = map_to_mqtt_messages(&stats, &host, &config.metrics, &topic_template);
+        let sensors = map_to_sensors(&stats, &host, &config.metrics, &topic_template, None);
+
+        let topic = "root/d35a7ea843c61c723a12f19a41c26ef1/telemetry/my-unit/host/host_cpu_pressure_some_avg10_in_percent";
+        assert_eq!(
+            messages,
+            vec![MqttMessage {
+                topic: topic.to_string(),
+                metric: "host_cpu_pressure_some_avg10_in_percent".to_string(),
+                value: 2.5,
+                precision: None,
+            }]
+        );
+        assert_eq!(
+            sensors,
+            vec![Sensor {
+                service_name: "host".to_string(),
+                container: None,
+                metric: "host_cpu_pressure_some_avg10_in_percent".to_string(),
+                interface: None,
+                state_topic: topic.to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn should_map_to_sensors_of_published_metrics() {
+        let input = ContainerStats {
+            service_name: "b".to_string(),
+            cpu_usage_in_percent: Some(1.5),
+            mem_usage_in_percent: None,
+            network_interfaces: BTreeMap::from([("eth0".to_string(), NetworkInterfaceStats::default())]),
+            ..Default::default()
+        };
+        let mut config: MqttConfig = get_config(build_path(vec!["test-data/config/mqtt.config.json"]));
+        config.metrics.push("network_interface_input_dropped".to_string());
+        let topic_template = build_topic_template(&config, "my-host").unwrap();
+
+        let actual = map_to_sensors(&[input], &HostStats::default(), &config.metrics, &topic_template, Some("avg"));
+
+        assert_eq!(
+            actual,
+            vec![
+                Sensor {
+                    service_name: "b".to_string(),
+                    container: None,
+                    metric: "cpu_usage_in_percent".to_string(),
+                    interface: None,
+                    state_topic: "root/d35a7ea843c61c723a12f19a41c26ef1/telemetry/my-unit/b/cpu_usage_in_percent/avg"
+                        .to_string(),
+                },
+                Sensor {
+                    service_name: "b".to_string(),
+                    container: None,
+                    metric: "network_interface_input_dropped".to_string(),
+                    interface: Some("eth0".to_string()),
+                    state_topic:
+                        "root/d35a7ea843c61c723a12f19a41c26ef1/telemetry/my-unit/b/network_interface_input_dropped/eth0/avg"
+                            .to_string(),
+                },
+            ]
+        )
+    }
+
+    #[test]
+    fn should_map_to_sensor_per_container_of_a_service_with_several() {
+        let container = |container_id_short: &str| ContainerStats {
+            service_name: "b".to_string(),
+            container_id_short: container_id_short.to_string(),
+            cpu_usage_in_percent: Some(1.5),
+            ..Default::default()
+        };
+        let stats = [container("4889ab0711ac"), container("f54e4ffc136d")];
+        let config: MqttConfig = get_config(build_path(vec!["test-data/config/mqtt.config.json"]));
+        let topic_template = build_topic_template(&config, "my-host").unwrap();
+
+        let collected = map_to_sensors(&stats, &HostStats::default(), &config.metrics, &topic_template, None);
+        let aggregated = map_to_sensors(&stats, &HostStats::default(), &config.metrics, &topic_template, Some("avg"));
+
+        let containers: Vec<Option<&str>> = collected.iter().map(|sensor| sensor.container.as_deref()).collect();
+        assert_eq!(containers, vec![Some("4889ab0711ac"), Some("f54e4ffc136d")]);
+        assert!(aggregated.iter().all(|sensor| sensor.container.is_none()));
+    }
+
+    #[test]
+    fn should_map_disk_usage_to_sensors_of_published_metrics() {
+        let input = DiskUsage {
+            service_name: "b".to_string(),
+            writable_layer_in_bytes: Some(52_428_800),
+            volumes_in_bytes: Some(1_094_713_344),
+            ..Default::default()
+        };
+        let config: MqttConfig = get_config(build_path(vec!["test-data/config/mqtt.config.json"]));
+        let topic_template = build_topic_template(&config, "my-host").unwrap();
+        let metrics = vec!["disk_volumes_in_bytes".to_string()];
+
+        let actual = map_disk_usage_to_sensors(&[input], &metrics, &topic_template);
+
+        assert_eq!(
+            actual,
+            vec![Sensor {
+                service_name: "b".to_string(),
+                container: None,
+                metric: "disk_volumes_in_bytes".to_string(),
+                interface: None,
+                state_topic: "root/d35a7ea843c61c723a12f19a41c26ef1/telemetry/my-unit/b/disk_volumes_in_bytes"
+                    .to_string(),
+            }]
+        )
+    }
+
+    #[test]
+    fn should_apply_deadband_from_the_first_collection() {
+        let mut deadband = DeadbandFilter::new(
+            serde_json::from_str(r#"{"metrics": {"cpu_usage_in_percent": {"mode": "ABSOLUTE", "value": 1.0}}}"#)
+                .unwrap(),
+        );
+        let message = || MqttMessage {
+            topic: "root/b/cpu_usage_in_percent".to_string(),
+            metric: "cpu_usage_in_percent".to_string(),
+            value: 1.75,
+            precision: None,
+        };
+        let mut known_connections = 0;
+        let at = |seconds: i64| DateTime::from_timestamp(1_740_830_400 + seconds, 0).unwrap();
+
+        let mut first = vec![message()];
+        apply_deadband(&mut deadband, &mut first, 1, &mut known_connections, at(0));
+        let mut second = vec![message()];
+        apply_deadband(&mut deadband, &mut second, 1, &mut known_connections, at(10));
+        let mut after_reconnect = vec![message()];
+        apply_deadband(&mut deadband, &mut after_reconnect, 2, &mut known_connections, at(20));
+
+        assert_eq!(first, vec![message()]);
+        assert_eq!(second, vec![]);
+        assert_eq!(after_reconnect, vec![message()]);
+    }
+
+    #[test]
+    fn should_build_static_source_status_topic() {
+        let mut config: MqttConfig = get_config(build_path(vec!["test-data/config/mqtt.config.json"]));
+        assert_eq!(build_source_status_topic(&config, "my-host").unwrap(), None);
+
+        config.source_status_topic = Some("root/{device_id}/telemetry/{unit}/source_status".to_string());
+        assert_eq!(
+            build_source_status_topic(&config, "my-host").unwrap(),
+            Some("root/d35a7ea843c61c723a12f19a41c26ef1/telemetry/my-unit/source_status".to_string())
+        );
+
+        config.source_status_topic = Some("root/{service_name}/source_status".to_string());
+        assert!(build_source_status_topic(&config, "my-host").is_err());
+    }
+
+    #[test]
+    fn should_require_sparkplug_config_for_sparkplug_payloads() {
+        let mut config: MqttConfig = get_config(build_path(vec!["test-data/config/mqtt.config.json"]));
+        assert!(build_sparkplug_node(&config).unwrap().is_none());
+
+        config.payload_format = PayloadFormat::SparkplugB;
+        assert!(build_sparkplug_node(&config).is_err());
+
+        config.sparkplug_b = Some(SparkplugBConfig {
+            group_id: "telemetry".to_string(),
+            edge_node_id: "my-edge-node".to_string(),
+        });
+        assert!(build_sparkplug_node(&config).unwrap().is_some());
+    }
+
+    #[test]
+    fn should_increment_bd_seq_with_every_connect() {
+        let first = next_bd_seq();
+        let second = next_bd_seq();
+
+        assert_eq!(second, (first + 1) % 256);
+        assert_eq!(BD_SEQ.load(Ordering::SeqCst), second);
+    }
+
     #[test]
     fn should_get_config() {
         let test_config_path = build_path(vec!["test-data/config/mqtt.config.json"]);
@@ -156,19 +1161,94 @@ mod tests {
             actual.root_topic_template,
             "root/{device_id}/telemetry/{unit}/{service_name}"
         );
+        assert_eq!(actual.topic_template, None);
+        assert_eq!(actual.metrics, default_metrics());
+        assert_eq!(actual.aggregation_window_in_seconds, None);
+        assert_eq!(actual.home_assistant_discovery, None);
+        assert_eq!(actual.payload_format, PayloadFormat::JSON);
+        assert_eq!(actual.sparkplug_b, None);
+        assert_eq!(actual.source_status_topic, None);
+        assert_eq!(actual.top_processes_topic, None);
+        assert_eq!(actual.deadband, None);
+    }
+
+    #[test]
+    fn should_map_configured_disk_usage_metrics() {
+        let input = DiskUsage {
+            service_name: "b".to_string(),
+            writable_layer_in_bytes: Some(52_428_800),
+            volumes_in_bytes: Some(1_094_713_344),
+            ..Default::default()
+        };
+        let config: MqttConfig = get_config(build_path(vec!["test-data/config/mqtt.config.json"]));
+        let topic_template = build_topic_template(&config, "my-host").unwrap();
+        let metrics = vec!["disk_volumes_in_bytes".to_string()];
+
+        let actual = map_disk_usage_to_mqtt_messages(&input, &metrics, &topic_template);
+
+        assert_eq!(
+            actual,
+            vec![MqttMessage {
+                topic: "root/d35a7ea843c61c723a12f19a41c26ef1/telemetry/my-unit/b/disk_volumes_in_bytes".to_string(),
+                metric: "disk_volumes_in_bytes".to_string(),
+                value: 1_094_713_344.0,
+                precision: Some(BytePrecision::EXACT),
+            }]
+        )
+    }
+
+    #[test]
+    fn should_map_top_processes_to_json_per_service() {
+        let mut config: MqttConfig = get_config(build_path(vec!["test-data/config/mqtt.config.json"]));
+        config.top_processes_topic = Some("root/{device_id}/{service_name}/top_processes".to_string());
+        let topic_template = build_top_processes_topic(&config, "my-host").unwrap().unwrap();
+        let process = ProcessStats {
+            pid: 102,
+            name: "python3".to_string(),
+            command: "python3 worker.py".to_string(),
+            cpu_usage_in_percent: Some(60.0),
+            mem_usage_in_bytes: 32_768_000,
+        };
+        let top_processes = TopProcesses {
+            service_name: "b".to_string(),
+            container_name: "b_1".to_string(),
+            container_id_short: "0c05c278da1f".to_string(),
+            timestamp: Collection::at("2025-03-01T12:00:00Z", vec![]).timestamp(),
+            by_cpu: vec![process.clone()],
+            by_memory: vec![process],
+        };
+
+        let actual = map_to_top_processes_messages(&[top_processes], &topic_template);
+
+        assert_eq!(actual[0].0, "root/d35a7ea843c61c723a12f19a41c26ef1/b/top_processes");
+        let payload: serde_json::Value = serde_json::from_str(&actual[0].1).unwrap();
+        assert_eq!(payload["timestamp"], "2025-03-01T12:00:00Z");
+        assert_eq!(payload["by_cpu"][0]["pid"], 102);
+        assert_eq!(payload["by_cpu"][0]["cpu_usage_in_percent"], 60.0);
+        assert_eq!(payload["by_memory"][0]["mem_usage_in_bytes"], 32_768_000);
+    }
+
+    #[test]
+    fn should_require_per_container_placeholder_in_top_processes_topic() {
+        let mut config: MqttConfig = get_config(build_path(vec!["test-data/config/mqtt.config.json"]));
+        config.top_processes_topic = Some("root/{device_id}/top_processes".to_string());
+
+        assert!(build_top_processes_topic(&config, "my-host").is_err());
     }
 
     #[test]
     #[ignore] // Manual test to running MQTT broker
     fn should_build_a_client_and_connect() {
-        assert_eq!(CLIENT.is_connected(), true);
+        assert!(CLIENT.is_connected());
 
         let test_message = MqttMessage {
             topic:
             "root/d35a7ea843c61c723a12f19a41c26ef1/telemetry/system/b/memory/usage_in_percent"
                 .to_string(),
+            metric: "memory_usage_in_percent".to_string(),
             value: 12.57,
+            precision: None,
         };
-        publish(test_message)
+        publish(test_message, Utc::now())
     }
 }
@@ -0,0 +1,27 @@
+/// Formats whole numbers as integers and others as f32.
+pub(crate) fn format_value(value: f64) -> String {
+    if value.fract() != 0.0 {
+        (value as f32).to_string()
+    } else if (i64::MIN as f64..i64::MAX as f64).contains(&value) {
+        format!("{}", value as i64)
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::percent(f64::from(31.12_f32), "31.12")]
+    #[case::bytes(8_589_934_593.0, "8589934593")]
+    #[case::zero(0.0, "0")]
+    #[case::exit_code(-1.0, "-1")]
+    #[case::negative_fraction(-0.5, "-0.5")]
+    #[case::beyond_i64(1e20, "100000000000000000000")]
+    fn should_format_value(#[case] value: f64, #[case] expected: &str) {
+        assert_eq!(format_value(value), expected);
+    }
+}
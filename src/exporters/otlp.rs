@@ -0,0 +1,723 @@
+use crate::domain::{BytePrecision, Collection, ContainerStats, DiskUsage, HostStats, MetricKind};
+use crate::exporters::exporter::Exporter;
+use crate::exporters::retry_buffer::{spawn_buffered_sender, BufferConfig};
+use crate::util::config::{build_path, get_config, CONFIG_DIR};
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use log::{error, warn};
+use opentelemetry_proto::tonic::collector::metrics::v1::metrics_service_client::MetricsServiceClient;
+use opentelemetry_proto::tonic::collector::metrics::v1::{
+    ExportMetricsPartialSuccess, ExportMetricsServiceRequest, ExportMetricsServiceResponse,
+};
+use opentelemetry_proto::tonic::common::v1::{any_value, AnyValue, InstrumentationScope, KeyValue};
+use opentelemetry_proto::tonic::metrics::v1::{
+    metric, number_data_point, AggregationTemporality, Gauge, Metric, NumberDataPoint, ResourceMetrics, ScopeMetrics,
+    Sum,
+};
+use opentelemetry_proto::tonic::resource::v1::Resource;
+use prost::Message;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+use tonic::metadata::{MetadataKey, MetadataMap, MetadataValue};
+use tonic::transport::{Channel, ClientTlsConfig, Endpoint};
+
+const HTTP_METRICS_PATH: &str = "/v1/metrics";
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Deserialize, Debug, PartialEq)]
+pub(crate) enum OtlpProtocol {
+    #[serde(rename = "HTTP_PROTOBUF")]
+    HttpProtobuf,
+    GRPC,
+}
+
+#[derive(Clone, Deserialize, Debug, PartialEq)]
+pub(crate) struct OtlpConfig {
+    pub(crate) protocol: OtlpProtocol,
+    /// Base URL of the receiver, e.g. `http://localhost:4318` for HTTP or `http://localhost:4317` for gRPC.
+    pub(crate) endpoint: String,
+    #[serde(default)]
+    pub(crate) headers: BTreeMap<String, String>,
+    pub(crate) device_id: String,
+    pub(crate) unit: String,
+    #[serde(default)]
+    pub(crate) resource_attributes: BTreeMap<String, String>,
+    #[serde(default = "default_timeout_in_seconds")]
+    pub(crate) timeout_in_seconds: u64,
+    #[serde(default)]
+    pub(crate) buffer: BufferConfig,
+}
+
+fn default_timeout_in_seconds() -> u64 {
+    10
+}
+
+lazy_static! {
+    static ref CONFIG: OtlpConfig = get_config(build_path(vec![&CONFIG_DIR, "otlp.config.json"]));
+}
+
+/// Metrics of one collection, buffered until sent.
+type Entry = Vec<Metric>;
+
+trait OtlpTransport {
+    fn send(&mut self, request: &ExportMetricsServiceRequest) -> anyhow::Result<()>;
+}
+
+struct HttpTransport {
+    agent: ureq::Agent,
+    url: String,
+    headers: BTreeMap<String, String>,
+}
+
+impl HttpTransport {
+    fn new(config: &OtlpConfig) -> Self {
+        let agent = ureq::Agent::config_builder()
+            .timeout_global(Some(Duration::from_secs(config.timeout_in_seconds)))
+            .build()
+            .into();
+        HttpTransport {
+            agent,
+            url: config.endpoint.trim_end_matches('/').to_string() + HTTP_METRICS_PATH,
+            headers: config.headers.clone(),
+        }
+    }
+}
+
+impl OtlpTransport for HttpTransport {
+    fn send(&mut self, request: &ExportMetricsServiceRequest) -> anyhow::Result<()> {
+        let mut http_request = self
+            .agent
+            .post(&self.url)
+            .header("Content-Type", "application/x-protobuf");
+        for (name, value) in &self.headers {
+            http_request = http_request.header(name, value);
+        }
+        let mut response = http_request.send(&request.encode_to_vec()[..])?;
+        let body = response.body_mut().read_to_vec()?;
+        if let Ok(response) = ExportMetricsServiceResponse::decode(body.as_slice()) {
+            warn_on_partial_success(response.partial_success);
+        }
+        Ok(())
+    }
+}
+
+struct GrpcTransport {
+    runtime: tokio::runtime::Runtime,
+    endpoint: Endpoint,
+    metadata: MetadataMap,
+    client: Option<MetricsServiceClient<Channel>>,
+}
+
+impl GrpcTransport {
+    fn new(config: &OtlpConfig) -> anyhow::Result<Self> {
+        let timeout = Duration::from_secs(config.timeout_in_seconds);
+        let mut endpoint = Endpoint::from_shared(config.endpoint.clone())?
+            .timeout(timeout)
+            .connect_timeout(timeout);
+        if config.endpoint.starts_with("https://") {
+            endpoint = endpoint.tls_config(ClientTlsConfig::new().with_webpki_roots())?;
+        }
+        let mut metadata = MetadataMap::new();
+        for (name, value) in &config.headers {
+            metadata.insert(
+                MetadataKey::from_bytes(name.to_lowercase().as_bytes())?,
+                MetadataValue::try_from(value.as_str())?,
+            );
+        }
+        Ok(GrpcTransport {
+            // Runs on the sender thread, outside of the tokio runtime of main.
+            runtime: tokio::runtime::Builder::new_current_thread().enable_all().build()?,
+            endpoint,
+            metadata,
+            client: None,
+        })
+    }
+}
+
+impl OtlpTransport for GrpcTransport {
+    fn send(&mut self, request: &ExportMetricsServiceRequest) -> anyhow::Result<()> {
+        self.runtime.block_on(async {
+            let client = match &mut self.client {
+                Some(client) => client,
+                None => self
+                    .client
+                    .insert(MetricsServiceClient::new(self.endpoint.connect().await?)),
+            };
+            let mut grpc_request = tonic::Request::new(request.clone());
+            *grpc_request.metadata_mut() = self.metadata.clone();
+            let response = client.export(grpc_request).await?;
+            warn_on_partial_success(response.into_inner().partial_success);
+            Ok(())
+        })
+    }
+}
+
+fn warn_on_partial_success(partial_success: Option<ExportMetricsPartialSuccess>) {
+    if let Some(partial_success) = partial_success.filter(|partial_success| partial_success.rejected_data_points > 0) {
+        warn!(
+            "OTLP receiver rejected {} data points: {}",
+            partial_success.rejected_data_points, partial_success.error_message
+        );
+    }
+}
+
+pub struct OtlpExporter {
+    sender: Sender<Entry>,
+    started_at: DateTime<Utc>,
+}
+
+impl OtlpExporter {
+    pub fn new() -> anyhow::Result<Self> {
+        let mut transport = build_transport(&CONFIG)?;
+        let resource = build_resource(&CONFIG);
+        let sender = spawn_buffered_sender("OTLP export", CONFIG.buffer.clone(), move |entries: &[Entry]| {
+            transport.send(&build_request(&resource, entries))
+        });
+        Ok(OtlpExporter {
+            sender,
+            started_at: Utc::now(),
+        })
+    }
+}
+
+impl Exporter for OtlpExporter {
+    fn export(&mut self, collection: &Collection) {
+        let time_unix_nano = unix_nano(collection.timestamp());
+        self.sender
+            .send(map_to_metrics(&collection.stats, &collection.host, time_unix_nano, unix_nano(self.started_at)))
+            .unwrap_or_else(|err| error!("OTLP export thread stopped: {}", err));
+    }
+
+    fn export_disk_usage(&mut self, disk_usage: &[DiskUsage]) {
+        self.sender
+            .send(map_disk_usage_to_metrics(disk_usage))
+            .unwrap_or_else(|err| error!("OTLP export thread stopped: {}", err));
+    }
+}
+
+fn build_transport(config: &OtlpConfig) -> anyhow::Result<Box<dyn OtlpTransport + Send>> {
+    if !config.endpoint.starts_with("http://") && !config.endpoint.starts_with("https://") {
+        return Err(anyhow!("OTLP endpoint {} must start with http:// or https://", config.endpoint));
+    }
+    Ok(match config.protocol {
+        OtlpProtocol::HttpProtobuf => Box::new(HttpTransport::new(config)),
+        OtlpProtocol::GRPC => Box::new(GrpcTransport::new(config)?),
+    })
+}
+
+fn build_resource(config: &OtlpConfig) -> Resource {
+    let mut attributes = vec![
+        string_attribute("service.name", env!("CARGO_PKG_NAME")),
+        string_attribute("device.id", &config.device_id),
+        string_attribute("balena.unit", &config.unit),
+    ];
+    attributes.extend(
+        config
+            .resource_attributes
+            .iter()
+            .map(|(key, value)| string_attribute(key, value)),
+    );
+    Resource {
+        attributes,
+        ..Default::default()
+    }
+}
+
+/// One request per batch; data points of the same metric are merged.
+fn build_request(resource: &Resource, entries: &[Entry]) -> ExportMetricsServiceRequest {
+    let mut metrics: Vec<Metric> = vec![];
+    for metric in entries.iter().flatten() {
+        match metrics.iter_mut().find(|existing| existing.name == metric.name) {
+            Some(existing) => match (&mut existing.data, &metric.data) {
+                (Some(metric::Data::Gauge(existing)), Some(metric::Data::Gauge(gauge))) => {
+                    existing.data_points.extend(gauge.data_points.iter().cloned())
+                }
+                (Some(metric::Data::Sum(existing)), Some(metric::Data::Sum(sum))) => {
+                    existing.data_points.extend(sum.data_points.iter().cloned())
+                }
+                _ => metrics.push(metric.clone()),
+            },
+            None => metrics.push(metric.clone()),
+        }
+    }
+
+    ExportMetricsServiceRequest {
+        resource_metrics: vec![ResourceMetrics {
+            resource: Some(resource.clone()),
+            scope_metrics: vec![ScopeMetrics {
+                scope: Some(InstrumentationScope {
+                    name: env!("CARGO_PKG_NAME").to_string(),
+                    version: env!("CARGO_PKG_VERSION").to_string(),
+                    ..Default::default()
+                }),
+                metrics,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+    }
+}
+
+/// Counters start with the container, or with the exporter if the container's start or the counter's is unknown.
+fn map_to_metrics(
+    stats: &[ContainerStats],
+    host: &HostStats,
+    time_unix_nano: u64,
+    exporter_start_unix_nano: u64,
+) -> Vec<Metric> {
+    let mut metrics: BTreeMap<(&'static str, &'static str), Vec<NumberDataPoint>> = BTreeMap::new();
+    for stat in stats {
+        let container_start_unix_nano = stat.started_at.map_or(exporter_start_unix_nano, unix_nano);
+        let values = stat.metrics().into_iter().filter_map(|(name, value)| Some((None, name, value?)));
+        let interface_values = stat
+            .interface_metrics()
+            .into_iter()
+            .map(|(interface, name, value)| (Some(interface), name, value));
+        for (interface, name, value) in values.chain(interface_values) {
+            let mut attributes = vec![
+                string_attribute("container.id", stat.id()),
+                string_attribute("container.name", &stat.container_name),
+                string_attribute("balena.service.name", &stat.service_name),
+            ];
+            if let Some(interface) = interface {
+                attributes.push(string_attribute("network.interface.name", interface));
+            }
+            if let Some(precision) = stat.precision_of(name) {
+                attributes.push(string_attribute("balena.byte_precision", precision.as_str()));
+            }
+            metrics.entry(("container", name)).or_default().push(NumberDataPoint {
+                attributes,
+                start_time_unix_nano: start_time_of(name, container_start_unix_nano, exporter_start_unix_nano),
+                time_unix_nano,
+                value: Some(number_data_point::Value::AsDouble(value)),
+                ..Default::default()
+            });
+        }
+    }
+    for (name, value) in host.metrics() {
+        let Some(value) = value else { continue };
+        metrics.entry(("host", name)).or_default().push(NumberDataPoint {
+            start_time_unix_nano: start_time_of(name, exporter_start_unix_nano, exporter_start_unix_nano),
+            time_unix_nano,
+            value: Some(number_data_point::Value::AsDouble(value)),
+            ..Default::default()
+        });
+    }
+    build_metrics(metrics)
+}
+
+/// Disk usage gauges, stamped with the time the engine answered.
+fn map_disk_usage_to_metrics(disk_usage: &[DiskUsage]) -> Vec<Metric> {
+    let mut metrics: BTreeMap<(&'static str, &'static str), Vec<NumberDataPoint>> = BTreeMap::new();
+    for usage in disk_usage {
+        for (name, value) in usage.metrics() {
+            let Some(value) = value else { continue };
+            metrics.entry(("container", name)).or_default().push(NumberDataPoint {
+                attributes: vec![
+                    string_attribute("container.id", &usage.container_id),
+                    string_attribute("container.name", &usage.container_name),
+                    string_attribute("balena.service.name", &usage.service_name),
+                    string_attribute("balena.byte_precision", BytePrecision::EXACT.as_str()),
+                ],
+                time_unix_nano: unix_nano(usage.timestamp),
+                value: Some(number_data_point::Value::AsDouble(value)),
+                ..Default::default()
+            });
+        }
+    }
+    build_metrics(metrics)
+}
+
+// Keyed by namespace and metric name; counters become sums.
+fn build_metrics(metrics: BTreeMap<(&'static str, &'static str), Vec<NumberDataPoint>>) -> Vec<Metric> {
+    metrics
+        .into_iter()
+        .map(|((namespace, name), data_points)| {
+            let data = if MetricKind::of(name).is_counter() {
+                metric::Data::Sum(Sum {
+                    data_points,
+                    aggregation_temporality: AggregationTemporality::Cumulative as i32,
+                    is_monotonic: true,
+                })
+            } else {
+                metric::Data::Gauge(Gauge { data_points })
+            };
+            Metric {
+                name: format!("{}.{}", namespace, name.strip_prefix("host_").unwrap_or(name)),
+                unit: unit_of(name).to_string(),
+                data: Some(data),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+// Gauges have no start time.
+fn start_time_of(metric: &str, container_start_unix_nano: u64, exporter_start_unix_nano: u64) -> u64 {
+    match MetricKind::of(metric) {
+        MetricKind::GAUGE => 0,
+        MetricKind::COUNTER => container_start_unix_nano,
+        MetricKind::LIFETIME => exporter_start_unix_nano,
+    }
+}
+
+fn unix_nano(timestamp: DateTime<Utc>) -> u64 {
+    timestamp.timestamp_nanos_opt().unwrap_or_default() as u64
+}
+
+// UCUM units as expected by OpenTelemetry.
+fn unit_of(metric: &str) -> &'static str {
+    if metric.ends_with("_in_percent") {
+        "%"
+    } else if metric.ends_with("_in_bytes") {
+        "By"
+    } else if metric == "amount_of_pids" {
+        "{process}"
+    } else if metric.ends_with("_cores") {
+        "{cpu}"
+    } else if metric.ends_with("_in_seconds") {
+        "s"
+    } else if metric.ends_with("_in_microseconds") {
+        "us"
+    } else if metric.ends_with("_events") {
+        "{event}"
+    } else if metric.ends_with("_packets") || metric.ends_with("_errors") || metric.ends_with("_dropped") {
+        "{packet}"
+    } else {
+        "1"
+    }
+}
+
+fn string_attribute(key: &str, value: &str) -> KeyValue {
+    KeyValue {
+        key: key.to_string(),
+        value: Some(AnyValue {
+            value: Some(any_value::Value::StringValue(value.to_string())),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{NetworkInterfaceStats, Pressure, PressureStats};
+    use crate::util::test_support::receive_request;
+    use byte_unit::{Byte, Unit};
+    use opentelemetry_proto::tonic::collector::metrics::v1::metrics_service_server::{
+        MetricsService, MetricsServiceServer,
+    };
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+
+    fn setup_config(protocol: OtlpProtocol, endpoint: String) -> OtlpConfig {
+        let mut config: OtlpConfig = get_config(build_path(vec!["test-data/config/otlp.config.json"]));
+        config.protocol = protocol;
+        config.endpoint = endpoint;
+        config
+    }
+
+    fn setup_stats() -> ContainerStats {
+        ContainerStats {
+            container_id: "4889ab0711ac17500a5de1c3d50eb6bfbc7eb367d349797f9fe8dcb92fd9e914".to_string(),
+            container_id_short: "4889ab0711ac".to_string(),
+            container_name: "b_10800414_3361262_f54e4ffc136d1344ee98993b36b9deeb".to_string(),
+            service_name: "b".to_string(),
+            cpu_usage_in_percent: Some(1.5),
+            network_input: Byte::from_i64_with_unit(541, Unit::MB),
+            ..Default::default()
+        }
+    }
+
+    fn attribute<'a>(attributes: &'a [KeyValue], key: &str) -> Option<&'a str> {
+        attributes
+            .iter()
+            .find(|attribute| attribute.key == key)
+            .and_then(|attribute| match attribute.value.as_ref()?.value.as_ref()? {
+                any_value::Value::StringValue(value) => Some(value.as_str()),
+                _ => None,
+            })
+    }
+
+    fn data_points(metric: &Metric) -> &[NumberDataPoint] {
+        match metric.data.as_ref().unwrap() {
+            metric::Data::Gauge(gauge) => &gauge.data_points,
+            metric::Data::Sum(sum) => &sum.data_points,
+            _ => panic!("Unexpected metric type"),
+        }
+    }
+
+    fn setup_request(config: &OtlpConfig) -> ExportMetricsServiceRequest {
+        let metrics = map_to_metrics(&[setup_stats()], &HostStats::default(), 1_000, 500);
+        build_request(&build_resource(config), &[metrics])
+    }
+
+    #[test]
+    fn should_map_stats_to_metrics_with_container_attributes() {
+        let actual = map_to_metrics(&[setup_stats()], &HostStats::default(), 1_000, 500);
+
+        let names: Vec<&str> = actual.iter().map(|metric| metric.name.as_str()).collect();
+        assert_eq!(names, vec!["container.cpu_usage_in_percent", "container.network_input_in_bytes"]);
+        let cpu = &actual[0];
+        assert_eq!(cpu.unit, "%");
+        assert!(matches!(cpu.data, Some(metric::Data::Gauge(_))));
+        let point = &data_points(cpu)[0];
+        assert_eq!(point.time_unix_nano, 1_000);
+        assert_eq!(point.value, Some(number_data_point::Value::AsDouble(1.5)));
+        assert_eq!(
+            attribute(&point.attributes, "container.id"),
+            Some("4889ab0711ac17500a5de1c3d50eb6bfbc7eb367d349797f9fe8dcb92fd9e914")
+        );
+        assert_eq!(
+            attribute(&point.attributes, "container.name"),
+            Some("b_10800414_3361262_f54e4ffc136d1344ee98993b36b9deeb")
+        );
+        assert_eq!(attribute(&point.attributes, "balena.service.name"), Some("b"));
+        assert_eq!(attribute(&point.attributes, "balena.byte_precision"), None);
+        let network = &actual[1];
+        assert_eq!(network.unit, "By");
+        assert_eq!(attribute(&data_points(network)[0].attributes, "balena.byte_precision"), Some("ROUNDED"));
+        assert!(matches!(&network.data, Some(metric::Data::Sum(sum)) if sum.is_monotonic));
+        assert_eq!(point.start_time_unix_nano, 0);
+        assert_eq!(data_points(network)[0].start_time_unix_nano, 500);
+    }
+
+    #[test]
+    fn should_fall_back_to_short_container_id() {
+        let stats = ContainerStats { container_id: String::new(), ..setup_stats() };
+
+        let actual = map_to_metrics(&[stats], &HostStats::default(), 1_000, 500);
+
+        assert_eq!(attribute(&data_points(&actual[0])[0].attributes, "container.id"), Some("4889ab0711ac"));
+    }
+
+    #[test]
+    fn should_start_sums_with_container() {
+        let stats = ContainerStats {
+            started_at: Some(DateTime::from_timestamp(1_740_830_400, 0).unwrap()),
+            ..setup_stats()
+        };
+
+        let actual = map_to_metrics(&[stats], &HostStats::default(), 1_740_830_460_000_000_000, 500);
+
+        let network = actual.iter().find(|metric| metric.name == "container.network_input_in_bytes").unwrap();
+        assert_eq!(data_points(network)[0].start_time_unix_nano, 1_740_830_400_000_000_000);
+    }
+
+    #[test]
+    fn should_map_counters_as_sums() {
+        let stats = ContainerStats {
+            started_at: Some(DateTime::from_timestamp(1_740_830_400, 0).unwrap()),
+            mem_oom_kill_events: Some(1),
+            restart_count: Some(2),
+            ..setup_stats()
+        };
+
+        let actual = map_to_metrics(&[stats], &HostStats::default(), 1_740_830_460_000_000_000, 500);
+
+        let oom_kills = actual.iter().find(|metric| metric.name == "container.memory_oom_kill_events").unwrap();
+        assert!(matches!(&oom_kills.data, Some(metric::Data::Sum(sum)) if sum.is_monotonic));
+        assert_eq!(data_points(oom_kills)[0].start_time_unix_nano, 1_740_830_400_000_000_000);
+        let restarts = actual.iter().find(|metric| metric.name == "container.restart_count").unwrap();
+        assert!(matches!(&restarts.data, Some(metric::Data::Sum(sum)) if sum.is_monotonic));
+        assert_eq!(data_points(restarts)[0].start_time_unix_nano, 500);
+    }
+
+    #[test]
+    fn should_map_host_metrics_once_without_container_attributes() {
+        let host = HostStats {
+            io_pressure: Some(PressureStats {
+                some: Pressure {
+                    avg10_in_percent: 0.5,
+                    avg60_in_percent: 0.25,
+                    avg300_in_percent: 0.125,
+                    total_in_microseconds: 4_000,
+                },
+                full: None,
+            }),
+            ..Default::default()
+        };
+
+        let actual = map_to_metrics(&[setup_stats(), setup_stats()], &host, 1_000, 500);
+
+        let average = actual.iter().find(|metric| metric.name == "host.io_pressure_some_avg10_in_percent").unwrap();
+        assert!(matches!(average.data, Some(metric::Data::Gauge(_))));
+        assert_eq!(data_points(average).len(), 1);
+        assert_eq!(data_points(average)[0].attributes, vec![]);
+        let total = actual.iter().find(|metric| metric.name == "host.io_pressure_some_total_in_microseconds").unwrap();
+        assert!(matches!(&total.data, Some(metric::Data::Sum(sum)) if sum.is_monotonic));
+        assert_eq!(total.unit, "us");
+        assert_eq!(data_points(total)[0].start_time_unix_nano, 500);
+        assert_eq!(data_points(total)[0].value, Some(number_data_point::Value::AsDouble(4_000.0)));
+    }
+
+    #[test]
+    fn should_map_disk_usage_to_gauges_at_its_timestamp() {
+        let disk_usage = DiskUsage {
+            service_name: "b".to_string(),
+            container_name: "b_1".to_string(),
+            container_id: "4889ab0711ac17500a5de1c3d50eb6bfbc7eb367d349797f9fe8dcb92fd9e914".to_string(),
+            timestamp: DateTime::from_timestamp(1_740_830_400, 0).unwrap(),
+            volumes_in_bytes: Some(1_024),
+            ..Default::default()
+        };
+
+        let actual = map_disk_usage_to_metrics(&[disk_usage]);
+
+        assert_eq!(actual.len(), 1);
+        assert_eq!(actual[0].name, "container.disk_volumes_in_bytes");
+        assert_eq!(actual[0].unit, "By");
+        assert!(matches!(actual[0].data, Some(metric::Data::Gauge(_))));
+        let point = &data_points(&actual[0])[0];
+        assert_eq!(point.time_unix_nano, 1_740_830_400_000_000_000);
+        assert_eq!(point.value, Some(number_data_point::Value::AsDouble(1_024.0)));
+        assert_eq!(
+            attribute(&point.attributes, "container.id"),
+            Some("4889ab0711ac17500a5de1c3d50eb6bfbc7eb367d349797f9fe8dcb92fd9e914")
+        );
+        assert_eq!(attribute(&point.attributes, "balena.service.name"), Some("b"));
+        assert_eq!(attribute(&point.attributes, "balena.byte_precision"), Some("EXACT"));
+    }
+
+    #[test]
+    fn should_map_interface_metrics_with_interface_attribute() {
+        let stats = ContainerStats {
+            network_interfaces: BTreeMap::from([("eth1".to_string(), NetworkInterfaceStats {
+                input_dropped: 3,
+                ..Default::default()
+            })]),
+            ..setup_stats()
+        };
+
+        let actual = map_to_metrics(&[stats], &HostStats::default(), 1_000, 500);
+
+        let dropped = actual
+            .iter()
+            .find(|metric| metric.name == "container.network_interface_input_dropped")
+            .unwrap();
+        assert!(matches!(&dropped.data, Some(metric::Data::Sum(sum)) if sum.is_monotonic));
+        let point = &data_points(dropped)[0];
+        assert_eq!(point.value, Some(number_data_point::Value::AsDouble(3.0)));
+        assert_eq!(attribute(&point.attributes, "network.interface.name"), Some("eth1"));
+    }
+
+    #[test]
+    fn should_merge_batched_collections_per_metric() {
+        let config = setup_config(OtlpProtocol::HttpProtobuf, "http://localhost:4318".to_string());
+        let entries = vec![
+            map_to_metrics(&[setup_stats()], &HostStats::default(), 1_000, 500),
+            map_to_metrics(&[setup_stats()], &HostStats::default(), 2_000, 500),
+        ];
+
+        let actual = build_request(&build_resource(&config), &entries);
+
+        let resource = actual.resource_metrics[0].resource.as_ref().unwrap();
+        assert_eq!(attribute(&resource.attributes, "device.id"), Some("d35a7ea843c61c723a12f19a41c26ef1"));
+        assert_eq!(attribute(&resource.attributes, "balena.unit"), Some("my-unit"));
+        assert_eq!(attribute(&resource.attributes, "deployment.environment"), Some("test"));
+        let metrics = &actual.resource_metrics[0].scope_metrics[0].metrics;
+        assert_eq!(metrics.len(), 2);
+        let times: Vec<u64> = data_points(&metrics[0]).iter().map(|point| point.time_unix_nano).collect();
+        assert_eq!(times, vec![1_000, 2_000]);
+    }
+
+    #[test]
+    fn should_reject_endpoint_without_scheme() {
+        let config = setup_config(OtlpProtocol::GRPC, "localhost:4317".to_string());
+
+        assert!(build_transport(&config).is_err());
+    }
+
+    #[test]
+    fn should_build_grpc_transport_over_tls() {
+        let config = setup_config(OtlpProtocol::GRPC, "https://localhost:4317".to_string());
+
+        assert!(build_transport(&config).is_ok());
+    }
+
+    #[test]
+    fn should_send_protobuf_over_http() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let config = setup_config(
+            OtlpProtocol::HttpProtobuf,
+            format!("http://{}", listener.local_addr().unwrap()),
+        );
+        let receiver = receive_request(listener, "200 OK", ExportMetricsServiceResponse::default().encode_to_vec());
+        let request = setup_request(&config);
+
+        build_transport(&config).unwrap().send(&request).unwrap();
+
+        let received = receiver.join().unwrap();
+        assert_eq!(received.head.start_line(), "POST /v1/metrics HTTP/1.1");
+        assert_eq!(received.head.header("content-type"), Some("application/x-protobuf"));
+        assert_eq!(received.head.header("x-api-key"), Some("secret"));
+        assert_eq!(ExportMetricsServiceRequest::decode(received.body.as_slice()).unwrap(), request);
+    }
+
+    #[test]
+    fn should_fail_on_http_error_status() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let config = setup_config(
+            OtlpProtocol::HttpProtobuf,
+            format!("http://{}", listener.local_addr().unwrap()),
+        );
+        let receiver = receive_request(listener, "503 Service Unavailable", vec![]);
+
+        let actual = build_transport(&config).unwrap().send(&setup_request(&config));
+
+        receiver.join().unwrap();
+        assert!(actual.is_err());
+    }
+
+    struct GrpcReceiver {
+        requests: mpsc::Sender<(Option<String>, ExportMetricsServiceRequest)>,
+    }
+
+    #[tonic::async_trait]
+    impl MetricsService for GrpcReceiver {
+        async fn export(
+            &self,
+            request: tonic::Request<ExportMetricsServiceRequest>,
+        ) -> Result<tonic::Response<ExportMetricsServiceResponse>, tonic::Status> {
+            let api_key = request
+                .metadata()
+                .get("x-api-key")
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string);
+            self.requests.send((api_key, request.into_inner())).unwrap();
+            Ok(tonic::Response::new(ExportMetricsServiceResponse::default()))
+        }
+    }
+
+    #[test]
+    fn should_send_over_grpc() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let listener = runtime.block_on(tokio::net::TcpListener::bind("127.0.0.1:0")).unwrap();
+        let address = listener.local_addr().unwrap();
+        let (requests, received) = mpsc::channel();
+        runtime.spawn(
+            tonic::transport::Server::builder()
+                .add_service(MetricsServiceServer::new(GrpcReceiver { requests }))
+                .serve_with_incoming(tonic::transport::server::TcpIncoming::from(listener)),
+        );
+        let config = setup_config(OtlpProtocol::GRPC, format!("http://{}", address));
+        let request = setup_request(&config);
+
+        build_transport(&config).unwrap().send(&request).unwrap();
+
+        let (api_key, actual) = received.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(api_key.as_deref(), Some("secret"));
+        assert_eq!(actual, request);
+    }
+
+    #[test]
+    fn should_get_config() {
+        let actual: OtlpConfig = get_config(build_path(vec!["test-data/config/otlp.config.json"]));
+
+        assert_eq!(actual.protocol, OtlpProtocol::HttpProtobuf);
+        assert_eq!(actual.timeout_in_seconds, 10);
+        assert_eq!(actual.buffer, BufferConfig::default());
+    }
+}
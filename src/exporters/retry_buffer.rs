@@ -0,0 +1,283 @@
+use log::{error, warn};
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Batching and retrying of exporters sending to remote endpoints.
+#[derive(Clone, Deserialize, Debug, PartialEq)]
+pub(crate) struct BufferConfig {
+    #[serde(default = "default_batch_size")]
+    pub(crate) batch_size: usize,
+    #[serde(default = "default_max_entries")]
+    pub(crate) max_entries: usize,
+    #[serde(default = "default_initial_retry_delay_in_seconds")]
+    pub(crate) initial_retry_delay_in_seconds: u64,
+    #[serde(default = "default_max_retry_delay_in_seconds")]
+    pub(crate) max_retry_delay_in_seconds: u64,
+}
+
+impl Default for BufferConfig {
+    fn default() -> Self {
+        BufferConfig {
+            batch_size: default_batch_size(),
+            max_entries: default_max_entries(),
+            initial_retry_delay_in_seconds: default_initial_retry_delay_in_seconds(),
+            max_retry_delay_in_seconds: default_max_retry_delay_in_seconds(),
+        }
+    }
+}
+
+fn default_batch_size() -> usize {
+    1
+}
+
+fn default_max_entries() -> usize {
+    1000
+}
+
+fn default_initial_retry_delay_in_seconds() -> u64 {
+    1
+}
+
+fn default_max_retry_delay_in_seconds() -> u64 {
+    300
+}
+
+pub(crate) struct RetryBuffer<T> {
+    entries: VecDeque<T>,
+    max_entries: usize,
+}
+
+impl<T> RetryBuffer<T> {
+    pub(crate) fn new(max_entries: usize) -> Self {
+        RetryBuffer {
+            entries: VecDeque::new(),
+            max_entries: max_entries.max(1),
+        }
+    }
+
+    pub(crate) fn push(&mut self, entry: T) {
+        if self.entries.len() >= self.max_entries {
+            warn!("Retry buffer full with {} entries; dropping the oldest.", self.entries.len());
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Sends full batches, oldest first; stops at the first failure, keeping the failed batch for the next attempt.
+    pub(crate) fn flush<F>(&mut self, batch_size: usize, send: &mut F) -> anyhow::Result<()>
+    where
+        F: FnMut(&[T]) -> anyhow::Result<()>,
+    {
+        let batch_size = batch_size.max(1);
+        while self.entries.len() >= batch_size {
+            send(&self.entries.make_contiguous()[..batch_size])?;
+            self.entries.drain(..batch_size);
+        }
+        Ok(())
+    }
+}
+
+/// Lower bound of retry delays.
+const MIN_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+pub(crate) struct Backoff {
+    initial: Duration,
+    max: Duration,
+    next: Duration,
+}
+
+impl Backoff {
+    pub(crate) fn new(initial: Duration, max: Duration) -> Self {
+        let initial = initial.max(MIN_RETRY_DELAY);
+        let max = max.max(initial);
+        Backoff {
+            initial,
+            max,
+            next: initial,
+        }
+    }
+
+    pub(crate) fn next_delay(&mut self) -> Duration {
+        let delay = self.next;
+        self.next = (self.next * 2).min(self.max);
+        delay
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.next = self.initial;
+    }
+}
+
+/// Spawns a thread sending the entries of the returned channel with `send`, buffered and retried per `config`.
+pub(crate) fn spawn_buffered_sender<T, F>(name: &str, config: BufferConfig, send: F) -> Sender<T>
+where
+    T: Send + 'static,
+    F: FnMut(&[T]) -> anyhow::Result<()> + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+    let thread_name = name.to_string();
+    thread::Builder::new()
+        .name(name.to_string())
+        .spawn(move || run(&thread_name, receiver, config, send))
+        .unwrap_or_else(|err| panic!("Could not spawn {} thread: {}", name, err));
+    sender
+}
+
+fn run<T, F>(name: &str, receiver: Receiver<T>, config: BufferConfig, mut send: F)
+where
+    F: FnMut(&[T]) -> anyhow::Result<()>,
+{
+    let mut buffer = RetryBuffer::new(config.max_entries);
+    let mut backoff = Backoff::new(
+        Duration::from_secs(config.initial_retry_delay_in_seconds),
+        Duration::from_secs(config.max_retry_delay_in_seconds),
+    );
+    let mut retry_at: Option<Instant> = None;
+
+    loop {
+        let received = match retry_at {
+            None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            Some(retry_at) => receiver.recv_timeout(retry_at.saturating_duration_since(Instant::now())),
+        };
+        match received {
+            Ok(entry) => buffer.push(entry),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        while let Ok(entry) = receiver.try_recv() {
+            buffer.push(entry);
+        }
+        if retry_at.is_some_and(|retry_at| Instant::now() < retry_at) {
+            continue;
+        }
+
+        match buffer.flush(config.batch_size, &mut send) {
+            Ok(()) => {
+                backoff.reset();
+                retry_at = None;
+            }
+            Err(err) => {
+                let delay = backoff.next_delay();
+                error!(
+                    "{} failed, retrying in {}s with {} buffered entries: {}",
+                    name,
+                    delay.as_secs_f32(),
+                    buffer.len(),
+                    err
+                );
+                retry_at = Some(Instant::now() + delay);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn should_drop_oldest_entries_when_full() {
+        let mut buffer = RetryBuffer::new(2);
+        buffer.push(1);
+        buffer.push(2);
+        buffer.push(3);
+
+        let mut sent = vec![];
+        buffer
+            .flush(1, &mut |batch: &[i32]| {
+                sent.extend_from_slice(batch);
+                Ok(())
+            })
+            .unwrap();
+
+        assert_eq!(sent, vec![2, 3]);
+    }
+
+    #[test]
+    fn should_send_full_batches_only() {
+        let mut buffer = RetryBuffer::new(10);
+        (1..=5).for_each(|entry| buffer.push(entry));
+
+        let mut sent = vec![];
+        buffer
+            .flush(2, &mut |batch: &[i32]| {
+                sent.push(batch.to_vec());
+                Ok(())
+            })
+            .unwrap();
+
+        assert_eq!(sent, vec![vec![1, 2], vec![3, 4]]);
+        assert_eq!(buffer.len(), 1);
+    }
+
+    #[test]
+    fn should_keep_entries_of_failed_batch() {
+        let mut buffer = RetryBuffer::new(10);
+        (1..=3).for_each(|entry| buffer.push(entry));
+
+        let mut attempts = 0;
+        let result = buffer.flush(1, &mut |_: &[i32]| {
+            attempts += 1;
+            if attempts == 2 { Err(anyhow!("unreachable")) } else { Ok(()) }
+        });
+
+        assert!(result.is_err());
+        assert_eq!(buffer.len(), 2);
+    }
+
+    #[test]
+    fn should_double_backoff_up_to_max() {
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(5));
+
+        let delays: Vec<u64> = (0..5).map(|_| backoff.next_delay().as_secs()).collect();
+        backoff.reset();
+
+        assert_eq!(delays, vec![1, 2, 4, 5, 5]);
+        assert_eq!(backoff.next_delay().as_secs(), 1);
+    }
+
+    #[test]
+    fn should_not_retry_without_delay() {
+        let mut backoff = Backoff::new(Duration::ZERO, Duration::ZERO);
+
+        assert_eq!(backoff.next_delay(), MIN_RETRY_DELAY);
+        assert_eq!(backoff.next_delay(), MIN_RETRY_DELAY);
+    }
+
+    #[test]
+    fn should_retry_in_background_until_sent() {
+        let sent = Arc::new(Mutex::new(vec![]));
+        let sent_by_thread = sent.clone();
+        let mut attempts = 0;
+        let config = BufferConfig {
+            initial_retry_delay_in_seconds: 0,
+            ..Default::default()
+        };
+        let sender = spawn_buffered_sender("test sender", config, move |batch: &[i32]| {
+            attempts += 1;
+            if attempts < 3 {
+                return Err(anyhow!("unreachable"));
+            }
+            sent_by_thread.lock().unwrap().extend_from_slice(batch);
+            Ok(())
+        });
+
+        sender.send(1).unwrap();
+        sender.send(2).unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while sent.lock().unwrap().len() < 2 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(*sent.lock().unwrap(), vec![1, 2]);
+    }
+}
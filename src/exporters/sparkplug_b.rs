@@ -0,0 +1,567 @@
+use crate::domain::{shared_services, ContainerStats, HostStats};
+use prost::Message;
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+const NAMESPACE: &str = "spBv1.0";
+pub(crate) const BD_SEQ_METRIC: &str = "bdSeq";
+pub(crate) const REBIRTH_METRIC: &str = "Node Control/Rebirth";
+
+/// Subset of the Sparkplug B protobuf schema (`sparkplug_b.proto`) used by this exporter.
+pub(crate) mod proto {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub(crate) struct Payload {
+        #[prost(uint64, optional, tag = "1")]
+        pub(crate) timestamp: Option<u64>,
+        #[prost(message, repeated, tag = "2")]
+        pub(crate) metrics: Vec<Metric>,
+        #[prost(uint64, optional, tag = "3")]
+        pub(crate) seq: Option<u64>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub(crate) struct Metric {
+        #[prost(string, optional, tag = "1")]
+        pub(crate) name: Option<String>,
+        #[prost(uint64, optional, tag = "3")]
+        pub(crate) timestamp: Option<u64>,
+        #[prost(uint32, optional, tag = "4")]
+        pub(crate) datatype: Option<u32>,
+        #[prost(bool, optional, tag = "7")]
+        pub(crate) is_null: Option<bool>,
+        #[prost(oneof = "Value", tags = "10, 11, 12, 13, 14, 15")]
+        pub(crate) value: Option<Value>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub(crate) enum Value {
+        #[prost(uint32, tag = "10")]
+        Int(u32),
+        #[prost(uint64, tag = "11")]
+        Long(u64),
+        #[prost(float, tag = "12")]
+        Float(f32),
+        #[prost(double, tag = "13")]
+        Double(f64),
+        #[prost(bool, tag = "14")]
+        Boolean(bool),
+        #[prost(string, tag = "15")]
+        String(String),
+    }
+}
+
+/// Sparkplug B data types, see the specification chapter "Data Types".
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(u32)]
+pub(crate) enum DataType {
+    UInt16 = 6,
+    UInt64 = 8,
+    Float = 9,
+    Double = 10,
+    Boolean = 11,
+    String = 12,
+}
+
+#[derive(Clone, Deserialize, Debug, PartialEq)]
+pub(crate) struct SparkplugBConfig {
+    pub(crate) group_id: String,
+    pub(crate) edge_node_id: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct SparkplugMessage {
+    pub(crate) topic: String,
+    pub(crate) payload: Vec<u8>,
+}
+
+/// Sparkplug B edge node with the host's metrics and one device per container; tracks births and sequence numbers.
+pub(crate) struct SparkplugNode {
+    config: SparkplugBConfig,
+    bd_seq: u64,
+    seq: u64,
+    born: bool,
+    // Network interfaces of every born device.
+    devices: BTreeMap<String, Vec<String>>,
+}
+
+impl SparkplugNode {
+    pub(crate) fn new(config: SparkplugBConfig) -> Self {
+        SparkplugNode {
+            config,
+            bd_seq: 0,
+            seq: 0,
+            born: false,
+            devices: BTreeMap::new(),
+        }
+    }
+
+    /// Forces NBIRTH with `bd_seq` and DBIRTHs with the next collection.
+    pub(crate) fn rebirth(&mut self, bd_seq: u64) {
+        self.bd_seq = bd_seq;
+        self.born = false;
+        self.devices.clear();
+    }
+
+    /// Host metrics are born with the node and sent as NDATA once any is available.
+    pub(crate) fn messages(
+        &mut self,
+        stats: &[ContainerStats],
+        host: &HostStats,
+        timestamp: u64,
+    ) -> Vec<SparkplugMessage> {
+        let mut messages = vec![];
+        let host_metrics: Vec<proto::Metric> =
+            host.metrics().into_iter().map(|(name, value)| build_value_metric(name, value)).collect();
+        if !self.born {
+            self.seq = 0;
+            messages.push(self.node_message("NBIRTH", host_metrics, timestamp));
+            self.born = true;
+        } else if host_metrics.iter().any(|metric| metric.value.is_some()) {
+            messages.push(self.node_message("NDATA", host_metrics, timestamp));
+        }
+
+        let shared = shared_services(stats.iter().map(|stat| stat.service_name.as_str()));
+        let devices: Vec<String> = stats.iter().map(|stat| device_id(stat, &shared)).collect();
+        let current: BTreeSet<&str> = devices.iter().map(String::as_str).collect();
+        let vanished: Vec<String> =
+            self.devices.keys().filter(|device| !current.contains(device.as_str())).cloned().collect();
+        for device in vanished {
+            messages.push(self.device_message("DDEATH", &device, vec![], timestamp));
+            self.devices.remove(&device);
+        }
+
+        for (stat, device) in stats.iter().zip(devices) {
+            let metrics = map_to_metrics(stat);
+            // DDATA may only contain born metrics, so interfaces attached since need a new DBIRTH.
+            let interfaces: Vec<String> = stat.network_interfaces.keys().cloned().collect();
+            let message_type = match self.devices.insert(device.clone(), interfaces.clone()) {
+                Some(born) if born == interfaces => "DDATA",
+                _ => "DBIRTH",
+            };
+            messages.push(self.device_message(message_type, &device, metrics, timestamp));
+        }
+        messages
+    }
+
+    fn node_message(&mut self, message_type: &str, metrics: Vec<proto::Metric>, timestamp: u64) -> SparkplugMessage {
+        let mut payload_metrics = vec![];
+        if message_type == "NBIRTH" {
+            payload_metrics.push(build_metric(BD_SEQ_METRIC, DataType::UInt64, Some(proto::Value::Long(self.bd_seq))));
+            payload_metrics.push(build_metric(REBIRTH_METRIC, DataType::Boolean, Some(proto::Value::Boolean(false))));
+        }
+        payload_metrics.extend(metrics);
+        let payload = proto::Payload {
+            timestamp: Some(timestamp),
+            metrics: payload_metrics,
+            seq: Some(self.next_seq()),
+        };
+        SparkplugMessage {
+            topic: node_topic(&self.config, message_type),
+            payload: payload.encode_to_vec(),
+        }
+    }
+
+    fn device_message(
+        &mut self,
+        message_type: &str,
+        device: &str,
+        metrics: Vec<proto::Metric>,
+        timestamp: u64,
+    ) -> SparkplugMessage {
+        let payload = proto::Payload {
+            timestamp: Some(timestamp),
+            metrics,
+            seq: Some(self.next_seq()),
+        };
+        SparkplugMessage {
+            topic: format!("{}/{}", node_topic(&self.config, message_type), device),
+            payload: payload.encode_to_vec(),
+        }
+    }
+
+    // NBIRTH starts at 0; every following message increments, wrapping after 255.
+    fn next_seq(&mut self) -> u64 {
+        let seq = self.seq;
+        self.seq = (self.seq + 1) % 256;
+        seq
+    }
+}
+
+/// Service name, which outlives releases; suffixed with the short container id while the service runs several.
+fn device_id(stat: &ContainerStats, shared: &BTreeSet<&str>) -> String {
+    match shared.contains(stat.service_name.as_str()) {
+        true => format!("{}_{}", stat.service_name, stat.container_id_short),
+        false => stat.service_name.clone(),
+    }
+}
+
+/// NDEATH to register as MQTT will of the connection with `bd_seq`.
+pub(crate) fn death_message(config: &SparkplugBConfig, bd_seq: u64) -> SparkplugMessage {
+    let payload = proto::Payload {
+        timestamp: None,
+        metrics: vec![build_metric(BD_SEQ_METRIC, DataType::UInt64, Some(proto::Value::Long(bd_seq)))],
+        seq: None,
+    };
+    SparkplugMessage {
+        topic: node_topic(config, "NDEATH"),
+        payload: payload.encode_to_vec(),
+    }
+}
+
+pub(crate) fn command_topic(config: &SparkplugBConfig) -> String {
+    node_topic(config, "NCMD")
+}
+
+fn node_topic(config: &SparkplugBConfig, message_type: &str) -> String {
+    format!("{}/{}/{}/{}", NAMESPACE, config.group_id, message_type, config.edge_node_id)
+}
+
+/// Whether an NCMD payload asks for a rebirth.
+pub(crate) fn is_rebirth_request(payload: &[u8]) -> bool {
+    proto::Payload::decode(payload).is_ok_and(|payload| {
+        payload.metrics.iter().any(|metric| {
+            metric.name.as_deref() == Some(REBIRTH_METRIC)
+                && metric.value == Some(proto::Value::Boolean(true))
+        })
+    })
+}
+
+fn map_to_metrics(stats: &ContainerStats) -> Vec<proto::Metric> {
+    let mut metrics = vec![
+        build_metric("container_id", DataType::String, Some(proto::Value::String(stats.container_id.clone()))),
+        build_metric(
+            "container_id_short",
+            DataType::String,
+            Some(proto::Value::String(stats.container_id_short.clone())),
+        ),
+        build_metric(
+            "container_name",
+            DataType::String,
+            Some(proto::Value::String(stats.container_name.clone())),
+        ),
+        build_metric("state", DataType::String, stats.state.clone().map(proto::Value::String)),
+        build_metric("health_status", DataType::String, stats.health_status.clone().map(proto::Value::String)),
+    ];
+    metrics.extend(stats.metrics().into_iter().map(|(name, value)| build_value_metric(name, value)));
+    // Named like their MQTT topics, e.g. `network_interface_input_dropped/eth0`.
+    metrics.extend(stats.interface_metrics().into_iter().map(|(interface, name, value)| {
+        build_metric(
+            &format!("{}/{}", name, interface),
+            DataType::UInt64,
+            Some(proto::Value::Long(value as u64)),
+        )
+    }));
+    metrics
+}
+
+fn build_value_metric(name: &str, value: Option<f64>) -> proto::Metric {
+    let data_type = data_type_of(name);
+    let value = value.map(|value| match data_type {
+        DataType::Float => proto::Value::Float(value as f32),
+        DataType::UInt64 => proto::Value::Long(value as u64),
+        DataType::UInt16 => proto::Value::Int(value as u32),
+        DataType::Boolean => proto::Value::Boolean(value != 0.0),
+        _ => proto::Value::Double(value),
+    });
+    build_metric(name, data_type, value)
+}
+
+fn data_type_of(metric: &str) -> DataType {
+    if metric.ends_with("_in_percent") {
+        DataType::Float
+    } else if metric.ends_with("_in_bytes") {
+        DataType::UInt64
+    } else if metric == "amount_of_pids" {
+        DataType::UInt16
+    } else if metric == "healthy" || metric == "oom_killed" {
+        DataType::Boolean
+    } else {
+        DataType::Double
+    }
+}
+
+fn build_metric(name: &str, data_type: DataType, value: Option<proto::Value>) -> proto::Metric {
+    proto::Metric {
+        name: Some(name.to_string()),
+        timestamp: None,
+        datatype: Some(data_type as u32),
+        is_null: value.is_none().then_some(true),
+        value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{NetworkInterfaceStats, Pressure, PressureStats};
+    use byte_unit::{Byte, Unit};
+
+    fn setup_config() -> SparkplugBConfig {
+        SparkplugBConfig {
+            group_id: "telemetry".to_string(),
+            edge_node_id: "d35a7ea843c61c723a12f19a41c26ef1".to_string(),
+        }
+    }
+
+    fn setup_node() -> SparkplugNode {
+        let mut node = SparkplugNode::new(setup_config());
+        node.rebirth(3);
+        node
+    }
+
+    fn setup_stats(service_name: &str) -> ContainerStats {
+        ContainerStats {
+            container_id: "4889ab0711ac17500a5de1c3d50eb6bfbc7eb367d349797f9fe8dcb92fd9e914".to_string(),
+            container_id_short: "4889ab0711ac".to_string(),
+            container_name: format!("{}_1", service_name),
+            service_name: service_name.to_string(),
+            cpu_usage_in_percent: Some(1.75),
+            mem_usage: Byte::from_i64_with_unit(318, Unit::MiB),
+            amount_of_pids: Some(26),
+            ..Default::default()
+        }
+    }
+
+    fn decode(message: &SparkplugMessage) -> proto::Payload {
+        proto::Payload::decode(message.payload.as_slice()).unwrap()
+    }
+
+    fn metric<'a>(payload: &'a proto::Payload, name: &str) -> &'a proto::Metric {
+        payload
+            .metrics
+            .iter()
+            .find(|metric| metric.name.as_deref() == Some(name))
+            .unwrap()
+    }
+
+    fn summary(messages: &[SparkplugMessage]) -> Vec<(String, Option<u64>)> {
+        messages
+            .iter()
+            .map(|message| (message.topic.clone(), decode(message).seq))
+            .collect()
+    }
+
+    #[test]
+    fn should_send_births_before_data() {
+        let mut node = setup_node();
+
+        let first = node.messages(&[setup_stats("a"), setup_stats("b")], &HostStats::default(), 1_000);
+        let second = node.messages(&[setup_stats("a"), setup_stats("b")], &HostStats::default(), 2_000);
+
+        assert_eq!(
+            summary(&first),
+            vec![
+                ("spBv1.0/telemetry/NBIRTH/d35a7ea843c61c723a12f19a41c26ef1".to_string(), Some(0)),
+                ("spBv1.0/telemetry/DBIRTH/d35a7ea843c61c723a12f19a41c26ef1/a".to_string(), Some(1)),
+                ("spBv1.0/telemetry/DBIRTH/d35a7ea843c61c723a12f19a41c26ef1/b".to_string(), Some(2)),
+            ]
+        );
+        assert_eq!(
+            summary(&second),
+            vec![
+                ("spBv1.0/telemetry/DDATA/d35a7ea843c61c723a12f19a41c26ef1/a".to_string(), Some(3)),
+                ("spBv1.0/telemetry/DDATA/d35a7ea843c61c723a12f19a41c26ef1/b".to_string(), Some(4)),
+            ]
+        );
+        let node_birth = decode(&first[0]);
+        assert_eq!(node_birth.timestamp, Some(1_000));
+        assert_eq!(metric(&node_birth, BD_SEQ_METRIC).value, Some(proto::Value::Long(3)));
+        assert_eq!(metric(&node_birth, REBIRTH_METRIC).value, Some(proto::Value::Boolean(false)));
+    }
+
+    #[test]
+    fn should_send_host_metrics_as_node_metrics() {
+        let mut node = setup_node();
+        let host = HostStats {
+            io_pressure: Some(PressureStats {
+                some: Pressure {
+                    avg10_in_percent: 0.5,
+                    avg60_in_percent: 0.25,
+                    avg300_in_percent: 0.125,
+                    total_in_microseconds: 4_000,
+                },
+                full: None,
+            }),
+            ..Default::default()
+        };
+
+        let first = node.messages(&[setup_stats("a")], &HostStats::default(), 1_000);
+        let second = node.messages(&[setup_stats("a")], &host, 2_000);
+
+        let node_birth = decode(&first[0]);
+        assert_eq!(metric(&node_birth, "host_io_pressure_some_avg10_in_percent").is_null, Some(true));
+        assert_eq!(
+            summary(&second)[0],
+            ("spBv1.0/telemetry/NDATA/d35a7ea843c61c723a12f19a41c26ef1".to_string(), Some(2))
+        );
+        let node_data = decode(&second[0]);
+        assert_eq!(metric(&node_data, "host_io_pressure_some_avg10_in_percent").value, Some(proto::Value::Float(0.5)));
+        assert_eq!(
+            metric(&node_data, "host_io_pressure_some_total_in_microseconds").value,
+            Some(proto::Value::Double(4_000.0))
+        );
+    }
+
+    #[test]
+    fn should_announce_new_and_vanished_devices() {
+        let mut node = setup_node();
+        node.messages(&[setup_stats("a")], &HostStats::default(), 1_000);
+
+        let actual = node.messages(&[setup_stats("b")], &HostStats::default(), 2_000);
+
+        assert_eq!(
+            summary(&actual),
+            vec![
+                ("spBv1.0/telemetry/DDEATH/d35a7ea843c61c723a12f19a41c26ef1/a".to_string(), Some(2)),
+                ("spBv1.0/telemetry/DBIRTH/d35a7ea843c61c723a12f19a41c26ef1/b".to_string(), Some(3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_restart_sequence_with_rebirth_and_wrap_after_255() {
+        let mut node = setup_node();
+        for _ in 0..300 {
+            node.messages(&[setup_stats("a")], &HostStats::default(), 1_000);
+        }
+        assert_eq!(summary(&node.messages(&[setup_stats("a")], &HostStats::default(), 1_000))[0].1, Some(301 % 256));
+
+        node.rebirth(4);
+        let actual = node.messages(&[setup_stats("a")], &HostStats::default(), 2_000);
+
+        assert_eq!(
+            summary(&actual),
+            vec![
+                ("spBv1.0/telemetry/NBIRTH/d35a7ea843c61c723a12f19a41c26ef1".to_string(), Some(0)),
+                ("spBv1.0/telemetry/DBIRTH/d35a7ea843c61c723a12f19a41c26ef1/a".to_string(), Some(1)),
+            ]
+        );
+        assert_eq!(metric(&decode(&actual[0]), BD_SEQ_METRIC).value, Some(proto::Value::Long(4)));
+    }
+
+    #[test]
+    fn should_announce_every_container_of_a_service_as_device() {
+        let mut node = setup_node();
+        node.messages(&[setup_stats("a")], &HostStats::default(), 1_000);
+        let second = ContainerStats {
+            container_id_short: "f54e4ffc136d".to_string(),
+            container_name: "a_2".to_string(),
+            ..setup_stats("a")
+        };
+
+        let actual = node.messages(&[setup_stats("a"), second], &HostStats::default(), 2_000);
+
+        assert_eq!(
+            summary(&actual),
+            vec![
+                ("spBv1.0/telemetry/DDEATH/d35a7ea843c61c723a12f19a41c26ef1/a".to_string(), Some(2)),
+                ("spBv1.0/telemetry/DBIRTH/d35a7ea843c61c723a12f19a41c26ef1/a_4889ab0711ac".to_string(), Some(3)),
+                ("spBv1.0/telemetry/DBIRTH/d35a7ea843c61c723a12f19a41c26ef1/a_f54e4ffc136d".to_string(), Some(4)),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_map_interface_metrics_and_rebirth_devices_with_new_interfaces() {
+        let mut node = setup_node();
+        let with_interfaces = |interfaces: &[&str]| ContainerStats {
+            network_interfaces: interfaces
+                .iter()
+                .map(|interface| {
+                    let stats = NetworkInterfaceStats {
+                        input_dropped: 7,
+                        ..Default::default()
+                    };
+                    (interface.to_string(), stats)
+                })
+                .collect(),
+            ..setup_stats("a")
+        };
+
+        let birth = node.messages(&[with_interfaces(&["eth0"])], &HostStats::default(), 1_000);
+        let data = node.messages(&[with_interfaces(&["eth0"])], &HostStats::default(), 2_000);
+        let rebirth = node.messages(&[with_interfaces(&["eth0", "eth1"])], &HostStats::default(), 3_000);
+
+        let device_birth = decode(&birth[1]);
+        let dropped = metric(&device_birth, "network_interface_input_dropped/eth0");
+        assert_eq!(dropped.datatype, Some(DataType::UInt64 as u32));
+        assert_eq!(dropped.value, Some(proto::Value::Long(7)));
+        assert!(data[0].topic.contains("/DDATA/"));
+        assert!(rebirth[0].topic.contains("/DBIRTH/"));
+        let device_rebirth = decode(&rebirth[0]);
+        let added = metric(&device_rebirth, "network_interface_input_dropped/eth1");
+        assert_eq!(added.value, Some(proto::Value::Long(7)));
+    }
+
+    #[test]
+    fn should_map_stats_to_typed_metrics() {
+        let mut node = setup_node();
+
+        let messages = node.messages(&[setup_stats("a")], &HostStats::default(), 1_000);
+
+        let device_birth = decode(&messages[1]);
+        let cpu = metric(&device_birth, "cpu_usage_in_percent");
+        assert_eq!(cpu.datatype, Some(DataType::Float as u32));
+        assert_eq!(cpu.value, Some(proto::Value::Float(1.75)));
+        let memory = metric(&device_birth, "memory_usage_in_bytes");
+        assert_eq!(memory.datatype, Some(DataType::UInt64 as u32));
+        assert_eq!(memory.value, Some(proto::Value::Long(333_447_168)));
+        let pids = metric(&device_birth, "amount_of_pids");
+        assert_eq!(pids.datatype, Some(DataType::UInt16 as u32));
+        assert_eq!(pids.value, Some(proto::Value::Int(26)));
+        let name = metric(&device_birth, "container_name");
+        assert_eq!(name.datatype, Some(DataType::String as u32));
+        let unavailable = metric(&device_birth, "network_input_in_bytes");
+        assert_eq!(unavailable.datatype, Some(DataType::UInt64 as u32));
+        assert_eq!(unavailable.is_null, Some(true));
+        assert_eq!(unavailable.value, None);
+    }
+
+    #[test]
+    fn should_map_inspection_to_typed_metrics() {
+        let mut node = setup_node();
+        let stats = ContainerStats {
+            state: Some("running".to_string()),
+            health_status: Some("unhealthy".to_string()),
+            oom_killed: Some(true),
+            ..setup_stats("a")
+        };
+
+        let messages = node.messages(&[stats], &HostStats::default(), 1_000);
+
+        let device_birth = decode(&messages[1]);
+        assert_eq!(metric(&device_birth, "state").value, Some(proto::Value::String("running".to_string())));
+        let healthy = metric(&device_birth, "healthy");
+        assert_eq!(healthy.datatype, Some(DataType::Boolean as u32));
+        assert_eq!(healthy.value, Some(proto::Value::Boolean(false)));
+        assert_eq!(metric(&device_birth, "oom_killed").value, Some(proto::Value::Boolean(true)));
+    }
+
+    #[test]
+    fn should_build_death_message_with_bd_seq() {
+        let actual = death_message(&setup_config(), 3);
+
+        assert_eq!(actual.topic, "spBv1.0/telemetry/NDEATH/d35a7ea843c61c723a12f19a41c26ef1");
+        let payload = decode(&actual);
+        assert_eq!(payload.seq, None);
+        assert_eq!(metric(&payload, BD_SEQ_METRIC).value, Some(proto::Value::Long(3)));
+    }
+
+    #[test]
+    fn should_detect_rebirth_request() {
+        let request = proto::Payload {
+            timestamp: Some(1_000),
+            metrics: vec![build_metric(REBIRTH_METRIC, DataType::Boolean, Some(proto::Value::Boolean(true)))],
+            seq: None,
+        };
+        let other = proto::Payload {
+            timestamp: Some(1_000),
+            metrics: vec![build_metric("Node Control/Reboot", DataType::Boolean, Some(proto::Value::Boolean(true)))],
+            seq: None,
+        };
+
+        assert!(is_rebirth_request(&request.encode_to_vec()));
+        assert!(!is_rebirth_request(&other.encode_to_vec()));
+        assert!(!is_rebirth_request(b"not protobuf"));
+    }
+}
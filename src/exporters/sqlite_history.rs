@@ -0,0 +1,346 @@
+use crate::domain::{Collection, ContainerStats, DiskUsage, HostStats, HOST_SERVICE_NAME};
+use crate::exporters::exporter::Exporter;
+use crate::util::config::{build_path, get_config, CONFIG_DIR};
+use anyhow::anyhow;
+use lazy_static::lazy_static;
+use log::error;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+#[derive(Clone, Deserialize, Debug, PartialEq)]
+pub(crate) struct RetentionTier {
+    pub(crate) name: String,
+    pub(crate) resolution_in_seconds: i64,
+    pub(crate) retention_in_seconds: i64,
+}
+
+#[derive(Clone, Deserialize, Debug, PartialEq)]
+pub(crate) struct SqliteHistoryConfig {
+    pub(crate) database_path: String,
+    /// Ordered from finest to coarsest resolution.
+    pub(crate) tiers: Vec<RetentionTier>,
+    pub(crate) status_endpoint_address: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct SeriesPoint {
+    pub(crate) timestamp: i64,
+    pub(crate) value: f64,
+}
+
+lazy_static! {
+    pub(crate) static ref CONFIG: SqliteHistoryConfig = get_config(build_path(vec![&CONFIG_DIR, "sqlite_history.config.json"]));
+}
+
+pub struct SqliteHistoryExporter {
+    connection: Connection,
+}
+
+impl SqliteHistoryExporter {
+    pub fn new() -> anyhow::Result<Self> {
+        Ok(SqliteHistoryExporter {
+            connection: open_database(&CONFIG.database_path)?,
+        })
+    }
+}
+
+impl Exporter for SqliteHistoryExporter {
+    fn export(&mut self, collection: &Collection) {
+        let timestamp = collection.timestamp().timestamp();
+        store(&mut self.connection, &collection.stats, &collection.host, timestamp, &CONFIG.tiers)
+            .and_then(|_| remove_expired(&self.connection, timestamp, &CONFIG.tiers))
+            .unwrap_or_else(|err| error!("Storing stats in history failed! Because of {}", err))
+    }
+
+    fn export_disk_usage(&mut self, disk_usage: &[DiskUsage]) {
+        for usage in disk_usage {
+            let values = usage.metrics().map(|(metric, value)| (usage.service_name.as_str(), metric, value));
+            store_values(&mut self.connection, values, usage.timestamp.timestamp(), &CONFIG.tiers)
+                .unwrap_or_else(|err| error!("Storing disk usage in history failed! Because of {}", err))
+        }
+    }
+}
+
+pub(crate) fn open_database(database_path: &str) -> anyhow::Result<Connection> {
+    if let Some(parent) = Path::new(database_path).parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let connection = Connection::open(database_path)?;
+    // WAL lets the status endpoint read while the exporter writes.
+    connection.pragma_update(None, "journal_mode", "WAL")?;
+    connection.busy_timeout(Duration::from_secs(5))?;
+    create_schema(&connection)?;
+    Ok(connection)
+}
+
+fn create_schema(connection: &Connection) -> anyhow::Result<()> {
+    connection.execute_batch(
+        "CREATE TABLE IF NOT EXISTS samples (
+            tier TEXT NOT NULL,
+            service_name TEXT NOT NULL,
+            metric TEXT NOT NULL,
+            bucket_start INTEGER NOT NULL,
+            value REAL NOT NULL,
+            count INTEGER NOT NULL,
+            PRIMARY KEY (tier, service_name, metric, bucket_start)
+        ) WITHOUT ROWID;
+        CREATE INDEX IF NOT EXISTS samples_by_age ON samples (tier, bucket_start);",
+    )?;
+    Ok(())
+}
+
+// Host metrics are kept under the service `host`.
+fn store(
+    connection: &mut Connection,
+    stats: &[ContainerStats],
+    host: &HostStats,
+    timestamp: i64,
+    tiers: &[RetentionTier],
+) -> anyhow::Result<()> {
+    let container_values = stats.iter().flat_map(|stat| {
+        stat.metrics().into_iter().map(|(metric, value)| (stat.service_name.as_str(), metric, value))
+    });
+    let host_values = host.metrics().into_iter().map(|(metric, value)| (HOST_SERVICE_NAME, metric, value));
+    store_values(connection, container_values.chain(host_values), timestamp, tiers)
+}
+
+// Every tier keeps a running average per bucket; unavailable values are skipped.
+fn store_values<'a>(
+    connection: &mut Connection,
+    values: impl IntoIterator<Item = (&'a str, &'a str, Option<f64>)>,
+    timestamp: i64,
+    tiers: &[RetentionTier],
+) -> anyhow::Result<()> {
+    let transaction = connection.transaction()?;
+    {
+        let mut statement = transaction.prepare_cached(
+            "INSERT INTO samples (tier, service_name, metric, bucket_start, value, count)
+            VALUES (?1, ?2, ?3, ?4, ?5, 1)
+            ON CONFLICT (tier, service_name, metric, bucket_start) DO UPDATE SET
+                value = (value * count + excluded.value) / (count + 1),
+                count = count + 1",
+        )?;
+        for (service_name, metric, value) in values {
+            let Some(value) = value else { continue };
+            for tier in tiers {
+                let bucket_start = timestamp - timestamp.rem_euclid(tier.resolution_in_seconds.max(1));
+                statement.execute(params![tier.name, service_name, metric, bucket_start, value])?;
+            }
+        }
+    }
+    transaction.commit()?;
+    Ok(())
+}
+
+fn remove_expired(connection: &Connection, now: i64, tiers: &[RetentionTier]) -> anyhow::Result<()> {
+    for tier in tiers {
+        connection.execute(
+            "DELETE FROM samples WHERE tier = ?1 AND bucket_start < ?2",
+            params![tier.name, now - tier.retention_in_seconds],
+        )?;
+    }
+    Ok(())
+}
+
+/// Finest tier still holding data from `from`, falling back to the coarsest one.
+pub(crate) fn select_tier(tiers: &[RetentionTier], from: i64, now: i64) -> Option<&RetentionTier> {
+    tiers
+        .iter()
+        .find(|tier| now - tier.retention_in_seconds <= from)
+        .or(tiers.last())
+}
+
+/// Series of all metrics of a service in `[from, to]`, keyed by metric name.
+pub(crate) fn query_series(
+    connection: &Connection,
+    service_name: &str,
+    tier: &RetentionTier,
+    from: i64,
+    to: i64,
+) -> anyhow::Result<BTreeMap<String, Vec<SeriesPoint>>> {
+    let mut statement = connection.prepare_cached(
+        "SELECT metric, bucket_start, value FROM samples
+        WHERE tier = ?1 AND service_name = ?2 AND bucket_start BETWEEN ?3 AND ?4
+        ORDER BY metric, bucket_start",
+    )?;
+    let rows = statement.query_map(params![tier.name, service_name, from, to], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            SeriesPoint {
+                timestamp: row.get(1)?,
+                value: row.get(2)?,
+            },
+        ))
+    })?;
+
+    let mut series: BTreeMap<String, Vec<SeriesPoint>> = BTreeMap::new();
+    for row in rows {
+        let (metric, point) = row?;
+        series.entry(metric).or_default().push(point);
+    }
+    Ok(series)
+}
+
+pub(crate) fn find_tier<'a>(tiers: &'a [RetentionTier], name: &str) -> anyhow::Result<&'a RetentionTier> {
+    tiers
+        .iter()
+        .find(|tier| tier.name == name)
+        .ok_or(anyhow!("Unknown tier {}", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Pressure, PressureStats};
+
+    fn setup_tiers() -> Vec<RetentionTier> {
+        vec![
+            RetentionTier {
+                name: "raw".to_string(),
+                resolution_in_seconds: 1,
+                retention_in_seconds: 86_400,
+            },
+            RetentionTier {
+                name: "minute".to_string(),
+                resolution_in_seconds: 60,
+                retention_in_seconds: 604_800,
+            },
+            RetentionTier {
+                name: "hour".to_string(),
+                resolution_in_seconds: 3_600,
+                retention_in_seconds: 7_776_000,
+            },
+        ]
+    }
+
+    fn setup_stats(cpu_usage_in_percent: f32) -> ContainerStats {
+        ContainerStats {
+            container_name: "b_10800414_3361262_f54e4ffc136d1344ee98993b36b9deeb".to_string(),
+            service_name: "b".to_string(),
+            cpu_usage_in_percent: Some(cpu_usage_in_percent),
+            ..Default::default()
+        }
+    }
+
+    fn setup_connection() -> Connection {
+        let connection = Connection::open_in_memory().unwrap();
+        create_schema(&connection).unwrap();
+        connection
+    }
+
+    fn cpu_values(connection: &Connection, tier: &str, from: i64, to: i64) -> Vec<(i64, f64)> {
+        let tiers = setup_tiers();
+        query_series(connection, "b", find_tier(&tiers, tier).unwrap(), from, to)
+            .unwrap()
+            .remove("cpu_usage_in_percent")
+            .unwrap_or_default()
+            .into_iter()
+            .map(|point| (point.timestamp, point.value))
+            .collect()
+    }
+
+    #[test]
+    fn should_store_raw_and_downsampled_averages() {
+        let mut connection = setup_connection();
+        let tiers = setup_tiers();
+
+        store(&mut connection, &[setup_stats(1.0)], &HostStats::default(), 3_600, &tiers).unwrap();
+        store(&mut connection, &[setup_stats(2.0)], &HostStats::default(), 3_615, &tiers).unwrap();
+        store(&mut connection, &[setup_stats(6.0)], &HostStats::default(), 3_660, &tiers).unwrap();
+
+        assert_eq!(
+            cpu_values(&connection, "raw", 0, 10_000),
+            vec![(3_600, 1.0), (3_615, 2.0), (3_660, 6.0)]
+        );
+        assert_eq!(cpu_values(&connection, "minute", 0, 10_000), vec![(3_600, 1.5), (3_660, 6.0)]);
+        assert_eq!(cpu_values(&connection, "hour", 0, 10_000), vec![(3_600, 3.0)]);
+    }
+
+    #[test]
+    fn should_skip_unavailable_metrics() {
+        let mut connection = setup_connection();
+
+        store(&mut connection, &[setup_stats(1.0)], &HostStats::default(), 3_600, &setup_tiers()).unwrap();
+
+        let tiers = setup_tiers();
+        let series = query_series(&connection, "b", &tiers[0], 0, 10_000).unwrap();
+        assert_eq!(series.keys().collect::<Vec<_>>(), vec!["cpu_usage_in_percent"]);
+    }
+
+    #[test]
+    fn should_store_host_metrics_under_host() {
+        let mut connection = setup_connection();
+        let host = HostStats {
+            cpu_pressure: Some(PressureStats {
+                some: Pressure {
+                    avg10_in_percent: 2.5,
+                    avg60_in_percent: 1.5,
+                    avg300_in_percent: 0.5,
+                    total_in_microseconds: 4_000,
+                },
+                full: None,
+            }),
+            ..Default::default()
+        };
+
+        store(&mut connection, &[setup_stats(1.0)], &host, 3_600, &setup_tiers()).unwrap();
+
+        let tiers = setup_tiers();
+        let series = query_series(&connection, HOST_SERVICE_NAME, &tiers[0], 0, 10_000).unwrap();
+        assert_eq!(series.len(), 4);
+        assert_eq!(series["host_cpu_pressure_some_avg10_in_percent"][0].value, 2.5);
+    }
+
+    #[test]
+    fn should_store_disk_usage_per_service() {
+        let mut connection = setup_connection();
+        let disk_usage = DiskUsage {
+            service_name: "b".to_string(),
+            volumes_in_bytes: Some(1_024),
+            ..Default::default()
+        };
+        let values = disk_usage.metrics().map(|(metric, value)| (disk_usage.service_name.as_str(), metric, value));
+
+        store_values(&mut connection, values, 3_600, &setup_tiers()).unwrap();
+
+        let tiers = setup_tiers();
+        let series = query_series(&connection, "b", &tiers[0], 0, 10_000).unwrap();
+        assert_eq!(series.keys().collect::<Vec<_>>(), vec!["disk_volumes_in_bytes"]);
+        assert_eq!(series["disk_volumes_in_bytes"][0].value, 1_024.0);
+    }
+
+    #[test]
+    fn should_remove_expired_samples_per_tier() {
+        let mut connection = setup_connection();
+        let tiers = setup_tiers();
+        store(&mut connection, &[setup_stats(1.0)], &HostStats::default(), 0, &tiers).unwrap();
+        store(&mut connection, &[setup_stats(2.0)], &HostStats::default(), 90_000, &tiers).unwrap();
+
+        remove_expired(&connection, 90_000, &tiers).unwrap();
+
+        assert_eq!(cpu_values(&connection, "raw", 0, 100_000), vec![(90_000, 2.0)]);
+        assert_eq!(cpu_values(&connection, "minute", 0, 100_000), vec![(0, 1.0), (90_000, 2.0)]);
+    }
+
+    #[test]
+    fn should_select_finest_tier_covering_range() {
+        let tiers = setup_tiers();
+        let now = 10_000_000;
+
+        assert_eq!(select_tier(&tiers, now - 3_600, now).unwrap().name, "raw");
+        assert_eq!(select_tier(&tiers, now - 2 * 86_400, now).unwrap().name, "minute");
+        assert_eq!(select_tier(&tiers, now - 30 * 86_400, now).unwrap().name, "hour");
+        assert_eq!(select_tier(&tiers, 0, now).unwrap().name, "hour");
+    }
+
+    #[test]
+    fn should_get_config() {
+        let actual: SqliteHistoryConfig = get_config(build_path(vec!["test-data/config/sqlite_history.config.json"]));
+
+        assert_eq!(actual.tiers, setup_tiers());
+    }
+}
@@ -0,0 +1,434 @@
+use crate::domain::{shared_services, Collection, ContainerStats, DiskUsage, HostStats};
+use crate::exporters::exporter::Exporter;
+use crate::exporters::number_format::format_value;
+use crate::util::config::{build_path, get_config, CONFIG_DIR};
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use log::error;
+use serde::Deserialize;
+use anyhow::anyhow;
+use std::collections::BTreeSet;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Deserialize, Debug, PartialEq)]
+enum StatsdFlavor {
+    STATSD,
+    DOGSTATSD,
+}
+
+#[derive(Clone, Deserialize, Debug, PartialEq)]
+struct StatsdConfig {
+    address: String,
+    flavor: StatsdFlavor,
+    #[serde(default)]
+    prefix: String,
+    device_id: String,
+    unit: String,
+    #[serde(default = "default_max_packet_size_in_bytes")]
+    max_packet_size_in_bytes: usize,
+}
+
+// Fits into an Ethernet frame without fragmentation, as recommended by StatsD.
+fn default_max_packet_size_in_bytes() -> usize {
+    1432
+}
+
+lazy_static! {
+    static ref CONFIG: StatsdConfig = get_config(build_path(vec![&CONFIG_DIR, "statsd.config.json"]));
+}
+
+pub struct StatsdExporter {
+    socket: UdpSocket,
+}
+
+impl StatsdExporter {
+    pub fn new() -> anyhow::Result<Self> {
+        Ok(StatsdExporter {
+            socket: bind_for(&CONFIG.address)?,
+        })
+    }
+
+    fn send(&self, lines: &[String], config: &StatsdConfig) {
+        for packet in pack(lines, config.max_packet_size_in_bytes) {
+            // Fire and forget; a missing agent is only noticed as send error on some platforms.
+            self.socket
+                .send_to(packet.as_bytes(), &config.address)
+                .map(|_| ())
+                .unwrap_or_else(|err| error!("Sending StatsD packet to {} failed! Because of {}", config.address, err));
+        }
+    }
+}
+
+impl Exporter for StatsdExporter {
+    fn export(&mut self, collection: &Collection) {
+        self.send(&map_to_gauges(&collection.stats, &collection.host, collection.timestamp(), &CONFIG), &CONFIG);
+    }
+
+    fn export_disk_usage(&mut self, disk_usage: &[DiskUsage]) {
+        self.send(&map_disk_usage_to_gauges(disk_usage, &CONFIG), &CONFIG);
+    }
+}
+
+/// Binds to any local address of the agent's address family, so IPv6 agents are reachable.
+fn bind_for(address: &str) -> anyhow::Result<UdpSocket> {
+    let target = address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow!("StatsD address {} resolves to no socket address", address))?;
+    let local = match target {
+        SocketAddr::V4(_) => "0.0.0.0:0",
+        SocketAddr::V6(_) => "[::]:0",
+    };
+    Ok(UdpSocket::bind(local)?)
+}
+
+/// Container a gauge belongs to; host gauges belong to none.
+struct GaugeContainer<'a> {
+    service_name: &'a str,
+    container_name: &'a str,
+    container_id_short: &'a str,
+    /// Whether its service runs several containers, which plain StatsD names then tell apart by short id.
+    shared: bool,
+}
+
+impl<'a> GaugeContainer<'a> {
+    fn of(stats: &'a ContainerStats, shared: &BTreeSet<&str>) -> Self {
+        GaugeContainer {
+            service_name: &stats.service_name,
+            container_name: &stats.container_name,
+            container_id_short: &stats.container_id_short,
+            shared: shared.contains(stats.service_name.as_str()),
+        }
+    }
+}
+
+/// One gauge line per available metric; without tags, service and interface are part of the metric name.
+/// Host metrics follow the containers' without service in their name or tags.
+fn map_to_gauges(
+    stats: &[ContainerStats],
+    host: &HostStats,
+    timestamp: DateTime<Utc>,
+    config: &StatsdConfig,
+) -> Vec<String> {
+    let host_gauges = host
+        .metrics()
+        .into_iter()
+        .filter_map(|(metric, value)| Some(format_gauge(None, metric, None, value?, timestamp, config)));
+    let shared = shared_services(stats.iter().map(|stat| stat.service_name.as_str()));
+    stats
+        .iter()
+        .flat_map(|stat| {
+            let values = stat
+                .metrics()
+                .into_iter()
+                .filter_map(move |(metric, value)| value.map(|value| (stat, None, metric, value)));
+            let interface_values = stat
+                .interface_metrics()
+                .into_iter()
+                .map(move |(interface, metric, value)| (stat, Some(interface), metric, value));
+            values.chain(interface_values)
+        })
+        .map(|(stat, interface, metric, value)| {
+            format_gauge(Some(&GaugeContainer::of(stat, &shared)), metric, interface, value, timestamp, config)
+        })
+        .chain(host_gauges)
+        .collect()
+}
+
+fn map_disk_usage_to_gauges(disk_usage: &[DiskUsage], config: &StatsdConfig) -> Vec<String> {
+    let shared = shared_services(disk_usage.iter().map(|usage| usage.service_name.as_str()));
+    disk_usage
+        .iter()
+        .flat_map(|usage| {
+            let container = GaugeContainer {
+                service_name: &usage.service_name,
+                container_name: &usage.container_name,
+                container_id_short: &usage.container_id_short,
+                shared: shared.contains(usage.service_name.as_str()),
+            };
+            usage
+                .metrics()
+                .into_iter()
+                .filter_map(move |(metric, value)| {
+                    Some(format_gauge(Some(&container), metric, None, value?, usage.timestamp, config))
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+fn format_gauge(
+    container: Option<&GaugeContainer>,
+    metric: &str,
+    interface: Option<&str>,
+    value: f64,
+    timestamp: DateTime<Utc>,
+    config: &StatsdConfig,
+) -> String {
+    match config.flavor {
+        StatsdFlavor::STATSD => format!(
+            "{}{}{}{}:{}|g",
+            config.prefix,
+            container.map(format_gauge_path).unwrap_or_default(),
+            metric,
+            interface.map(|interface| format!(".{}", sanitize(interface))).unwrap_or_default(),
+            format_value(value)
+        ),
+        StatsdFlavor::DOGSTATSD => format!(
+            "{}{}:{}|g|#{}device_id:{},unit:{}{}|T{}",
+            config.prefix,
+            metric,
+            format_value(value),
+            container
+                .map(|container| format!(
+                    "service_name:{},container_name:{},container_id:{},",
+                    sanitize_tag(container.service_name),
+                    sanitize_tag(container.container_name),
+                    sanitize_tag(container.container_id_short)
+                ))
+                .unwrap_or_default(),
+            sanitize_tag(&config.device_id),
+            sanitize_tag(&config.unit),
+            interface.map(|interface| format!(",interface:{}", sanitize_tag(interface))).unwrap_or_default(),
+            timestamp.timestamp()
+        ),
+    }
+}
+
+fn format_gauge_path(container: &GaugeContainer) -> String {
+    match container.shared {
+        true => format!("{}.{}.", sanitize(container.service_name), sanitize(container.container_id_short)),
+        false => format!("{}.", sanitize(container.service_name)),
+    }
+}
+
+/// Joins lines into newline separated packets of at most `max_packet_size` bytes; longer lines are sent alone.
+fn pack(lines: &[String], max_packet_size: usize) -> Vec<String> {
+    let mut packets: Vec<String> = vec![];
+    for line in lines {
+        match packets.last_mut() {
+            Some(packet) if packet.len() + 1 + line.len() <= max_packet_size => {
+                packet.push('\n');
+                packet.push_str(line);
+            }
+            _ => packets.push(line.clone()),
+        }
+    }
+    packets
+}
+
+// `:`, `|` and `@` separate the parts of a StatsD line.
+fn sanitize(name: &str) -> String {
+    name.replace([':', '|', '@', '\n'], "_")
+}
+
+// Tags are additionally separated by `,`.
+fn sanitize_tag(value: &str) -> String {
+    sanitize(value).replace(',', "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{NetworkInterfaceStats, Pressure, PressureStats};
+    use byte_unit::{Byte, Unit};
+    use chrono::TimeZone;
+    use std::collections::BTreeMap;
+    use rstest::rstest;
+    use std::time::Duration;
+
+    fn setup_config(flavor: StatsdFlavor) -> StatsdConfig {
+        let mut config: StatsdConfig = get_config(build_path(vec!["test-data/config/statsd.config.json"]));
+        config.flavor = flavor;
+        config
+    }
+
+    fn setup_stats() -> ContainerStats {
+        ContainerStats {
+            container_id_short: "4889ab0711ac".to_string(),
+            container_name: "b_10800414_3361262_f54e4ffc136d1344ee98993b36b9deeb".to_string(),
+            service_name: "b".to_string(),
+            cpu_usage_in_percent: Some(1.75),
+            mem_usage_in_percent: Some(31.12),
+            mem_usage: Byte::from_i64_with_unit(318, Unit::MiB),
+            ..Default::default()
+        }
+    }
+
+    fn setup_timestamp() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 3, 1, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn should_map_to_statsd_gauges() {
+        let config = setup_config(StatsdFlavor::STATSD);
+
+        let actual = map_to_gauges(&[setup_stats()], &HostStats::default(), setup_timestamp(), &config);
+
+        assert_eq!(
+            actual,
+            vec![
+                "balena.b.cpu_usage_in_percent:1.75|g",
+                "balena.b.memory_usage_in_percent:31.12|g",
+                "balena.b.memory_usage_in_bytes:333447168|g",
+            ]
+        );
+    }
+
+    #[test]
+    fn should_map_to_dogstatsd_gauges_with_tags() {
+        let config = setup_config(StatsdFlavor::DOGSTATSD);
+
+        let actual = map_to_gauges(&[setup_stats()], &HostStats::default(), setup_timestamp(), &config);
+
+        assert_eq!(
+            actual[0],
+            "balena.cpu_usage_in_percent:1.75|g|#service_name:b,\
+             container_name:b_10800414_3361262_f54e4ffc136d1344ee98993b36b9deeb,container_id:4889ab0711ac,\
+             device_id:d35a7ea843c61c723a12f19a41c26ef1,unit:my-unit|T1740830400"
+        );
+    }
+
+    #[rstest]
+    #[case::statsd(StatsdFlavor::STATSD, "balena.host_io_pressure_some_total_in_microseconds:4000|g")]
+    #[case::dogstatsd(
+        StatsdFlavor::DOGSTATSD,
+        "balena.host_io_pressure_some_total_in_microseconds:4000|g|\
+         #device_id:d35a7ea843c61c723a12f19a41c26ef1,unit:my-unit|T1740830400"
+    )]
+    fn should_map_host_metrics_to_gauges_once(#[case] flavor: StatsdFlavor, #[case] expected: &str) {
+        let host = HostStats {
+            io_pressure: Some(PressureStats {
+                some: Pressure {
+                    avg10_in_percent: 0.5,
+                    avg60_in_percent: 0.25,
+                    avg300_in_percent: 0.125,
+                    total_in_microseconds: 4_000,
+                },
+                full: None,
+            }),
+            ..Default::default()
+        };
+
+        let actual = map_to_gauges(&[setup_stats(), setup_stats()], &host, setup_timestamp(), &setup_config(flavor));
+
+        assert_eq!(actual.iter().filter(|line| **line == expected).count(), 1, "{:?}", actual);
+    }
+
+    #[rstest]
+    #[case::statsd(StatsdFlavor::STATSD, "balena.b.network_interface_input_dropped.eth0:3|g")]
+    #[case::dogstatsd(
+        StatsdFlavor::DOGSTATSD,
+        "balena.network_interface_input_dropped:3|g|#service_name:b,\
+         container_name:b_10800414_3361262_f54e4ffc136d1344ee98993b36b9deeb,container_id:4889ab0711ac,\
+         device_id:d35a7ea843c61c723a12f19a41c26ef1,unit:my-unit,interface:eth0|T1740830400"
+    )]
+    fn should_map_interface_metrics_to_gauges(#[case] flavor: StatsdFlavor, #[case] expected: &str) {
+        let stats = ContainerStats {
+            network_interfaces: BTreeMap::from([(
+                "eth0".to_string(),
+                NetworkInterfaceStats {
+                    input_dropped: 3,
+                    ..Default::default()
+                },
+            )]),
+            ..setup_stats()
+        };
+
+        let actual = map_to_gauges(&[stats], &HostStats::default(), setup_timestamp(), &setup_config(flavor));
+
+        assert!(actual.contains(&expected.to_string()), "{:?}", actual);
+    }
+
+    #[test]
+    fn should_name_statsd_gauges_by_container_of_a_service_with_several() {
+        let update = ContainerStats {
+            container_id_short: "f54e4ffc136d".to_string(),
+            ..setup_stats()
+        };
+        let config = setup_config(StatsdFlavor::STATSD);
+
+        let actual = map_to_gauges(&[setup_stats(), update], &HostStats::default(), setup_timestamp(), &config);
+
+        assert_eq!(actual[0], "balena.b.4889ab0711ac.cpu_usage_in_percent:1.75|g");
+        assert_eq!(actual[3], "balena.b.f54e4ffc136d.cpu_usage_in_percent:1.75|g");
+    }
+
+    #[rstest]
+    #[case::all_in_one(100, vec!["a:1|g\nb:2|g\nc:3|g"])]
+    #[case::exact_fit(11, vec!["a:1|g\nb:2|g", "c:3|g"])]
+    #[case::one_per_packet(10, vec!["a:1|g", "b:2|g", "c:3|g"])]
+    #[case::line_longer_than_packet(2, vec!["a:1|g", "b:2|g", "c:3|g"])]
+    fn should_pack_lines_into_packets(#[case] max_packet_size: usize, #[case] expected: Vec<&str>) {
+        let lines = vec!["a:1|g".to_string(), "b:2|g".to_string(), "c:3|g".to_string()];
+
+        let actual = pack(&lines, max_packet_size);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn should_send_datagrams() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let mut config = setup_config(StatsdFlavor::STATSD);
+        config.address = receiver.local_addr().unwrap().to_string();
+        config.max_packet_size_in_bytes = 80;
+        let exporter = StatsdExporter::new().unwrap();
+
+        let lines = map_to_gauges(&[setup_stats()], &HostStats::default(), setup_timestamp(), &config);
+        exporter.send(&lines, &config);
+
+        let mut buffer = [0; 1500];
+        let mut datagrams = vec![];
+        for _ in 0..2 {
+            let size = receiver.recv(&mut buffer).unwrap();
+            datagrams.push(String::from_utf8_lossy(&buffer[..size]).to_string());
+        }
+        assert_eq!(
+            datagrams,
+            vec![
+                "balena.b.cpu_usage_in_percent:1.75|g\nbalena.b.memory_usage_in_percent:31.12|g",
+                "balena.b.memory_usage_in_bytes:333447168|g",
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case::ipv4("127.0.0.1:8125", true)]
+    #[case::ipv6("[::1]:8125", false)]
+    fn should_bind_to_address_family_of_agent(#[case] address: &str, #[case] expected_ipv4: bool) {
+        let actual = bind_for(address).unwrap();
+
+        assert_eq!(actual.local_addr().unwrap().is_ipv4(), expected_ipv4);
+    }
+
+    #[rstest]
+    #[case::statsd(StatsdFlavor::STATSD, "balena.b.disk_volumes_in_bytes:1094713344|g")]
+    #[case::dogstatsd(
+        StatsdFlavor::DOGSTATSD,
+        "balena.disk_volumes_in_bytes:1094713344|g|#service_name:b,container_name:b_1,container_id:4889ab0711ac,\
+         device_id:d35a7ea843c61c723a12f19a41c26ef1,unit:my-unit|T1740830400"
+    )]
+    fn should_map_disk_usage_to_gauges(#[case] flavor: StatsdFlavor, #[case] expected: &str) {
+        let disk_usage = DiskUsage {
+            service_name: "b".to_string(),
+            container_name: "b_1".to_string(),
+            container_id_short: "4889ab0711ac".to_string(),
+            timestamp: setup_timestamp(),
+            volumes_in_bytes: Some(1_094_713_344),
+            ..Default::default()
+        };
+
+        let actual = map_disk_usage_to_gauges(&[disk_usage], &setup_config(flavor));
+
+        assert_eq!(actual, vec![expected.to_string()]);
+    }
+
+    #[test]
+    fn should_sanitize_separators() {
+        assert_eq!(sanitize("a:b|c@d"), "a_b_c_d");
+        assert_eq!(sanitize_tag("a,b"), "a_b");
+    }
+}
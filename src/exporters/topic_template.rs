@@ -0,0 +1,192 @@
+use anyhow::anyhow;
+use std::env;
+use std::fs;
+
+const ENV_PREFIX: &str = "env:";
+
+#[derive(Clone, Debug, PartialEq)]
+enum Segment {
+    Literal(String),
+    ServiceName,
+    ContainerName,
+    ContainerIdShort,
+    Metric,
+}
+
+/// Values known at config load, substituted once when parsing a template.
+pub(crate) struct StaticValues<'a> {
+    pub(crate) device_id: &'a str,
+    pub(crate) unit: &'a str,
+    pub(crate) hostname: &'a str,
+}
+
+/// Values substituted per published message.
+pub(crate) struct TopicValues<'a> {
+    pub(crate) service_name: &'a str,
+    pub(crate) container_name: &'a str,
+    pub(crate) container_id_short: &'a str,
+    pub(crate) metric: &'a str,
+}
+
+/// Topic with placeholders like `{service_name}` or `{env:SITE}`, validated when parsing.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct TopicTemplate {
+    segments: Vec<Segment>,
+}
+
+impl TopicTemplate {
+    pub(crate) fn parse(template: &str, static_values: &StaticValues) -> anyhow::Result<Self> {
+        let mut segments = vec![];
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .map(|end| start + end)
+                .ok_or(anyhow!("Unclosed placeholder in topic template {}", template))?;
+            push_literal(&mut segments, &rest[..start]);
+            segments.push(parse_placeholder(&rest[start + 1..end], static_values)?);
+            rest = &rest[end + 1..];
+        }
+        if rest.contains('}') {
+            return Err(anyhow!("Unopened placeholder in topic template {}", template));
+        }
+        push_literal(&mut segments, rest);
+
+        Ok(TopicTemplate { segments })
+    }
+
+    pub(crate) fn contains_metric(&self) -> bool {
+        self.segments.contains(&Segment::Metric)
+    }
+
+    pub(crate) fn contains_container(&self) -> bool {
+        self.segments
+            .iter()
+            .any(|segment| matches!(segment, Segment::ContainerName | Segment::ContainerIdShort))
+    }
+
+    /// The topic, if it has no per-message placeholders.
+    pub(crate) fn as_static(&self) -> Option<String> {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Literal(literal) => Some(literal.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub(crate) fn render(&self, values: &TopicValues) -> String {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Literal(literal) => literal.as_str(),
+                Segment::ServiceName => values.service_name,
+                Segment::ContainerName => values.container_name,
+                Segment::ContainerIdShort => values.container_id_short,
+                Segment::Metric => values.metric,
+            })
+            .collect()
+    }
+}
+
+fn push_literal(segments: &mut Vec<Segment>, literal: &str) {
+    if literal.is_empty() {
+        return;
+    }
+    match segments.last_mut() {
+        Some(Segment::Literal(previous)) => previous.push_str(literal),
+        _ => segments.push(Segment::Literal(literal.to_string())),
+    }
+}
+
+fn parse_placeholder(placeholder: &str, static_values: &StaticValues) -> anyhow::Result<Segment> {
+    let segment = match placeholder {
+        "device_id" => Segment::Literal(static_values.device_id.to_string()),
+        "unit" => Segment::Literal(static_values.unit.to_string()),
+        "hostname" => Segment::Literal(static_values.hostname.to_string()),
+        "service_name" => Segment::ServiceName,
+        "container_name" => Segment::ContainerName,
+        "container_id_short" => Segment::ContainerIdShort,
+        "metric" => Segment::Metric,
+        _ => match placeholder.strip_prefix(ENV_PREFIX) {
+            Some(name) => Segment::Literal(
+                env::var(name).map_err(|_| anyhow!("Environment variable {} of topic template is not set", name))?,
+            ),
+            None => return Err(anyhow!("Unknown placeholder {{{}}} in topic template", placeholder)),
+        },
+    };
+    Ok(segment)
+}
+
+/// Hostname from `HOSTNAME` (set by docker/balena) or `/etc/hostname`.
+pub(crate) fn read_hostname() -> String {
+    env::var("HOSTNAME")
+        .ok()
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .map(|hostname| hostname.trim().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const STATIC_VALUES: StaticValues = StaticValues {
+        device_id: "d35a7ea843c61c723a12f19a41c26ef1",
+        unit: "my-unit",
+        hostname: "my-host",
+    };
+
+    const TOPIC_VALUES: TopicValues = TopicValues {
+        service_name: "b",
+        container_name: "b_10800414_3361262_f54e4ffc136d1344ee98993b36b9deeb",
+        container_id_short: "4889ab0711ac",
+        metric: "cpu_usage_in_percent",
+    };
+
+    #[rstest]
+    #[case::legacy_root("root/{device_id}/telemetry/{unit}/{service_name}", "root/d35a7ea843c61c723a12f19a41c26ef1/telemetry/my-unit/b")]
+    #[case::full_topic("site/{hostname}/{service_name}/{container_id_short}/{metric}", "site/my-host/b/4889ab0711ac/cpu_usage_in_percent")]
+    #[case::container_name("{container_name}-{metric}", "b_10800414_3361262_f54e4ffc136d1344ee98993b36b9deeb-cpu_usage_in_percent")]
+    #[case::env("{env:CARGO_PKG_NAME}/{metric}", "balena-multi-container-telemetry/cpu_usage_in_percent")]
+    #[case::no_placeholders("static/topic", "static/topic")]
+    fn should_render_topic(#[case] template: &str, #[case] expected: &str) {
+        let actual = TopicTemplate::parse(template, &STATIC_VALUES).unwrap().render(&TOPIC_VALUES);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[rstest]
+    #[case::unknown_placeholder("root/{device}/{metric}")]
+    #[case::unset_env("root/{env:SURELY_NOT_SET_IN_ANY_TEST_ENV}/{metric}")]
+    #[case::unclosed("root/{service_name")]
+    #[case::unopened("root/service_name}")]
+    fn should_reject_invalid_template(#[case] template: &str) {
+        assert!(TopicTemplate::parse(template, &STATIC_VALUES).is_err());
+    }
+
+    #[test]
+    fn should_render_static_topic_only_without_per_message_placeholders() {
+        let static_topic = TopicTemplate::parse("root/{device_id}/{unit}/status", &STATIC_VALUES).unwrap();
+        let per_message_topic = TopicTemplate::parse("root/{service_name}/status", &STATIC_VALUES).unwrap();
+
+        assert_eq!(static_topic.as_static(), Some("root/d35a7ea843c61c723a12f19a41c26ef1/my-unit/status".to_string()));
+        assert_eq!(per_message_topic.as_static(), None);
+    }
+
+    #[test]
+    fn should_detect_metric_placeholder() {
+        assert!(TopicTemplate::parse("a/{metric}", &STATIC_VALUES).unwrap().contains_metric());
+        assert!(!TopicTemplate::parse("a/{service_name}", &STATIC_VALUES).unwrap().contains_metric());
+    }
+
+    #[rstest]
+    #[case::container_name("a/{container_name}/{metric}", true)]
+    #[case::container_id_short("a/{container_id_short}/{metric}", true)]
+    #[case::service_name("a/{service_name}/{metric}", false)]
+    fn should_detect_container_placeholders(#[case] template: &str, #[case] expected: bool) {
+        assert_eq!(TopicTemplate::parse(template, &STATIC_VALUES).unwrap().contains_container(), expected);
+    }
+}
@@ -0,0 +1,423 @@
+use crate::domain::{Collection, TopProcesses};
+use crate::exporters::exporter::Exporter;
+use crate::exporters::retry_buffer::{spawn_buffered_sender, BufferConfig};
+use crate::util::config::{build_path, get_config, CONFIG_DIR};
+use anyhow::anyhow;
+use chrono::{DateTime, SecondsFormat, Utc};
+use lazy_static::lazy_static;
+use log::error;
+use regex::{Captures, Regex};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::env;
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+const STRING_PLACEHOLDERS: [&str; 3] = ["device_id", "unit", "sent_at"];
+const JSON_PLACEHOLDERS: [&str; 3] = ["collections", "containers", "top_processes"];
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Deserialize, Debug, PartialEq)]
+enum HttpMethod {
+    POST,
+    PUT,
+    PATCH,
+}
+
+#[derive(Clone, Deserialize, Debug, PartialEq)]
+struct WebhookConfig {
+    url: String,
+    #[serde(default = "default_method")]
+    method: HttpMethod,
+    /// Header values may contain `{env:NAME}`, e.g. `"Authorization": "Bearer {env:WEBHOOK_TOKEN}"`.
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default = "default_body_template")]
+    body_template: Value,
+    device_id: String,
+    unit: String,
+    #[serde(default = "default_timeout_in_seconds")]
+    timeout_in_seconds: u64,
+    #[serde(default)]
+    buffer: BufferConfig,
+}
+
+fn default_method() -> HttpMethod {
+    HttpMethod::POST
+}
+
+fn default_body_template() -> Value {
+    json!({
+        "device_id": "{device_id}",
+        "unit": "{unit}",
+        "collections": "{collections}",
+        "top_processes": "{top_processes}",
+    })
+}
+
+fn default_timeout_in_seconds() -> u64 {
+    10
+}
+
+lazy_static! {
+    static ref CONFIG: WebhookConfig = get_config(build_path(vec![&CONFIG_DIR, "webhook.config.json"]));
+    static ref PLACEHOLDER: Regex = Regex::new(r"\{([a-z_]+)\}").unwrap();
+    static ref ENV_PLACEHOLDER: Regex = Regex::new(r"\{env:([A-Za-z_][A-Za-z0-9_]*)\}").unwrap();
+}
+
+/// One collection as rendered into `{collections}`, or top processes of `{top_processes}`.
+type Entry = Value;
+
+struct WebhookSender {
+    agent: ureq::Agent,
+    method: &'static str,
+    url: String,
+    headers: Vec<(String, String)>,
+    body_template: Value,
+    device_id: String,
+    unit: String,
+}
+
+impl WebhookSender {
+    fn new(config: &WebhookConfig) -> anyhow::Result<Self> {
+        validate_template(&config.body_template)?;
+        let headers = config
+            .headers
+            .iter()
+            .map(|(name, value)| Ok((name.clone(), resolve_env(value)?)))
+            .collect::<anyhow::Result<Vec<(String, String)>>>()?;
+        let agent = ureq::Agent::config_builder()
+            .timeout_global(Some(Duration::from_secs(config.timeout_in_seconds)))
+            .build()
+            .into();
+        Ok(WebhookSender {
+            agent,
+            method: match config.method {
+                HttpMethod::POST => "POST",
+                HttpMethod::PUT => "PUT",
+                HttpMethod::PATCH => "PATCH",
+            },
+            url: config.url.clone(),
+            headers,
+            body_template: config.body_template.clone(),
+            device_id: config.device_id.clone(),
+            unit: config.unit.clone(),
+        })
+    }
+
+    fn send(&self, entries: &[Entry]) -> anyhow::Result<()> {
+        let body = render_body(&self.body_template, &self.device_id, &self.unit, Utc::now(), entries);
+        let mut request = ureq::http::Request::builder()
+            .method(self.method)
+            .uri(&self.url)
+            .header("Content-Type", "application/json");
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        match self.agent.run(request.body(body.to_string())?) {
+            Ok(_) => Ok(()),
+            // Retrying won't help with a rejected request; drop it instead of blocking the buffer.
+            Err(ureq::Error::StatusCode(status)) if (400..500).contains(&status) && status != 408 && status != 429 => {
+                error!("Webhook rejected {} collections with status {}; dropping them.", entries.len(), status);
+                Ok(())
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+pub struct WebhookExporter {
+    sender: Sender<Entry>,
+    // Top processes are only sent if the body template has a place for them.
+    top_processes: bool,
+}
+
+impl WebhookExporter {
+    pub fn new() -> anyhow::Result<Self> {
+        let webhook_sender = WebhookSender::new(&CONFIG)?;
+        let sender = spawn_buffered_sender("Webhook export", CONFIG.buffer.clone(), move |entries: &[Entry]| {
+            webhook_sender.send(entries)
+        });
+        Ok(WebhookExporter {
+            sender,
+            top_processes: uses_placeholder(&CONFIG.body_template, "top_processes"),
+        })
+    }
+}
+
+impl Exporter for WebhookExporter {
+    fn export(&mut self, collection: &Collection) {
+        self.sender
+            .send(map_to_entry(collection))
+            .unwrap_or_else(|err| error!("Webhook export thread stopped: {}", err));
+    }
+
+    fn export_top_processes(&mut self, top_processes: &[TopProcesses]) {
+        if self.top_processes {
+            self.sender
+                .send(json!({"top_processes": top_processes}))
+                .unwrap_or_else(|err| error!("Webhook export thread stopped: {}", err));
+        }
+    }
+}
+
+fn map_to_entry(collection: &Collection) -> Entry {
+    json!({
+        "timestamp": collection.timestamp().to_rfc3339_opts(SecondsFormat::Secs, true),
+        "sequence": collection.sequence,
+        "source": collection.source,
+        "containers": collection.stats,
+        "host": collection.host,
+    })
+}
+
+fn uses_placeholder(template: &Value, name: &str) -> bool {
+    match template {
+        Value::String(string) => PLACEHOLDER.captures_iter(string).any(|captures| &captures[1] == name),
+        Value::Array(values) => values.iter().any(|value| uses_placeholder(value, name)),
+        Value::Object(fields) => fields.values().any(|value| uses_placeholder(value, name)),
+        _ => false,
+    }
+}
+
+/// Placeholders must be known; `{collections}`, `{containers}` and `{top_processes}` must make up a whole string.
+fn validate_template(template: &Value) -> anyhow::Result<()> {
+    match template {
+        Value::String(string) => {
+            for captures in PLACEHOLDER.captures_iter(string) {
+                let name = &captures[1];
+                if JSON_PLACEHOLDERS.contains(&name) {
+                    if string != &captures[0] {
+                        return Err(anyhow!("Placeholder {{{}}} must be a whole string in body template", name));
+                    }
+                } else if !STRING_PLACEHOLDERS.contains(&name) {
+                    return Err(anyhow!("Unknown placeholder {{{}}} in body template", name));
+                }
+            }
+            Ok(())
+        }
+        Value::Array(values) => values.iter().try_for_each(validate_template),
+        Value::Object(fields) => fields.values().try_for_each(validate_template),
+        _ => Ok(()),
+    }
+}
+
+fn render_body(
+    template: &Value,
+    device_id: &str,
+    unit: &str,
+    sent_at: DateTime<Utc>,
+    entries: &[Entry],
+) -> Value {
+    let collections: Vec<&Entry> = entries.iter().filter(|entry| entry.get("containers").is_some()).collect();
+    let top_processes: Vec<Value> = entries
+        .iter()
+        .flat_map(|entry| entry["top_processes"].as_array().cloned().unwrap_or_default())
+        .collect();
+    let containers: Vec<Value> = collections
+        .iter()
+        .flat_map(|entry| {
+            let timestamp = entry["timestamp"].clone();
+            entry["containers"].as_array().cloned().unwrap_or_default().into_iter().map(move |mut container| {
+                if let Value::Object(fields) = &mut container {
+                    fields.insert("timestamp".to_string(), timestamp.clone());
+                }
+                container
+            })
+        })
+        .collect();
+    let values = BTreeMap::from([
+        ("device_id", json!(device_id)),
+        ("unit", json!(unit)),
+        ("sent_at", json!(sent_at.to_rfc3339_opts(SecondsFormat::Secs, true))),
+        ("collections", json!(collections)),
+        ("containers", json!(containers)),
+        ("top_processes", json!(top_processes)),
+    ]);
+    render(template, &values)
+}
+
+fn render(template: &Value, values: &BTreeMap<&str, Value>) -> Value {
+    match template {
+        Value::String(string) => {
+            if let Some(captures) = PLACEHOLDER.captures(string).filter(|captures| &captures[0] == string) {
+                return values[&captures[1]].clone();
+            }
+            Value::String(
+                PLACEHOLDER
+                    .replace_all(string, |captures: &Captures| {
+                        values[&captures[1]].as_str().unwrap_or_default().to_string()
+                    })
+                    .to_string(),
+            )
+        }
+        Value::Array(items) => Value::Array(items.iter().map(|item| render(item, values)).collect()),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, value)| (key.clone(), render(value, values)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+fn resolve_env(value: &str) -> anyhow::Result<String> {
+    let mut missing = None;
+    let resolved = ENV_PLACEHOLDER.replace_all(value, |captures: &Captures| {
+        env::var(&captures[1]).unwrap_or_else(|_| {
+            missing = Some(captures[1].to_string());
+            String::new()
+        })
+    });
+    match missing {
+        Some(name) => Err(anyhow!("Environment variable {} of webhook header is not set", name)),
+        None => Ok(resolved.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::ContainerStats;
+    use crate::util::test_support::receive_request;
+    use chrono::TimeZone;
+    use rstest::rstest;
+    use std::net::TcpListener;
+
+    fn setup_config(url: String) -> WebhookConfig {
+        let mut config: WebhookConfig = get_config(build_path(vec!["test-data/config/webhook.config.json"]));
+        config.url = url;
+        config
+    }
+
+    fn setup_entries() -> Vec<Entry> {
+        let stats = ContainerStats {
+            service_name: "b".to_string(),
+            cpu_usage_in_percent: Some(1.75),
+            ..Default::default()
+        };
+        vec![
+            map_to_entry(&Collection::at("2025-03-01T12:00:00Z", vec![stats])),
+            map_to_entry(&Collection::at("2025-03-01T12:00:15Z", vec![])),
+        ]
+    }
+
+    #[test]
+    fn should_render_default_template() {
+        let sent_at = Utc.with_ymd_and_hms(2025, 3, 1, 12, 0, 30).unwrap();
+
+        let actual = render_body(&default_body_template(), "my-device", "my-unit", sent_at, &setup_entries());
+
+        assert_eq!(actual["device_id"], "my-device");
+        assert_eq!(actual["unit"], "my-unit");
+        let collections = actual["collections"].as_array().unwrap();
+        assert_eq!(collections.len(), 2);
+        assert_eq!(collections[0]["timestamp"], "2025-03-01T12:00:00Z");
+        assert_eq!(collections[0]["sequence"], 1);
+        assert_eq!(collections[0]["source"], "CLI");
+        assert_eq!(collections[0]["containers"][0]["service_name"], "b");
+        assert_eq!(collections[0]["containers"][0]["cpu_usage_in_percent"], 1.75);
+        assert_eq!(collections[1]["containers"], json!([]));
+        assert_eq!(collections[1]["host"], json!({"cpu_pressure": null, "memory_pressure": null, "io_pressure": null}));
+    }
+
+    #[test]
+    fn should_render_custom_template() {
+        let template = json!({
+            "source": "balena/{device_id}/{unit}",
+            "sentAt": "{sent_at}",
+            "data": {"items": "{containers}"},
+            "version": 2,
+        });
+        let sent_at = Utc.with_ymd_and_hms(2025, 3, 1, 12, 0, 30).unwrap();
+
+        let actual = render_body(&template, "my-device", "my-unit", sent_at, &setup_entries());
+
+        assert_eq!(actual["source"], "balena/my-device/my-unit");
+        assert_eq!(actual["sentAt"], "2025-03-01T12:00:30Z");
+        assert_eq!(actual["version"], 2);
+        let items = actual["data"]["items"].as_array().unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0]["timestamp"], "2025-03-01T12:00:00Z");
+        assert_eq!(items[0]["service_name"], "b");
+    }
+
+    #[test]
+    fn should_render_top_processes_apart_from_collections() {
+        let top_processes = TopProcesses {
+            service_name: "b".to_string(),
+            container_name: "b_1".to_string(),
+            container_id_short: "4889ab0711ac".to_string(),
+            timestamp: Collection::at("2025-03-01T12:00:20Z", vec![]).timestamp(),
+            by_cpu: vec![],
+            by_memory: vec![],
+        };
+        let mut entries = setup_entries();
+        entries.push(json!({"top_processes": [top_processes]}));
+        let sent_at = Utc.with_ymd_and_hms(2025, 3, 1, 12, 0, 30).unwrap();
+
+        let actual = render_body(&default_body_template(), "my-device", "my-unit", sent_at, &entries);
+
+        assert_eq!(actual["collections"].as_array().unwrap().len(), 2);
+        let top_processes = actual["top_processes"].as_array().unwrap();
+        assert_eq!(top_processes.len(), 1);
+        assert_eq!(top_processes[0]["service_name"], "b");
+        assert_eq!(top_processes[0]["timestamp"], "2025-03-01T12:00:20Z");
+    }
+
+    #[test]
+    fn should_find_placeholder_in_template() {
+        assert!(uses_placeholder(&default_body_template(), "top_processes"));
+        assert!(!uses_placeholder(&json!({"data": {"items": "{containers}"}}), "top_processes"));
+    }
+
+    #[rstest]
+    #[case::unknown(json!({"a": "{device}"}))]
+    #[case::json_in_string(json!(["containers: {containers}"]))]
+    fn should_reject_invalid_template(#[case] template: Value) {
+        assert!(validate_template(&template).is_err());
+    }
+
+    #[test]
+    fn should_resolve_env_in_headers() {
+        assert_eq!(
+            resolve_env("Bearer {env:CARGO_PKG_NAME}").unwrap(),
+            "Bearer balena-multi-container-telemetry"
+        );
+        assert!(resolve_env("Bearer {env:SURELY_NOT_SET_IN_ANY_TEST_ENV}").is_err());
+    }
+
+    #[test]
+    fn should_send_rendered_body_with_headers() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let config = setup_config(format!("http://{}/ingest", listener.local_addr().unwrap()));
+        let receiver = receive_request(listener, "204 No Content", vec![]);
+
+        WebhookSender::new(&config).unwrap().send(&setup_entries()).unwrap();
+
+        let received = receiver.join().unwrap();
+        assert_eq!(received.head.start_line(), "PUT /ingest HTTP/1.1");
+        assert_eq!(received.head.header("content-type"), Some("application/json"));
+        assert_eq!(received.head.header("authorization"), Some("Bearer balena-multi-container-telemetry"));
+        let body: Value = serde_json::from_slice(&received.body).unwrap();
+        assert_eq!(body["device"], "d35a7ea843c61c723a12f19a41c26ef1");
+        assert_eq!(body["collections"].as_array().unwrap().len(), 2);
+    }
+
+    #[rstest]
+    #[case::rejected("400 Bad Request", true)]
+    #[case::rate_limited("429 Too Many Requests", false)]
+    #[case::unavailable("503 Service Unavailable", false)]
+    fn should_retry_transient_errors_only(#[case] status: &'static str, #[case] expected_ok: bool) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let config = setup_config(format!("http://{}/ingest", listener.local_addr().unwrap()));
+        let receiver = receive_request(listener, status, vec![]);
+
+        let actual = WebhookSender::new(&config).unwrap().send(&setup_entries());
+
+        receiver.join().unwrap();
+        assert_eq!(actual.is_ok(), expected_ok);
+    }
+}
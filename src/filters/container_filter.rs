@@ -0,0 +1,219 @@
+use crate::domain::ContainerStats;
+use crate::util::config::{build_path, get_config, CONFIG_DIR};
+use anyhow::anyhow;
+use glob::Pattern;
+use log::info;
+use regex::Regex;
+use serde::Deserialize;
+
+const REGEX_PREFIX: &str = "regex:";
+
+#[derive(Clone, Deserialize, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct ContainerFilterConfig {
+    pub include_service_names: Vec<String>,
+    pub exclude_service_names: Vec<String>,
+    /// Globs like `b_*`, or regexes when prefixed with `regex:`.
+    pub include_container_name_patterns: Vec<String>,
+    pub exclude_container_name_patterns: Vec<String>,
+    /// `key` to match on presence or `key=value` where value may be a glob.
+    pub include_labels: Vec<String>,
+    pub exclude_labels: Vec<String>,
+}
+
+#[derive(Debug)]
+enum NamePattern {
+    Glob(Pattern),
+    Regex(Regex),
+}
+
+#[derive(Debug)]
+struct LabelRule {
+    key: String,
+    value: Option<Pattern>,
+}
+
+#[derive(Debug, Default)]
+struct Rules {
+    service_names: Vec<String>,
+    container_name_patterns: Vec<NamePattern>,
+    labels: Vec<LabelRule>,
+}
+
+/// Keeps containers matching any include rule, if there are any, and no exclude rule.
+#[derive(Debug, Default)]
+pub struct ContainerFilter {
+    include: Rules,
+    exclude: Rules,
+}
+
+pub fn get_container_filter_config() -> ContainerFilterConfig {
+    get_config(build_path(vec![&CONFIG_DIR, "container_filter.config.json"]))
+}
+
+impl ContainerFilter {
+    pub fn new(config: &ContainerFilterConfig) -> anyhow::Result<Self> {
+        Ok(ContainerFilter {
+            include: Rules::new(
+                &config.include_service_names,
+                &config.include_container_name_patterns,
+                &config.include_labels,
+            )?,
+            exclude: Rules::new(
+                &config.exclude_service_names,
+                &config.exclude_container_name_patterns,
+                &config.exclude_labels,
+            )?,
+        })
+    }
+
+    pub fn has_label_rules(&self) -> bool {
+        !self.include.labels.is_empty() || !self.exclude.labels.is_empty()
+    }
+
+    pub fn apply(&self, stats: Vec<ContainerStats>) -> Vec<ContainerStats> {
+        let total = stats.len();
+        let kept: Vec<ContainerStats> = stats.into_iter().filter(|stat| self.keeps(stat)).collect();
+        info!("Filtered out {} of {} containers.", total - kept.len(), total);
+        kept
+    }
+
+    fn keeps(&self, stats: &ContainerStats) -> bool {
+        (self.include.is_empty() || self.include.matches(stats)) && !self.exclude.matches(stats)
+    }
+}
+
+impl Rules {
+    fn new(service_names: &[String], container_name_patterns: &[String], labels: &[String]) -> anyhow::Result<Self> {
+        Ok(Rules {
+            service_names: service_names.to_vec(),
+            container_name_patterns: container_name_patterns
+                .iter()
+                .map(|pattern| parse_name_pattern(pattern))
+                .collect::<anyhow::Result<_>>()?,
+            labels: labels
+                .iter()
+                .map(|rule| parse_label_rule(rule))
+                .collect::<anyhow::Result<_>>()?,
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.service_names.is_empty() && self.container_name_patterns.is_empty() && self.labels.is_empty()
+    }
+
+    fn matches(&self, stats: &ContainerStats) -> bool {
+        self.service_names.contains(&stats.service_name)
+            || self
+                .container_name_patterns
+                .iter()
+                .any(|pattern| match pattern {
+                    NamePattern::Glob(glob) => glob.matches(&stats.container_name),
+                    NamePattern::Regex(regex) => regex.is_match(&stats.container_name),
+                })
+            || self.labels.iter().any(|rule| {
+                stats.labels.get(&rule.key).is_some_and(|value| {
+                    rule.value.as_ref().is_none_or(|pattern| pattern.matches(value))
+                })
+            })
+    }
+}
+
+fn parse_name_pattern(pattern: &str) -> anyhow::Result<NamePattern> {
+    match pattern.strip_prefix(REGEX_PREFIX) {
+        Some(regex) => Regex::new(regex)
+            .map(NamePattern::Regex)
+            .map_err(|err| anyhow!("Invalid container name regex {}: {}", regex, err)),
+        None => Pattern::new(pattern)
+            .map(NamePattern::Glob)
+            .map_err(|err| anyhow!("Invalid container name glob {}: {}", pattern, err)),
+    }
+}
+
+fn parse_label_rule(rule: &str) -> anyhow::Result<LabelRule> {
+    let (key, value) = match rule.split_once('=') {
+        Some((key, value)) => (key, Some(value)),
+        None => (rule, None),
+    };
+    Ok(LabelRule {
+        key: key.to_string(),
+        value: value
+            .map(Pattern::new)
+            .transpose()
+            .map_err(|err| anyhow!("Invalid label value glob in {}: {}", rule, err))?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::collections::BTreeMap;
+
+    fn setup_stats(container_name: &str, labels: &[(&str, &str)]) -> ContainerStats {
+        ContainerStats {
+            container_name: container_name.to_string(),
+            service_name: container_name.split('_').next().unwrap().to_string(),
+            labels: labels
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect::<BTreeMap<String, String>>(),
+            ..Default::default()
+        }
+    }
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn should_keep_everything_without_rules() {
+        let filter = ContainerFilter::new(&ContainerFilterConfig::default()).unwrap();
+
+        let actual = filter.apply(vec![setup_stats("a_1_2_3", &[]), setup_stats("balena_supervisor", &[])]);
+
+        assert_eq!(actual.len(), 2);
+    }
+
+    #[rstest]
+    #[case::exclude_service(ContainerFilterConfig { exclude_service_names: strings(&["telemetry"]), ..Default::default() }, vec!["a_1_2_3", "b_1_2_3", "balena_supervisor"])]
+    #[case::include_service(ContainerFilterConfig { include_service_names: strings(&["a", "b"]), ..Default::default() }, vec!["a_1_2_3", "b_1_2_3"])]
+    #[case::exclude_glob(ContainerFilterConfig { exclude_container_name_patterns: strings(&["balena_*", "t*"]), ..Default::default() }, vec!["a_1_2_3", "b_1_2_3"])]
+    #[case::include_regex(ContainerFilterConfig { include_container_name_patterns: strings(&["regex:^[ab]_\\d+"]), ..Default::default() }, vec!["a_1_2_3", "b_1_2_3"])]
+    #[case::include_and_exclude(ContainerFilterConfig { include_container_name_patterns: strings(&["*_1_2_3"]), exclude_service_names: strings(&["b"]), ..Default::default() }, vec!["a_1_2_3", "telemetry_1_2_3"])]
+    #[case::include_label_key(ContainerFilterConfig { include_labels: strings(&["io.balena.features.supervisor-api"]), ..Default::default() }, vec!["balena_supervisor"])]
+    #[case::exclude_label_value(ContainerFilterConfig { exclude_labels: strings(&["telemetry=disabled*"]), ..Default::default() }, vec!["a_1_2_3", "telemetry_1_2_3", "balena_supervisor"])]
+    fn should_filter_containers(#[case] config: ContainerFilterConfig, #[case] expected: Vec<&str>) {
+        let filter = ContainerFilter::new(&config).unwrap();
+        let input = vec![
+            setup_stats("a_1_2_3", &[]),
+            setup_stats("b_1_2_3", &[("telemetry", "disabled-for-now")]),
+            setup_stats("telemetry_1_2_3", &[("telemetry", "enabled")]),
+            setup_stats("balena_supervisor", &[("io.balena.features.supervisor-api", "1")]),
+        ];
+
+        let actual: Vec<String> = filter
+            .apply(input)
+            .into_iter()
+            .map(|stats| stats.container_name)
+            .collect();
+
+        assert_eq!(actual, expected);
+    }
+
+    #[rstest]
+    #[case::invalid_regex(ContainerFilterConfig { include_container_name_patterns: strings(&["regex:("]), ..Default::default() })]
+    #[case::invalid_glob(ContainerFilterConfig { exclude_container_name_patterns: strings(&["a[*"]), ..Default::default() })]
+    #[case::invalid_label_glob(ContainerFilterConfig { exclude_labels: strings(&["key=[*"]), ..Default::default() })]
+    fn should_reject_invalid_patterns(#[case] config: ContainerFilterConfig) {
+        assert!(ContainerFilter::new(&config).is_err());
+    }
+
+    #[test]
+    fn should_get_config() {
+        let actual: ContainerFilterConfig = get_config(build_path(vec!["test-data/config/container_filter.config.json"]));
+
+        assert_eq!(actual.exclude_service_names, strings(&["balena-multi-container-telemetry"]));
+        assert!(actual.include_labels.is_empty());
+    }
+}
@@ -4,39 +4,159 @@ use crate::collectors::balena_stats_collector_config::{
     get_collector_config, BalenaStatsCollectorConfig, CollectorType,
 };
 use crate::collectors::balena_stats_file_collector::BalenaStatsFileCollector;
-use crate::exporters::mqtt::export;
+use crate::collectors::balena_stats_socket_collector::BalenaStatsSocketCollector;
+use crate::collectors::container_inspector::ContainerInspector;
+use crate::collectors::disk_usage::DiskUsageCollector;
+use crate::collectors::stats_enricher::StatsEnricher;
+use crate::collectors::top_processes::TopProcessesCollector;
+use crate::domain::Collection;
+use crate::exporters::csv_archive::CsvArchiveExporter;
+use crate::exporters::exporter::Exporter;
+use crate::exporters::exporter_config::{get_exporters_config, ExporterType};
+use crate::exporters::json_lines::JsonLinesExporter;
+use crate::exporters::mqtt::MqttExporter;
+use crate::exporters::otlp::OtlpExporter;
+use crate::exporters::sqlite_history::SqliteHistoryExporter;
+use crate::exporters::sqlite_history;
+use crate::exporters::statsd::StatsdExporter;
+use crate::exporters::webhook::WebhookExporter;
+use crate::filters::container_filter::{get_container_filter_config, ContainerFilter};
 use crate::util::config::{build_path, verify_path_or_copy_default_into_path, CONFIG_DIR};
+use chrono::Utc;
 use lazy_static::lazy_static;
 use log::{error, info, warn};
-use log4rs;
+use std::env;
+use std::process::exit;
 use tokio::time::{self, Duration};
 
 mod collectors;
+mod commands;
 mod domain;
 mod exporters;
+mod filters;
 mod parsers;
+mod status_endpoint;
 mod util;
 
 lazy_static! {
     pub static ref COLLECTOR_CONFIG: BalenaStatsCollectorConfig = get_collector_config();
 }
 
-async fn tick() {
-    info!("Starting tick.");
+fn build_exporters() -> Vec<Box<dyn Exporter>> {
+    get_exporters_config()
+        .exporters
+        .into_iter()
+        .map(|exporter_type| -> Box<dyn Exporter> {
+            match exporter_type {
+                ExporterType::MQTT => Box::new(
+                    MqttExporter::new(COLLECTOR_CONFIG.collection_interval_in_seconds)
+                        .expect("Invalid MQTT config"),
+                ),
+                ExporterType::JsonLines => Box::new(JsonLinesExporter),
+                ExporterType::CsvArchive => Box::new(CsvArchiveExporter),
+                ExporterType::SqliteHistory => {
+                    if let Some(address) = sqlite_history::CONFIG.status_endpoint_address.clone() {
+                        tokio::spawn(status_endpoint::serve(address, sqlite_history::CONFIG.clone()));
+                    }
+                    Box::new(SqliteHistoryExporter::new().expect("Could not open history database"))
+                }
+                ExporterType::OTLP => Box::new(OtlpExporter::new().expect("Invalid OTLP config")),
+                ExporterType::STATSD => Box::new(StatsdExporter::new().expect("Could not open StatsD socket")),
+                ExporterType::WEBHOOK => Box::new(WebhookExporter::new().expect("Invalid webhook config")),
+            }
+        })
+        .collect()
+}
 
-    let collector: Box<dyn BalenaStatsCollector> = match (COLLECTOR_CONFIG).mode {
+// Built once, as collectors keep state between ticks.
+fn build_collector() -> Box<dyn BalenaStatsCollector> {
+    match (COLLECTOR_CONFIG).mode {
         CollectorType::CLI => Box::new(BalenaStatsCliCollector),
-        CollectorType::FILE => Box::new(BalenaStatsFileCollector),
-    };
+        CollectorType::FILE => Box::new(BalenaStatsFileCollector::new(&COLLECTOR_CONFIG)),
+        CollectorType::SOCKET => Box::new(BalenaStatsSocketCollector::new(&COLLECTOR_CONFIG)),
+    }
+}
+
+// Collectors on their own, slower intervals, whose results are exported separately from the collections.
+struct SlowCollectors {
+    top_processes: Option<TopProcessesCollector>,
+    disk_usage: Option<DiskUsageCollector>,
+}
+
+async fn tick(
+    collector: &mut dyn BalenaStatsCollector,
+    enricher: &mut StatsEnricher,
+    slow_collectors: &mut SlowCollectors,
+    container_filter: &ContainerFilter,
+    exporters: &mut [Box<dyn Exporter>],
+    source_stale: &mut Option<bool>,
+    sequence: &mut u64,
+) {
+    info!("Starting tick.");
 
+    let started_at = Utc::now();
+    *sequence += 1;
     match collector.collect() {
-        Ok(collection) => {
+        Ok(Some(stats)) => {
             info!("Successfully collected stats.");
-            export(collection)
+            let mut stats = container_filter.apply(stats);
+            enricher.enrich(&mut stats);
+            let ended_at = Utc::now();
+            let collection = Collection {
+                sequence: *sequence,
+                source: collector.source(),
+                started_at,
+                ended_at,
+                timestamp: collector.stats_timestamp().unwrap_or(ended_at),
+                stats,
+                host: enricher.read_host(),
+            };
+            info!(
+                "Collection {} took {} ms.",
+                collection.sequence,
+                collection.duration().num_milliseconds()
+            );
+            exporters
+                .iter_mut()
+                .for_each(|exporter| exporter.export(&collection));
+            if let Some(top_processes) = slow_collectors
+                .top_processes
+                .as_mut()
+                .and_then(|collector| collector.collect(&collection.stats, collection.timestamp()))
+            {
+                exporters
+                    .iter_mut()
+                    .for_each(|exporter| exporter.export_top_processes(&top_processes));
+            }
+            if let Some(disk_usage) = slow_collectors
+                .disk_usage
+                .as_ref()
+                .and_then(|collector| collector.collect(&collection.stats))
+            {
+                exporters
+                    .iter_mut()
+                    .for_each(|exporter| exporter.export_disk_usage(&disk_usage));
+            }
         }
+        Ok(None) => info!("No new stats available."),
         Err(err) => error!("Could not collect stats!: {}", err),
     };
 
+    // Exporters are only notified of changes.
+    if let Some(status) = collector.source_status()
+        && *source_stale != Some(status.stale)
+    {
+        match status.last_update {
+            _ if !status.stale => info!("Stats source is current."),
+            Some(last_update) => warn!("Stats source is stale; last updated at {}.", last_update),
+            None => warn!("Stats source is stale; no update seen yet."),
+        }
+        *source_stale = Some(status.stale);
+        exporters
+            .iter_mut()
+            .for_each(|exporter| exporter.export_source_status(&status));
+    }
+
     info!("Ending tick.");
 }
 
@@ -48,12 +168,52 @@ async fn main() {
     log4rs::init_file(verified_path, Default::default()).unwrap();
     warn!("Logging < warn to file only; please see log directory.");
 
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some(commands::EXPORT_ARCHIVE) {
+        if let Err(err) = commands::export_archive(&args[1..]) {
+            error!("Exporting archive failed: {}", err);
+            eprintln!("Exporting archive failed: {}", err);
+            exit(1);
+        }
+        return;
+    }
+
+    let container_filter =
+        ContainerFilter::new(&get_container_filter_config()).expect("Invalid container filter config");
+    if container_filter.has_label_rules() {
+        warn!("Container label filter rules only match containers whose labels are known to the collector.");
+    }
+    let mut collector = build_collector();
+    let mut enricher = StatsEnricher::new(&COLLECTOR_CONFIG.proc_path, &COLLECTOR_CONFIG.cgroup_path);
+    if let Some(inspect_mode) = COLLECTOR_CONFIG.inspect_mode {
+        enricher = enricher.with_inspector(ContainerInspector::new(inspect_mode, &COLLECTOR_CONFIG));
+    }
+    let mut slow_collectors = SlowCollectors {
+        top_processes: COLLECTOR_CONFIG.top_processes.clone().map(|config| {
+            TopProcessesCollector::new(config, &COLLECTOR_CONFIG.proc_path, &COLLECTOR_CONFIG.cgroup_path)
+        }),
+        disk_usage: COLLECTOR_CONFIG.disk_usage.as_ref().map(|config| {
+            DiskUsageCollector::start(&COLLECTOR_CONFIG.socket_path, Duration::from_secs(config.interval_in_seconds))
+        }),
+    };
+    let mut exporters = build_exporters();
+    let mut source_stale = None;
+    let mut sequence = 0;
     let mut interval = time::interval(Duration::from_secs(
         COLLECTOR_CONFIG.collection_interval_in_seconds,
     ));
 
     loop {
         interval.tick().await;
-        tick().await;
+        tick(
+            collector.as_mut(),
+            &mut enricher,
+            &mut slow_collectors,
+            &container_filter,
+            &mut exporters,
+            &mut source_stale,
+            &mut sequence,
+        )
+        .await;
     }
 }
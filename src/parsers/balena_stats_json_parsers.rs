@@ -25,21 +25,29 @@ struct ContainerStatsAsStrings {
     pids: String,
 }
 
-pub fn parse(json_str: &str) -> Vec<ContainerStats> {
-    let parsed = parse_raw(json_str).expect(&format!("Cannot parse raw json: {}", json_str));
+pub fn parse(json_str: &str) -> anyhow::Result<Vec<ContainerStats>> {
+    let parsed = parse_raw(json_str)?;
     let mapped: Vec<ContainerStats> = parsed
         .into_iter()
-        .map(|string_stats| map(string_stats))
+        .map(map)
         .filter_map(|parsed| parsed.ok())
         .collect();
 
-    mapped
+    Ok(mapped)
 }
 
+// Rows which don't deserialize, e.g. objects lacking a column, are skipped.
 fn parse_raw(json_str: &str) -> Result<Vec<ContainerStatsAsStrings>, serde_json::Error> {
-    let stats: Result<Vec<ContainerStatsAsStrings>, serde_json::Error> =
-        serde_json::from_str(json_str);
-    stats
+    let rows: Vec<serde_json::Value> = serde_json::from_str(json_str)?;
+    let stats = rows
+        .into_iter()
+        .filter_map(|row| {
+            serde_json::from_value(row)
+                .map_err(|err| warn!("Skipping stats row which cannot be parsed: {}", err))
+                .ok()
+        })
+        .collect();
+    Ok(stats)
 }
 
 fn map(stat_strings: ContainerStatsAsStrings) -> anyhow::Result<ContainerStats> {
@@ -64,12 +72,13 @@ fn map(stat_strings: ContainerStatsAsStrings) -> anyhow::Result<ContainerStats>
         mem_limit: mem_limit_in_bytes.ok(),
         block_device_input: block_device_input_in_bytes.ok(),
         block_device_output: block_device_output_in_bytes.ok(),
+        ..Default::default()
     };
 
     Ok(stats)
 }
 
-fn parse_or_log<T: FromStr>(input: &str) -> Option<T>
+pub(crate) fn parse_or_log<T: FromStr>(input: &str) -> Option<T>
 where
     <T as FromStr>::Err: std::fmt::Display,
 {
@@ -86,7 +95,7 @@ where
 }
 
 // see https://doc.rust-lang.org/book/ch04-03-slices.html#string-slices-as-parameters
-fn parse_bytes_from_two_values(input: &str) -> [anyhow::Result<Byte>; 2] {
+pub(crate) fn parse_bytes_from_two_values(input: &str) -> [anyhow::Result<Byte>; 2] {
     let parsed_bytes = input
         .split("/")
         .map(|string| (string, parse_byte_from_str(string)))
@@ -104,8 +113,8 @@ fn parse_bytes_from_two_values(input: &str) -> [anyhow::Result<Byte>; 2] {
 
     match parsed_bytes.as_slice() {
         [first, second] => [
-            first.clone().map_err(|e| anyhow::Error::new(e)),
-            second.clone().map_err(|e| anyhow::Error::new(e)),
+            first.clone().map_err(anyhow::Error::new),
+            second.clone().map_err(anyhow::Error::new),
         ],
         _ => {
             let error_msg = format!("Not exactly two Bytes found in string: {}", input);
@@ -118,15 +127,13 @@ fn parse_byte_from_str(input: &str) -> Result<Byte, ParseError> {
     Byte::parse_str(input.trim(), true)
 }
 
-fn parse_service_name(container_name: &str) -> String {
+pub(crate) fn parse_service_name(container_name: &str) -> String {
     let mut parts: Vec<&str> = container_name.split("_").collect();
     if parts.len() > 2 {
         parts.truncate(parts.len() - 3);
     }
 
-    let service_name = parts.join("_");
-
-    service_name
+    parts.join("_")
 }
 
 #[cfg(test)]
@@ -162,6 +169,19 @@ mod tests {
         assert_eq!(first, &first_expected);
     }
 
+    #[test]
+    fn it_skips_rows_which_cannot_be_parsed() {
+        let test_json = r#"[{"Name": "a"}, 42, {"BlockIO": "0B / 0B", "CPUPerc": "1.75%", "Container": "4889ab0711ac",
+            "ID": "4889ab0711ac17500a5de1c3d50eb6bfbc7eb367d349797f9fe8dcb92fd9e914", "MemPerc": "31.12%",
+            "MemUsage": "318.6MiB / 1GiB", "Name": "b_10800414_3361262_f54e4ffc136d1344ee98993b36b9deeb",
+            "NetIO": "541MB / 680MB", "PIDs": "26"}]"#;
+
+        let actual = parse_raw(test_json).unwrap();
+
+        assert_eq!(actual, vec![setup_test_data()]);
+        assert!(parse("no json").is_err());
+    }
+
     #[test]
     fn it_maps_from_string_to_stats() {
         let input = setup_test_data();
@@ -181,6 +201,7 @@ mod tests {
             block_device_input: Byte::from_i64_with_unit(0, Unit::B),
             block_device_output: Byte::from_i64_with_unit(0, Unit::B),
             amount_of_pids: Some(26),
+            ..Default::default()
         };
 
         let actual = map(input);
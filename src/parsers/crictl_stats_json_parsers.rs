@@ -0,0 +1,163 @@
+use crate::domain::{BytePrecision, ContainerStats};
+use byte_unit::Byte;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+const KUBERNETES_CONTAINER_NAME_LABEL: &str = "io.kubernetes.container.name";
+
+/// Output of `crictl stats -o json`; only the fields mapped into `ContainerStats`.
+#[derive(Deserialize, Debug)]
+struct CrictlStatsOutput {
+    #[serde(default)]
+    stats: Vec<CrictlContainerStats>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CrictlContainerStats {
+    attributes: Attributes,
+    cpu: Option<CpuUsage>,
+    memory: Option<MemoryUsage>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Attributes {
+    id: String,
+    metadata: Metadata,
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Metadata {
+    name: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct CpuUsage {
+    usage_nano_cores: Option<UInt64Value>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct MemoryUsage {
+    working_set_bytes: Option<UInt64Value>,
+    available_bytes: Option<UInt64Value>,
+}
+
+#[derive(Deserialize, Debug)]
+struct UInt64Value {
+    value: JsonUInt64,
+}
+
+// The protobuf JSON mapping writes 64 bit integers as strings, though some crictl versions write numbers.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum JsonUInt64 {
+    Number(u64),
+    String(String),
+}
+
+impl UInt64Value {
+    fn get(&self) -> Option<u64> {
+        match &self.value {
+            JsonUInt64::Number(value) => Some(*value),
+            JsonUInt64::String(value) => value.parse().ok(),
+        }
+    }
+}
+
+pub fn parse(json_str: &str) -> anyhow::Result<Vec<ContainerStats>> {
+    let parsed: CrictlStatsOutput = serde_json::from_str(json_str)?;
+    Ok(parsed.stats.into_iter().map(map).collect())
+}
+
+/// CPU is relative to one core like `docker stats`; the memory limit is derived from the available bytes.
+fn map(stats: CrictlContainerStats) -> ContainerStats {
+    let cpu_usage_in_percent = stats
+        .cpu
+        .and_then(|cpu| cpu.usage_nano_cores)
+        .and_then(|value| value.get())
+        .map(|nano_cores| (nano_cores as f64 / 10_000_000.0) as f32);
+    let working_set = stats
+        .memory
+        .as_ref()
+        .and_then(|memory| memory.working_set_bytes.as_ref())
+        .and_then(UInt64Value::get);
+    let available = stats
+        .memory
+        .as_ref()
+        .and_then(|memory| memory.available_bytes.as_ref())
+        .and_then(UInt64Value::get);
+    let mem_limit = working_set.zip(available).map(|(working_set, available)| working_set + available);
+    let mem_usage_in_percent = working_set
+        .zip(mem_limit)
+        .filter(|(_, limit)| *limit > 0)
+        .map(|(working_set, limit)| (working_set as f64 / limit as f64 * 100.0) as f32);
+
+    let attributes = stats.attributes;
+    let service_name = attributes
+        .labels
+        .get(KUBERNETES_CONTAINER_NAME_LABEL)
+        .cloned()
+        .unwrap_or_else(|| attributes.metadata.name.clone());
+    ContainerStats {
+        container_id_short: attributes.id.chars().take(12).collect(),
+        container_id: attributes.id,
+        container_name: attributes.metadata.name,
+        service_name,
+        cpu_usage_in_percent,
+        mem_usage_in_percent,
+        mem_usage: working_set.map(Byte::from_u64),
+        mem_limit: mem_limit.map(Byte::from_u64),
+        labels: attributes.labels,
+        byte_precision: BytePrecision::EXACT,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_crictl_stats() {
+        let json_str = include_str!("../../test-data/crictl_stats.json");
+
+        let actual = parse(json_str).unwrap();
+
+        assert_eq!(actual.len(), 2);
+        let first = &actual[0];
+        assert_eq!(first.container_id, "4889ab0711ac17500a5de1c3d50eb6bfbc7eb367d349797f9fe8dcb92fd9e914");
+        assert_eq!(first.container_id_short, "4889ab0711ac");
+        assert_eq!(first.container_name, "nginx");
+        assert_eq!(first.service_name, "nginx");
+        assert_eq!(first.cpu_usage_in_percent, Some(1.75));
+        assert_eq!(first.mem_usage, Some(Byte::from_u64(268_435_456)));
+        assert_eq!(first.mem_limit, Some(Byte::from_u64(1_073_741_824)));
+        assert_eq!(first.mem_usage_in_percent, Some(25.0));
+        assert_eq!(first.byte_precision, BytePrecision::EXACT);
+        assert_eq!(first.network_input, None);
+        assert_eq!(first.amount_of_pids, None);
+        assert_eq!(first.labels["io.kubernetes.pod.namespace"], "default");
+    }
+
+    #[test]
+    fn should_parse_numeric_values_and_missing_fields() {
+        let json_str = include_str!("../../test-data/crictl_stats.json");
+
+        let actual = parse(json_str).unwrap();
+
+        let second = &actual[1];
+        assert_eq!(second.service_name, "coredns");
+        assert_eq!(second.cpu_usage_in_percent, None);
+        assert_eq!(second.mem_usage, Some(Byte::from_u64(12_582_912)));
+        assert_eq!(second.mem_limit, None);
+        assert_eq!(second.mem_usage_in_percent, None);
+    }
+
+    #[test]
+    fn should_parse_empty_output() {
+        assert_eq!(parse("{}").unwrap(), vec![]);
+    }
+}
@@ -0,0 +1,123 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// `GET /system/df`; only the fields of containers and volumes.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct DfResponse {
+    containers: Option<Vec<DfContainer>>,
+    volumes: Option<Vec<DfVolume>>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct DfContainer {
+    id: String,
+    size_rw: Option<i64>,
+    #[serde(default)]
+    mounts: Vec<DfMount>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct DfMount {
+    #[serde(rename = "Type")]
+    mount_type: String,
+    name: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct DfVolume {
+    name: String,
+    usage_data: Option<DfUsageData>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct DfUsageData {
+    size: i64,
+}
+
+/// Disk usage of a container; `None` where the engine did not calculate a size.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContainerDiskUsage {
+    pub id: String,
+    /// Files written by the container outside of volumes.
+    pub writable_layer_in_bytes: Option<u64>,
+    /// Sum of the named volumes the container mounts; volumes shared by containers count for each of them.
+    pub volumes_in_bytes: Option<u64>,
+}
+
+pub fn parse_disk_usage(json_str: &str) -> anyhow::Result<Vec<ContainerDiskUsage>> {
+    let df: DfResponse = serde_json::from_str(json_str)?;
+    // The engine reports -1 for sizes it did not calculate.
+    let volume_sizes: BTreeMap<String, Option<u64>> = df
+        .volumes
+        .unwrap_or_default()
+        .into_iter()
+        .map(|volume| {
+            let size = volume.usage_data.and_then(|usage| u64::try_from(usage.size).ok());
+            (volume.name, size)
+        })
+        .collect();
+
+    Ok(df
+        .containers
+        .unwrap_or_default()
+        .into_iter()
+        .map(|container| {
+            let volume_sizes: Vec<Option<u64>> = container
+                .mounts
+                .iter()
+                .filter(|mount| mount.mount_type == "volume")
+                .filter_map(|mount| mount.name.as_ref())
+                .map(|name| volume_sizes.get(name).copied().flatten())
+                .collect();
+            ContainerDiskUsage {
+                id: container.id,
+                writable_layer_in_bytes: container.size_rw.and_then(|size| u64::try_from(size).ok()),
+                volumes_in_bytes: volume_sizes.into_iter().sum(),
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_disk_usage_per_container() {
+        let actual = parse_disk_usage(include_str!("../../test-data/docker_api_system_df.json")).unwrap();
+
+        assert_eq!(
+            actual,
+            vec![
+                ContainerDiskUsage {
+                    id: "4889ab0711ac17500a5de1c3d50eb6bfbc7eb367d349797f9fe8dcb92fd9e914".to_string(),
+                    writable_layer_in_bytes: Some(52_428_800),
+                    volumes_in_bytes: Some(1_094_713_344),
+                },
+                ContainerDiskUsage {
+                    id: "c6a10bcb79ab2c3f8e9d0a1b2c3d4e5f60718293a4b5c6d7e8f9a0b1c2d3e4f5".to_string(),
+                    writable_layer_in_bytes: Some(4096),
+                    volumes_in_bytes: Some(0),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn should_leave_uncalculated_volume_sizes_unavailable() {
+        let json = r#"{
+            "Containers": [{"Id": "a", "SizeRw": -1, "Mounts": [{"Type": "volume", "Name": "unused"}]}],
+            "Volumes": [{"Name": "unused", "UsageData": {"Size": -1, "RefCount": 0}}]
+        }"#;
+
+        let actual = parse_disk_usage(json).unwrap();
+
+        assert_eq!(actual[0].writable_layer_in_bytes, None);
+        assert_eq!(actual[0].volumes_in_bytes, None);
+    }
+}
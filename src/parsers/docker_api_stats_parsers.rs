@@ -0,0 +1,249 @@
+use crate::domain::{BytePrecision, ContainerStats, NetworkInterfaceStats};
+use crate::parsers::balena_stats_json_parsers::parse_service_name;
+use byte_unit::Byte;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+// Set by the balena supervisor and docker compose.
+const SERVICE_NAME_LABELS: [&str; 2] = ["io.balena.service-name", "com.docker.compose.service"];
+
+/// Entry of `GET /containers/json`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContainerSummary {
+    pub id: String,
+    #[serde(default)]
+    names: Vec<String>,
+    labels: Option<BTreeMap<String, String>>,
+}
+
+/// `GET /containers/{id}/stats?stream=false`; only the fields mapped into `ContainerStats`.
+#[derive(Deserialize, Debug)]
+struct StatsResponse {
+    #[serde(default)]
+    cpu_stats: CpuStats,
+    #[serde(default)]
+    precpu_stats: CpuStats,
+    #[serde(default)]
+    memory_stats: MemoryStats,
+    networks: Option<BTreeMap<String, NetworkStats>>,
+    blkio_stats: Option<BlkioStats>,
+    pids_stats: Option<PidsStats>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct CpuStats {
+    #[serde(default)]
+    cpu_usage: CpuUsage,
+    system_cpu_usage: Option<u64>,
+    online_cpus: Option<u32>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct CpuUsage {
+    #[serde(default)]
+    total_usage: u64,
+    percpu_usage: Option<Vec<u64>>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct MemoryStats {
+    usage: Option<u64>,
+    limit: Option<u64>,
+    #[serde(default)]
+    stats: BTreeMap<String, u64>,
+}
+
+#[derive(Deserialize, Debug)]
+struct NetworkStats {
+    rx_bytes: u64,
+    #[serde(default)]
+    rx_packets: u64,
+    #[serde(default)]
+    rx_errors: u64,
+    #[serde(default)]
+    rx_dropped: u64,
+    tx_bytes: u64,
+    #[serde(default)]
+    tx_packets: u64,
+    #[serde(default)]
+    tx_errors: u64,
+    #[serde(default)]
+    tx_dropped: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct BlkioStats {
+    io_service_bytes_recursive: Option<Vec<BlkioEntry>>,
+}
+
+#[derive(Deserialize, Debug)]
+struct BlkioEntry {
+    op: String,
+    value: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct PidsStats {
+    current: Option<u16>,
+}
+
+pub fn parse_containers(json_str: &str) -> anyhow::Result<Vec<ContainerSummary>> {
+    Ok(serde_json::from_str(json_str)?)
+}
+
+/// Calculates values like `docker stats` does, but keeps byte values exact.
+pub fn parse(container: &ContainerSummary, json_str: &str) -> anyhow::Result<ContainerStats> {
+    let stats: StatsResponse = serde_json::from_str(json_str)?;
+    let container_name = container
+        .names
+        .first()
+        .map(|name| name.trim_start_matches('/').to_string())
+        .unwrap_or_default();
+    let labels = container.labels.clone().unwrap_or_default();
+    let service_name = SERVICE_NAME_LABELS
+        .iter()
+        .find_map(|label| labels.get(*label).cloned())
+        .unwrap_or_else(|| parse_service_name(&container_name));
+    let mem_usage = memory_usage(&stats.memory_stats);
+    let networks = stats.networks.unwrap_or_default();
+    let block_io = stats
+        .blkio_stats
+        .and_then(|blkio| blkio.io_service_bytes_recursive)
+        .unwrap_or_default();
+
+    Ok(ContainerStats {
+        container_id_short: container.id.chars().take(12).collect(),
+        container_id: container.id.clone(),
+        container_name,
+        service_name,
+        cpu_usage_in_percent: Some(cpu_usage_in_percent(&stats.cpu_stats, &stats.precpu_stats)),
+        mem_usage_in_percent: mem_usage
+            .zip(stats.memory_stats.limit)
+            .filter(|(_, limit)| *limit > 0)
+            .map(|(usage, limit)| (usage as f64 / limit as f64 * 100.0) as f32),
+        mem_usage: mem_usage.map(Byte::from_u64),
+        mem_limit: stats.memory_stats.limit.map(Byte::from_u64),
+        network_input: (!networks.is_empty()).then(|| Byte::from_u64(networks.values().map(|net| net.rx_bytes).sum())),
+        network_output: (!networks.is_empty()).then(|| Byte::from_u64(networks.values().map(|net| net.tx_bytes).sum())),
+        block_device_input: Some(Byte::from_u64(sum_of_operation(&block_io, "read"))),
+        block_device_output: Some(Byte::from_u64(sum_of_operation(&block_io, "write"))),
+        network_interfaces: networks
+            .iter()
+            .map(|(interface, net)| (interface.clone(), to_interface_stats(net)))
+            .collect(),
+        amount_of_pids: stats.pids_stats.and_then(|pids| pids.current),
+        labels,
+        byte_precision: BytePrecision::EXACT,
+        ..Default::default()
+    })
+}
+
+fn to_interface_stats(net: &NetworkStats) -> NetworkInterfaceStats {
+    NetworkInterfaceStats {
+        input_in_bytes: net.rx_bytes,
+        input_packets: net.rx_packets,
+        input_errors: net.rx_errors,
+        input_dropped: net.rx_dropped,
+        output_in_bytes: net.tx_bytes,
+        output_packets: net.tx_packets,
+        output_errors: net.tx_errors,
+        output_dropped: net.tx_dropped,
+    }
+}
+
+// Relative to one core; the engine samples `precpu_stats` about a second before `cpu_stats`.
+fn cpu_usage_in_percent(cpu: &CpuStats, precpu: &CpuStats) -> f32 {
+    let cpu_delta = cpu.cpu_usage.total_usage.saturating_sub(precpu.cpu_usage.total_usage) as f64;
+    let system_delta = cpu
+        .system_cpu_usage
+        .unwrap_or_default()
+        .saturating_sub(precpu.system_cpu_usage.unwrap_or_default()) as f64;
+    let online_cpus = cpu
+        .online_cpus
+        .or_else(|| cpu.cpu_usage.percpu_usage.as_ref().map(|percpu| percpu.len() as u32))
+        .unwrap_or(1);
+    if cpu_delta > 0.0 && system_delta > 0.0 {
+        (cpu_delta / system_delta * f64::from(online_cpus) * 100.0) as f32
+    } else {
+        0.0
+    }
+}
+
+// Without inactive page cache: `total_inactive_file` on cgroup v1, `inactive_file` on v2.
+fn memory_usage(memory: &MemoryStats) -> Option<u64> {
+    let usage = memory.usage?;
+    let inactive_file = memory
+        .stats
+        .get("total_inactive_file")
+        .or_else(|| memory.stats.get("inactive_file"))
+        .filter(|inactive_file| **inactive_file < usage)
+        .copied()
+        .unwrap_or_default();
+    Some(usage - inactive_file)
+}
+
+fn sum_of_operation(entries: &[BlkioEntry], operation: &str) -> u64 {
+    entries
+        .iter()
+        .filter(|entry| entry.op.eq_ignore_ascii_case(operation))
+        .map(|entry| entry.value)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_containers() -> Vec<ContainerSummary> {
+        parse_containers(include_str!("../../test-data/docker_api_containers.json")).unwrap()
+    }
+
+    #[test]
+    fn should_parse_containers() {
+        let actual = setup_containers();
+
+        assert_eq!(actual.len(), 2);
+        assert_eq!(actual[0].id, "4889ab0711ac17500a5de1c3d50eb6bfbc7eb367d349797f9fe8dcb92fd9e914");
+        assert_eq!(actual[1].labels, None);
+    }
+
+    #[test]
+    fn should_parse_exact_stats() {
+        let containers = setup_containers();
+
+        let actual = parse(&containers[0], include_str!("../../test-data/docker_api_stats.json")).unwrap();
+
+        assert_eq!(actual.container_id_short, "4889ab0711ac");
+        assert_eq!(actual.container_name, "b_10800414_3361262_f54e4ffc136d1344ee98993b36b9deeb");
+        assert_eq!(actual.service_name, "b");
+        assert_eq!(actual.cpu_usage_in_percent, Some(7.0));
+        assert_eq!(actual.mem_usage, Some(Byte::from_u64(334_071_168)));
+        assert_eq!(actual.mem_limit, Some(Byte::from_u64(1_073_741_824)));
+        assert_eq!(actual.mem_usage_in_percent, Some(31.112802));
+        assert_eq!(actual.network_input, Some(Byte::from_u64(541_235_567)));
+        assert_eq!(actual.network_output, Some(Byte::from_u64(680_125_456)));
+        assert_eq!(actual.block_device_input, Some(Byte::from_u64(1_048_576)));
+        assert_eq!(actual.block_device_output, Some(Byte::from_u64(2_097_152)));
+        assert_eq!(actual.amount_of_pids, Some(26));
+        assert_eq!(actual.network_interfaces.len(), 2);
+        assert_eq!(actual.network_interfaces["eth0"].input_packets, 412_345);
+        assert_eq!(actual.network_interfaces["eth1"].input_errors, 1);
+        assert_eq!(actual.network_interfaces["eth1"].input_dropped, 3);
+        assert_eq!(actual.network_interfaces["eth1"].output_in_bytes, 2000);
+        assert_eq!(actual.labels.get("io.balena.app-id"), Some(&"10800414".to_string()));
+        assert_eq!(actual.byte_precision, BytePrecision::EXACT);
+    }
+
+    #[test]
+    fn should_fall_back_to_service_name_from_container_name() {
+        let containers = setup_containers();
+
+        let actual = parse(&containers[1], "{}").unwrap();
+
+        assert_eq!(actual.service_name, "balena_supervisor");
+        assert_eq!(actual.cpu_usage_in_percent, Some(0.0));
+        assert_eq!(actual.mem_usage, None);
+        assert_eq!(actual.network_input, None);
+    }
+}
@@ -0,0 +1,120 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+/// Entry of `docker inspect` or `GET /containers/{id}/json`; only the fields of interest.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct InspectResponse {
+    id: String,
+    state: InspectState,
+    #[serde(default)]
+    restart_count: u32,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct InspectState {
+    status: String,
+    #[serde(default)]
+    running: bool,
+    #[serde(default, rename = "OOMKilled")]
+    oom_killed: bool,
+    #[serde(default)]
+    exit_code: i64,
+    started_at: Option<String>,
+    health: Option<InspectHealth>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct InspectHealth {
+    status: String,
+}
+
+/// State of a container as known to the engine, which `docker stats` doesn't show.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContainerInspection {
+    pub id: String,
+    /// E.g. `running`, `restarting` or `exited`.
+    pub state: String,
+    pub running: bool,
+    /// `starting`, `healthy` or `unhealthy`; `None` without health check.
+    pub health_status: Option<String>,
+    pub restart_count: u32,
+    /// `None` if never started.
+    pub started_at: Option<DateTime<Utc>>,
+    /// Of the last exit; kept by the engine while the container runs again.
+    pub exit_code: i64,
+    pub oom_killed: bool,
+}
+
+/// Output of `docker inspect`: a JSON array.
+pub fn parse_inspections(json_str: &str) -> anyhow::Result<Vec<ContainerInspection>> {
+    let responses: Vec<InspectResponse> = serde_json::from_str(json_str)?;
+    Ok(responses.into_iter().map(to_inspection).collect())
+}
+
+/// Response of `GET /containers/{id}/json`: a single object.
+pub fn parse_inspection(json_str: &str) -> anyhow::Result<ContainerInspection> {
+    Ok(to_inspection(serde_json::from_str(json_str)?))
+}
+
+fn to_inspection(response: InspectResponse) -> ContainerInspection {
+    let state = response.state;
+    ContainerInspection {
+        id: response.id,
+        state: state.status,
+        running: state.running,
+        health_status: state.health.map(|health| health.status),
+        restart_count: response.restart_count,
+        started_at: state.started_at.as_deref().and_then(parse_started_at),
+        exit_code: state.exit_code,
+        oom_killed: state.oom_killed,
+    }
+}
+
+// The engine reports `0001-01-01T00:00:00Z` for containers which were never started.
+fn parse_started_at(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|started_at| started_at.to_utc())
+        .filter(|started_at| started_at.timestamp() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_inspections() {
+        let actual = parse_inspections(include_str!("../../test-data/docker_inspect.json")).unwrap();
+
+        assert_eq!(
+            actual[0],
+            ContainerInspection {
+                id: "4889ab0711ac17500a5de1c3d50eb6bfbc7eb367d349797f9fe8dcb92fd9e914".to_string(),
+                state: "running".to_string(),
+                running: true,
+                health_status: Some("unhealthy".to_string()),
+                restart_count: 2,
+                started_at: DateTime::parse_from_rfc3339("2025-03-01T11:00:00.123456789Z").ok().map(|at| at.to_utc()),
+                exit_code: 137,
+                oom_killed: true,
+            }
+        );
+        assert_eq!(actual[1].state, "created");
+        assert_eq!(actual[1].health_status, None);
+        assert_eq!(actual[1].started_at, None);
+    }
+
+    #[test]
+    fn should_parse_single_inspection() {
+        let json = r#"{"Id": "4889ab0711ac", "State": {"Status": "exited", "ExitCode": 1}}"#;
+
+        let actual = parse_inspection(json).unwrap();
+
+        assert_eq!(actual.state, "exited");
+        assert_eq!(actual.exit_code, 1);
+        assert_eq!(actual.restart_count, 0);
+    }
+}
@@ -1 +1,6 @@
 pub mod balena_stats_json_parsers;
+pub mod crictl_stats_json_parsers;
+pub mod docker_api_df_parsers;
+pub mod docker_api_stats_parsers;
+pub mod docker_inspect_parsers;
+pub mod podman_stats_json_parsers;
\ No newline at end of file
@@ -0,0 +1,94 @@
+use crate::domain::ContainerStats;
+use crate::parsers::balena_stats_json_parsers::{parse_bytes_from_two_values, parse_or_log};
+use serde::Deserialize;
+
+/// Entry of `podman stats --no-stream --format json`.
+#[derive(Deserialize, Debug, PartialEq)]
+struct PodmanStatsAsStrings {
+    id: String,
+    name: String,
+    cpu_percent: String,
+    mem_usage: String,
+    mem_percent: String,
+    net_io: String,
+    block_io: String,
+    pids: String,
+}
+
+pub fn parse(json_str: &str) -> anyhow::Result<Vec<ContainerStats>> {
+    let parsed: Vec<PodmanStatsAsStrings> = serde_json::from_str(json_str)?;
+    Ok(parsed.into_iter().map(map).collect())
+}
+
+// Podman has no balena-style container names, so the container name is the service name.
+fn map(stat_strings: PodmanStatsAsStrings) -> ContainerStats {
+    let [network_input, network_output] = parse_bytes_from_two_values(&stat_strings.net_io);
+    let [mem_usage, mem_limit] = parse_bytes_from_two_values(&stat_strings.mem_usage);
+    let [block_device_input, block_device_output] = parse_bytes_from_two_values(&stat_strings.block_io);
+
+    ContainerStats {
+        container_id_short: stat_strings.id.chars().take(12).collect(),
+        container_id: stat_strings.id,
+        service_name: stat_strings.name.clone(),
+        container_name: stat_strings.name,
+        cpu_usage_in_percent: parse_or_log(&stat_strings.cpu_percent.replace("%", "")),
+        mem_usage_in_percent: parse_or_log(&stat_strings.mem_percent.replace("%", "")),
+        mem_usage: mem_usage.ok(),
+        mem_limit: mem_limit.ok(),
+        network_input: network_input.ok(),
+        network_output: network_output.ok(),
+        block_device_input: block_device_input.ok(),
+        block_device_output: block_device_output.ok(),
+        amount_of_pids: parse_or_log(&stat_strings.pids),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byte_unit::{Byte, Unit};
+
+    #[test]
+    fn should_parse_podman_stats() {
+        let json_str = include_str!("../../test-data/podman_stats.json");
+
+        let actual = parse(json_str).unwrap();
+
+        assert_eq!(actual.len(), 2);
+        assert_eq!(
+            actual[0],
+            ContainerStats {
+                container_id: "d6e9a1d6d3f2".to_string(),
+                container_id_short: "d6e9a1d6d3f2".to_string(),
+                container_name: "nginx".to_string(),
+                service_name: "nginx".to_string(),
+                cpu_usage_in_percent: Some(1.75),
+                mem_usage_in_percent: Some(31.12),
+                mem_usage: Byte::from_f64_with_unit(318.6, Unit::MB),
+                mem_limit: Byte::from_f64_with_unit(1.024, Unit::GB),
+                network_input: Byte::from_i64_with_unit(541, Unit::MB),
+                network_output: Byte::from_i64_with_unit(680, Unit::MB),
+                block_device_input: Byte::from_i64_with_unit(0, Unit::B),
+                block_device_output: Byte::from_f64_with_unit(4.096, Unit::KB),
+                amount_of_pids: Some(26),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn should_keep_unparsable_values_unavailable() {
+        let json_str = include_str!("../../test-data/podman_stats.json");
+
+        let actual = parse(json_str).unwrap();
+
+        assert_eq!(actual[1].cpu_usage_in_percent, None);
+        assert_eq!(actual[1].amount_of_pids, Some(2));
+    }
+
+    #[test]
+    fn should_fail_on_invalid_json() {
+        assert!(parse("{\"id\": \"d6e9a1d6d3f2\"}").is_err());
+    }
+}
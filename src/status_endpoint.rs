@@ -0,0 +1,232 @@
+use crate::exporters::sqlite_history::{find_tier, open_database, query_series, select_tier, SqliteHistoryConfig};
+use crate::util::http::{build_response, parse_message};
+use anyhow::anyhow;
+use chrono::Utc;
+use log::{info, warn};
+use serde_json::json;
+use std::collections::HashMap;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+const MAX_REQUEST_SIZE: usize = 8192;
+const DEFAULT_RANGE_IN_SECONDS: i64 = 3_600;
+
+#[derive(Debug, PartialEq)]
+struct Response {
+    status: u16,
+    body: String,
+}
+
+/// Minimal HTTP endpoint for technicians on site, e.g. `GET /history/{service_name}?from=<unix>&to=<unix>&tier=minute`.
+pub async fn serve(address: String, config: SqliteHistoryConfig) {
+    let listener = match TcpListener::bind(&address).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            warn!("Could not bind status endpoint to {}: {}", address, err);
+            return;
+        }
+    };
+    info!("Status endpoint listening on {}", address);
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                let config = config.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = handle_connection(stream, &config).await {
+                        warn!("Status endpoint request failed: {}", err);
+                    }
+                });
+            }
+            Err(err) => warn!("Status endpoint could not accept connection: {}", err),
+        }
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, config: &SqliteHistoryConfig) -> anyhow::Result<()> {
+    let mut buffer = vec![];
+    let mut chunk = [0; 1024];
+    let (head, _) = loop {
+        if let Some(message) = parse_message(&buffer)? {
+            break message;
+        }
+        if buffer.len() >= MAX_REQUEST_SIZE {
+            return Err(anyhow!("Request too large"));
+        }
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            return Err(anyhow!("Incomplete request"));
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    };
+
+    let request = head.start_line().to_string();
+    let config = config.clone();
+    let response = tokio::task::spawn_blocking(move || route(&request, &config)).await?;
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        _ => "Internal Server Error",
+    };
+    let status = format!("{} {}", response.status, reason);
+    stream
+        .write_all(&build_response(&status, Some("application/json"), response.body.as_bytes()))
+        .await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+fn route(request: &str, config: &SqliteHistoryConfig) -> Response {
+    let Some((method, path, query)) = parse_request_line(request) else {
+        return error_response(400, "Malformed request");
+    };
+    if method != "GET" {
+        return error_response(400, "Only GET is supported");
+    }
+
+    match path.strip_prefix("/history/") {
+        Some(service_name) if !service_name.is_empty() => history(service_name, &query, config)
+            .unwrap_or_else(|err| error_response(400, &err.to_string())),
+        _ => error_response(404, "Not found"),
+    }
+}
+
+fn history(service_name: &str, query: &HashMap<String, String>, config: &SqliteHistoryConfig) -> anyhow::Result<Response> {
+    let now = Utc::now().timestamp();
+    let to = parse_timestamp_param(query, "to")?.unwrap_or(now);
+    let from = parse_timestamp_param(query, "from")?.unwrap_or(to - DEFAULT_RANGE_IN_SECONDS);
+    let tier = match query.get("tier") {
+        Some(name) => find_tier(&config.tiers, name)?,
+        None => select_tier(&config.tiers, from, now).ok_or(anyhow!("No tiers configured"))?,
+    };
+
+    let connection = open_database(&config.database_path)?;
+    let series = query_series(&connection, service_name, tier, from, to)?;
+    let body = json!({
+        "service_name": service_name,
+        "tier": tier.name,
+        "resolution_in_seconds": tier.resolution_in_seconds,
+        "from": from,
+        "to": to,
+        "series": series,
+    });
+    Ok(Response {
+        status: 200,
+        body: body.to_string(),
+    })
+}
+
+fn parse_timestamp_param(query: &HashMap<String, String>, name: &str) -> anyhow::Result<Option<i64>> {
+    query
+        .get(name)
+        .map(|value| value.parse::<i64>().map_err(|_| anyhow!("{} must be a unix timestamp in seconds", name)))
+        .transpose()
+}
+
+fn parse_request_line(request: &str) -> Option<(String, String, HashMap<String, String>)> {
+    let mut parts = request.lines().next()?.split_whitespace();
+    let method = parts.next()?.to_string();
+    let target = parts.next()?;
+    let (path, query_string) = target.split_once('?').unwrap_or((target, ""));
+    let query = query_string
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (percent_decode(key, true), percent_decode(value, true)))
+        .collect();
+    Some((method, percent_decode(path, false), query))
+}
+
+// Invalid escapes are kept as they are; `+` is a space in query strings only.
+fn percent_decode(value: &str, is_query: bool) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = bytes
+            .get(index + 1..index + 3)
+            .filter(|_| bytes[index] == b'%')
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (escaped, bytes[index]) {
+            (Some(byte), _) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            (None, b'+') if is_query => {
+                decoded.push(b' ');
+                index += 1;
+            }
+            (None, byte) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+fn error_response(status: u16, message: &str) -> Response {
+    Response {
+        status,
+        body: json!({ "error": message }).to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exporters::sqlite_history::RetentionTier;
+
+    fn setup_config() -> SqliteHistoryConfig {
+        SqliteHistoryConfig {
+            database_path: ":memory:".to_string(),
+            tiers: vec![RetentionTier {
+                name: "raw".to_string(),
+                resolution_in_seconds: 1,
+                retention_in_seconds: 86_400,
+            }],
+            status_endpoint_address: None,
+        }
+    }
+
+    #[test]
+    fn should_parse_request_line() {
+        let (method, path, query) =
+            parse_request_line("GET /history/b?from=1&tier=raw HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+
+        assert_eq!(method, "GET");
+        assert_eq!(path, "/history/b");
+        assert_eq!(query.get("from").unwrap(), "1");
+        assert_eq!(query.get("tier").unwrap(), "raw");
+    }
+
+    #[test]
+    fn should_percent_decode_path_and_query() {
+        let (_, path, query) =
+            parse_request_line("GET /history/my%20service?tier=r%61w&note=a+b%2Bc&bad=%zz HTTP/1.1\r\n\r\n").unwrap();
+
+        assert_eq!(path, "/history/my service");
+        assert_eq!(query.get("tier").unwrap(), "raw");
+        assert_eq!(query.get("note").unwrap(), "a b+c");
+        assert_eq!(query.get("bad").unwrap(), "%zz");
+    }
+
+    #[test]
+    fn should_answer_history_request() {
+        let response = route("GET /history/b?from=0&to=10 HTTP/1.1\r\n\r\n", &setup_config());
+
+        assert_eq!(response.status, 200);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&response.body).unwrap(),
+            json!({"service_name": "b", "tier": "raw", "resolution_in_seconds": 1, "from": 0, "to": 10, "series": {}})
+        );
+    }
+
+    #[test]
+    fn should_reject_unknown_paths_and_params() {
+        assert_eq!(route("GET /metrics HTTP/1.1\r\n\r\n", &setup_config()).status, 404);
+        assert_eq!(route("GET /history/b?from=yesterday HTTP/1.1\r\n\r\n", &setup_config()).status, 400);
+        assert_eq!(route("GET /history/b?tier=week HTTP/1.1\r\n\r\n", &setup_config()).status, 400);
+        assert_eq!(route("POST /history/b HTTP/1.1\r\n\r\n", &setup_config()).status, 400);
+    }
+}
@@ -0,0 +1,151 @@
+use anyhow::anyhow;
+
+/// Start line and header lines of an HTTP/1.1 message.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Head(String);
+
+impl Head {
+    pub(crate) fn start_line(&self) -> &str {
+        self.0.lines().next().unwrap_or_default()
+    }
+
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.0
+            .lines()
+            .skip(1)
+            .filter_map(|line| line.split_once(':'))
+            .find(|(header, _)| header.trim().eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.trim())
+    }
+
+    /// Status code of a response.
+    pub(crate) fn status(&self) -> Option<u16> {
+        self.start_line().split_whitespace().nth(1)?.parse().ok()
+    }
+}
+
+/// Head and decoded body; `None` while `bytes` hold no complete message. Without `Content-Length` or chunked
+/// encoding, the body is the rest of `bytes`.
+pub(crate) fn parse_message(bytes: &[u8]) -> anyhow::Result<Option<(Head, Vec<u8>)>> {
+    let Some(head_end) = find(bytes, b"\r\n\r\n") else {
+        return Ok(None);
+    };
+    let head = Head(String::from_utf8_lossy(&bytes[..head_end]).to_string());
+    let body = &bytes[head_end + 4..];
+    let body = if head
+        .header("transfer-encoding")
+        .is_some_and(|encoding| encoding.eq_ignore_ascii_case("chunked"))
+    {
+        decode_chunked(body)?
+    } else if let Some(length) = head.header("content-length") {
+        let length: usize = length.parse().map_err(|_| anyhow!("Invalid Content-Length {}", length))?;
+        body.get(..length).map(<[u8]>::to_vec)
+    } else {
+        Some(body.to_vec())
+    };
+    Ok(body.map(|body| (head, body)))
+}
+
+pub(crate) fn build_response(status: &str, content_type: Option<&str>, body: &[u8]) -> Vec<u8> {
+    let content_type = content_type.map(|content_type| format!("Content-Type: {}\r\n", content_type));
+    let mut response = format!(
+        "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type.unwrap_or_default(),
+        body.len()
+    )
+    .into_bytes();
+    response.extend_from_slice(body);
+    response
+}
+
+// Decoded as bytes, as chunks may split multi-byte characters; `None` until the last chunk.
+fn decode_chunked(body: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+    let mut decoded = vec![];
+    let mut rest = body;
+    loop {
+        let Some(size_end) = find(rest, b"\r\n") else {
+            return Ok(None);
+        };
+        let size_line = String::from_utf8_lossy(&rest[..size_end]);
+        // Chunk extensions after `;` are allowed but not used.
+        let size = usize::from_str_radix(size_line.split(';').next().unwrap_or_default().trim(), 16)?;
+        if size == 0 {
+            return Ok(Some(decoded));
+        }
+        let chunk = &rest[size_end + 2..];
+        if chunk.len() < size + 2 {
+            return Ok(None);
+        }
+        decoded.extend_from_slice(&chunk[..size]);
+        rest = chunk[size..].strip_prefix(b"\r\n").ok_or(anyhow!("Chunk without CRLF"))?;
+    }
+}
+
+fn find(bytes: &[u8], pattern: &[u8]) -> Option<usize> {
+    bytes.windows(pattern.len()).position(|window| window == pattern)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::content_length("HTTP/1.1 200 OK\r\nContent-Length: 13\r\n\r\n[{\"Id\":\"a\"}]\n", "[{\"Id\":\"a\"}]\n")]
+    #[case::chunked(
+        "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\n[{\"Id\r\n8;ext=1\r\n\":\"a\"}]\n\r\n0\r\n\r\n",
+        "[{\"Id\":\"a\"}]\n"
+    )]
+    #[case::until_end("HTTP/1.1 404 Not Found\r\n\r\n{}", "{}")]
+    fn should_parse_message(#[case] message: &str, #[case] expected: &str) {
+        let (_, body) = parse_message(message.as_bytes()).unwrap().unwrap();
+
+        assert_eq!(body, expected.as_bytes());
+    }
+
+    #[test]
+    fn should_decode_multi_byte_characters_split_by_chunks() {
+        let message = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\n\"\xc3\r\n2\r\n\xbc\"\r\n0\r\n\r\n";
+
+        let (_, body) = parse_message(message).unwrap().unwrap();
+
+        assert_eq!(String::from_utf8(body).unwrap(), "\"ü\"");
+    }
+
+    #[rstest]
+    #[case::head("POST /v1/metrics HTTP/1.1\r\nContent-Length: 2")]
+    #[case::body("POST /v1/metrics HTTP/1.1\r\nContent-Length: 2\r\n\r\n[")]
+    #[case::chunk("HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nff\r\n[]")]
+    #[case::last_chunk("HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\n[]\r\n")]
+    fn should_wait_for_complete_message(#[case] message: &str) {
+        assert_eq!(parse_message(message.as_bytes()).unwrap(), None);
+    }
+
+    #[rstest]
+    #[case::chunk_size("HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n[]")]
+    #[case::content_length("HTTP/1.1 200 OK\r\nContent-Length: many\r\n\r\n[]")]
+    fn should_reject_invalid_message(#[case] message: &str) {
+        assert!(parse_message(message.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn should_read_head() {
+        let (head, _) = parse_message(b"HTTP/1.1 404 Not Found\r\ncontent-type: text/plain\r\n\r\n").unwrap().unwrap();
+
+        assert_eq!(head.start_line(), "HTTP/1.1 404 Not Found");
+        assert_eq!(head.status(), Some(404));
+        assert_eq!(head.header("Content-Type"), Some("text/plain"));
+        assert_eq!(head.header("Content-Length"), None);
+    }
+
+    #[test]
+    fn should_build_response() {
+        let actual = build_response("200 OK", Some("application/json"), b"{}");
+
+        assert_eq!(
+            String::from_utf8(actual).unwrap(),
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{}"
+        );
+    }
+}
@@ -1 +1,5 @@
-pub mod config;
\ No newline at end of file
+pub mod config;
+pub(crate) mod http;
+pub(crate) mod parallel;
+#[cfg(test)]
+pub(crate) mod test_support;
@@ -0,0 +1,49 @@
+use log::warn;
+use std::thread;
+
+/// Runs `task` for every item in its own thread, like `docker stats` requests containers; failed items are logged
+/// after `failure`, e.g. "Could not inspect container a", and skipped.
+pub(crate) fn map_in_parallel<T, R>(
+    items: &[T],
+    task: impl Fn(&T) -> anyhow::Result<R> + Sync,
+    failure: impl Fn(&T) -> String,
+) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+{
+    thread::scope(|scope| {
+        let handles: Vec<_> = items.iter().map(|item| (item, scope.spawn(|| task(item)))).collect();
+        handles
+            .into_iter()
+            .filter_map(|(item, handle)| match handle.join() {
+                Ok(Ok(result)) => Some(result),
+                Ok(Err(err)) => {
+                    warn!("{}: {}", failure(item), err);
+                    None
+                }
+                Err(_) => None,
+            })
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn should_keep_order_and_skip_failed_items() {
+        let actual = map_in_parallel(
+            &[1, 2, 3],
+            |item| match item {
+                2 => Err(anyhow!("gone")),
+                _ => Ok(item * 10),
+            },
+            |item| format!("Could not map {}", item),
+        );
+
+        assert_eq!(actual, vec![10, 30]);
+    }
+}
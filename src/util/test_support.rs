@@ -0,0 +1,78 @@
+use crate::util::http::{build_response, parse_message, Head};
+use std::env;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::os::unix::net::UnixListener;
+use std::path::PathBuf;
+use std::process;
+use std::thread;
+
+/// Request line and headers as sent, plus the body.
+pub(crate) struct ReceivedRequest {
+    pub(crate) head: Head,
+    pub(crate) body: Vec<u8>,
+}
+
+/// Empty directory for the files of `test_name`, unique per test process.
+pub(crate) fn temp_dir(test_name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("{}_{}", test_name, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Minimal HTTP stand-in answering a single request with `status`, e.g. `200 OK`.
+pub(crate) fn receive_request(
+    listener: TcpListener,
+    status: &'static str,
+    response_body: Vec<u8>,
+) -> thread::JoinHandle<ReceivedRequest> {
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let (head, body) = read_request(&mut stream);
+        stream.write_all(&build_response(status, None, &response_body)).unwrap();
+        ReceivedRequest { head, body }
+    })
+}
+
+/// Minimal Docker API stand-in on a unix socket in `temp_dir(test_name)`, answering `count` requests with the body
+/// of their path, otherwise 404. Returns the socket path and the request lines.
+pub(crate) fn serve_unix_socket(
+    test_name: &str,
+    count: usize,
+    responses: Vec<(&'static str, &'static str)>,
+) -> (PathBuf, thread::JoinHandle<Vec<String>>) {
+    let socket_path = temp_dir(test_name).join("docker.sock");
+    let listener = UnixListener::bind(&socket_path).unwrap();
+    let server = thread::spawn(move || {
+        (0..count)
+            .map(|_| {
+                let (mut stream, _) = listener.accept().unwrap();
+                let (head, _) = read_request(&mut stream);
+                let path = head.start_line().split_whitespace().nth(1).unwrap_or_default();
+                let (status, body) = responses
+                    .iter()
+                    .find(|(response_path, _)| *response_path == path)
+                    .map(|(_, body)| ("200 OK", *body))
+                    .unwrap_or(("404 Not Found", "{\"message\":\"No such container\"}"));
+                stream.write_all(&build_response(status, Some("application/json"), body.as_bytes())).unwrap();
+                head.start_line().to_string()
+            })
+            .collect()
+    });
+    (socket_path, server)
+}
+
+fn read_request(stream: &mut impl Read) -> (Head, Vec<u8>) {
+    let mut bytes = vec![];
+    let mut chunk = [0; 4096];
+    loop {
+        if let Some(message) = parse_message(&bytes).unwrap() {
+            return message;
+        }
+        let read = stream.read(&mut chunk).unwrap();
+        assert!(read > 0, "Connection closed before the request was complete");
+        bytes.extend_from_slice(&chunk[..read]);
+    }
+}